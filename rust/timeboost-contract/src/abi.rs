@@ -0,0 +1,179 @@
+//! Contract ABIs compiled into the crate, for tooling that needs them without the typed bindings
+
+use std::collections::BTreeMap;
+
+use alloy::{
+    primitives::{B256, Selector},
+    sol_types::{SolCall, SolEvent},
+};
+
+use crate::KeyManager;
+
+/// JSON ABI of the KeyManager contract, the same artifact the bindings were generated from.
+/// Regenerate together with the bindings via `just gen-rust-bindings`.
+pub fn key_manager_abi() -> &'static str {
+    include_str!("../abi/KeyManager.json")
+}
+
+/// `(name, selector)` for each of the binding types `$ty`, the name being the signature up to
+/// its parameter list
+macro_rules! by_name {
+    ($trait:ident::$selector:ident: $($ty:ident),* $(,)?) => {
+        BTreeMap::from([$((
+            name(<KeyManager::$ty as $trait>::SIGNATURE),
+            <KeyManager::$ty as $trait>::$selector.into(),
+        )),*])
+    };
+}
+
+fn name(signature: &'static str) -> &'static str {
+    signature
+        .split_once('(')
+        .map_or(signature, |(name, _)| name)
+}
+
+/// Selectors of the KeyManager functions by name, e.g. for decoding calldata in traces. The
+/// KeyManager doesn't overload functions, so names are unique.
+pub fn key_manager_selectors() -> BTreeMap<&'static str, Selector> {
+    by_name!(SolCall::SELECTOR:
+        committeesCall,
+        currentCommitteeIdCall,
+        getCommitteeByIdCall,
+        initializeCall,
+        managerCall,
+        nextCommitteeIdCall,
+        ownerCall,
+        proxiableUUIDCall,
+        pruneUntilCall,
+        renounceOwnershipCall,
+        setManagerCall,
+        setNextCommitteeCall,
+        setThresholdEncryptionKeyCall,
+        thresholdEncryptionKeyCall,
+        transferOwnershipCall,
+        upgradeToCall,
+        upgradeToAndCallCall,
+        verifyQuorumSignaturesCall,
+    )
+}
+
+/// Topic hashes (`topic0`) of the events the KeyManager emits by name, including those of the
+/// proxy and upgrade contracts it inherits
+pub fn key_manager_event_topics() -> BTreeMap<&'static str, B256> {
+    by_name!(SolEvent::SIGNATURE_HASH:
+        AdminChanged,
+        BeaconUpgraded,
+        CommitteeCreated,
+        CommitteesPruned,
+        Initialized,
+        ManagerChanged,
+        OwnershipTransferred,
+        ThresholdEncryptionKeyUpdated,
+        Upgraded,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{key_manager_abi, key_manager_event_topics, key_manager_selectors};
+    use crate::KeyManager::{self, KeyManagerCalls, KeyManagerEvents};
+    use alloy::{
+        json_abi::JsonAbi,
+        primitives::keccak256,
+        sol_types::{SolCall, SolEvent},
+    };
+
+    #[test]
+    fn test_key_manager_abi_matches_bindings() {
+        let abi: JsonAbi = serde_json::from_str(key_manager_abi()).unwrap();
+
+        let selector = |name: &str| abi.function(name).unwrap()[0].selector();
+        assert_eq!(
+            selector("setNextCommittee"),
+            KeyManager::setNextCommitteeCall::SELECTOR
+        );
+        assert_eq!(
+            selector("getCommitteeById"),
+            KeyManager::getCommitteeByIdCall::SELECTOR
+        );
+        assert_eq!(selector("initialize"), KeyManager::initializeCall::SELECTOR);
+
+        let topic = |name: &str| abi.event(name).unwrap()[0].selector();
+        assert_eq!(
+            topic("CommitteeCreated"),
+            KeyManager::CommitteeCreated::SIGNATURE_HASH
+        );
+    }
+
+    #[test]
+    fn test_key_manager_selectors() {
+        let selectors = key_manager_selectors();
+        let topics = key_manager_event_topics();
+        // every function and event of the bindings
+        let mut all: Vec<_> = selectors.values().map(|s| s.0).collect();
+        all.sort();
+        let mut expected = KeyManagerCalls::SELECTORS.to_vec();
+        expected.sort();
+        assert_eq!(all, expected);
+        let mut all: Vec<_> = topics.values().map(|t| t.0).collect();
+        all.sort();
+        let mut expected = KeyManagerEvents::SELECTORS.to_vec();
+        expected.sort();
+        assert_eq!(all, expected);
+
+        // and of the JSON ABI
+        let abi: JsonAbi = serde_json::from_str(key_manager_abi()).unwrap();
+        assert_eq!(selectors.len(), abi.functions().count());
+        for f in abi.functions() {
+            assert_eq!(selectors[f.name.as_str()], f.selector(), "{}", f.name);
+        }
+        assert_eq!(topics.len(), abi.events().count());
+        for e in abi.events() {
+            assert_eq!(topics[e.name.as_str()], e.selector(), "{}", e.name);
+        }
+
+        // known signatures, so that a changed one doesn't go unnoticed
+        let selector = |signature: &str| keccak256(signature)[..4].to_vec();
+        for (name, signature) in [
+            ("getCommitteeById", "getCommitteeById(uint64)"),
+            ("manager", "manager()"),
+            ("nextCommitteeId", "nextCommitteeId()"),
+            ("pruneUntil", "pruneUntil(uint64)"),
+            ("setManager", "setManager(address)"),
+            (
+                "setNextCommittee",
+                "setNextCommittee(uint64,(bytes,bytes,bytes,address,string,string)[])",
+            ),
+            (
+                "setThresholdEncryptionKey",
+                "setThresholdEncryptionKey(bytes)",
+            ),
+            (
+                "verifyQuorumSignatures",
+                "verifyQuorumSignatures(bytes32,bytes[])",
+            ),
+        ] {
+            assert_eq!(selectors[name].to_vec(), selector(signature), "{name}");
+        }
+        assert_eq!(selectors["manager"].to_string(), "0x481c6a75");
+        for (name, signature) in [
+            ("CommitteeCreated", "CommitteeCreated(uint64)"),
+            ("CommitteesPruned", "CommitteesPruned(uint64,uint64)"),
+            ("ManagerChanged", "ManagerChanged(address,address)"),
+            (
+                "ThresholdEncryptionKeyUpdated",
+                "ThresholdEncryptionKeyUpdated(bytes)",
+            ),
+        ] {
+            assert_eq!(topics[name], keccak256(signature), "{name}");
+        }
+        assert_eq!(
+            selectors["setNextCommittee"],
+            KeyManager::setNextCommitteeCall::SELECTOR
+        );
+        assert_eq!(
+            topics["CommitteeCreated"],
+            KeyManager::CommitteeCreated::SIGNATURE_HASH
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Checksummed (de)serialization of addresses
+//!
+//! alloy serializes an [`Address`] as lowercase hex. The addresses in this crate's serializable
+//! types go through here instead, so they are always written EIP-55 checksummed (e.g.
+//! `0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266`) and read back whatever their casing.
+//!
+//! Use [`AddressHex`] as a field type, or this module with
+//! `#[serde(with = "timeboost_contract::address_hex")]` on an `Address` field
+//! ([`option`] for an `Option<Address>`).
+
+use std::{fmt, str::FromStr};
+
+use alloy::primitives::{Address, hex::FromHexError};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An [`Address`] serialized checksummed, see the [module docs](self)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AddressHex(pub Address);
+
+impl From<Address> for AddressHex {
+    fn from(address: Address) -> Self {
+        Self(address)
+    }
+}
+
+impl From<AddressHex> for Address {
+    fn from(address: AddressHex) -> Self {
+        address.0
+    }
+}
+
+impl fmt::Display for AddressHex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.to_checksum(None))
+    }
+}
+
+/// parses any casing, without checking the checksum
+impl FromStr for AddressHex {
+    type Err = FromHexError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(s.parse()?))
+    }
+}
+
+impl Serialize for AddressHex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AddressHex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize(deserializer).map(Self)
+    }
+}
+
+pub fn serialize<S: Serializer>(address: &Address, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&address.to_checksum(None))
+}
+
+/// any casing is accepted, a checksum isn't verified
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Address, D::Error> {
+    Address::deserialize(deserializer)
+}
+
+/// The same for an `Option<Address>`, `None` (de)serialized as null
+pub mod option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(
+        address: &Option<Address>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        address.map(AddressHex).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Address>, D::Error> {
+        Ok(Option::<AddressHex>::deserialize(deserializer)?.map(Into::into))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AddressHex;
+    use alloy::primitives::{Address, address};
+    use serde::{Deserialize, Serialize};
+
+    const CHECKSUMMED: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Fields {
+        #[serde(with = "super")]
+        plain: Address,
+        #[serde(with = "super::option")]
+        optional: Option<Address>,
+        wrapped: AddressHex,
+    }
+
+    #[test]
+    fn test_address_hex() {
+        let addr = address!("0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+        assert_eq!(AddressHex(addr).to_string(), CHECKSUMMED);
+        assert_eq!(
+            serde_json::to_string(&AddressHex(addr)).unwrap(),
+            format!("\"{CHECKSUMMED}\"")
+        );
+
+        // any casing, even an invalid checksum, reads back the same address
+        let upper = format!("0x{}", CHECKSUMMED[2..].to_uppercase());
+        for s in [
+            CHECKSUMMED,
+            &CHECKSUMMED.to_lowercase(),
+            &upper,
+            "0xF39fd6e51aad88F6F4ce6aB8827279cffFb92266",
+        ] {
+            let parsed: AddressHex = serde_json::from_str(&format!("\"{s}\"")).unwrap();
+            assert_eq!(parsed.0, addr);
+            assert_eq!(s.parse::<AddressHex>().unwrap(), parsed);
+            assert_eq!(
+                serde_json::to_string(&parsed).unwrap(),
+                format!("\"{CHECKSUMMED}\"")
+            );
+        }
+        assert!(serde_json::from_str::<AddressHex>("\"0x1234\"").is_err());
+
+        let fields = Fields {
+            plain: addr,
+            optional: None,
+            wrapped: AddressHex(Address::with_last_byte(0xab)),
+        };
+        let json = serde_json::to_value(&fields).unwrap();
+        assert_eq!(json["plain"], CHECKSUMMED);
+        assert!(json["optional"].is_null());
+        assert_eq!(serde_json::from_value::<Fields>(json).unwrap(), fields);
+
+        let json = serde_json::json!({
+            "plain": upper,
+            "optional": CHECKSUMMED.to_lowercase(),
+            "wrapped": "0x00000000000000000000000000000000000000AB",
+        });
+        let read: Fields = serde_json::from_value(json).unwrap();
+        assert_eq!(read.plain, addr);
+        assert_eq!(read.optional, Some(addr));
+        assert_eq!(read.wrapped, fields.wrapped);
+        assert_eq!(
+            serde_json::to_value(&read).unwrap()["optional"],
+            CHECKSUMMED
+        );
+    }
+}
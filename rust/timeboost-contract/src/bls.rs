@@ -0,0 +1,122 @@
+//! BLS attestation of committee state
+//!
+//! Scheme: BLS over BLS12-381 in the "minimal public key size" variant, as standardized in the
+//! IETF BLS signature draft with proof-of-possession, i.e. public keys are compressed 48 byte G1
+//! points, signatures compressed 96 byte G2 points and messages are hashed to G2 with
+//! [`DST`]. Members attest to the committee by signing its [`CommitteeSol::identity_hash`],
+//! their `sigKey` has to be their BLS public key.
+//!
+//! Fast aggregate verification is only sound if every public key has a verified proof of
+//! possession, which is assumed to be checked when the committee is assembled.
+
+use alloy::primitives::{B256, Bytes};
+use anyhow::{Context, Result, anyhow, ensure};
+use blst::{
+    BLST_ERROR,
+    min_pk::{AggregateSignature, PublicKey, SecretKey, Signature},
+};
+
+use crate::CommitteeSol;
+
+/// Domain separation tag of the proof-of-possession ciphersuite
+pub const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// The message members sign to attest to `committee`
+pub fn attestation_message(committee: &CommitteeSol) -> B256 {
+    committee.identity_hash()
+}
+
+/// Sign the attestation of `committee` with a member's BLS secret key
+pub fn sign_committee(secret: &SecretKey, committee: &CommitteeSol) -> Bytes {
+    let msg = attestation_message(committee);
+    Bytes::copy_from_slice(&secret.sign(msg.as_slice(), DST, &[]).to_bytes())
+}
+
+/// Aggregate compressed member signatures into a single compressed signature
+pub fn aggregate_signatures(signatures: &[Bytes]) -> Result<Bytes> {
+    ensure!(!signatures.is_empty(), "no signatures to aggregate");
+    let signatures = signatures
+        .iter()
+        .enumerate()
+        .map(|(i, sig)| {
+            Signature::from_bytes(sig).map_err(|err| anyhow!("invalid signature {i}: {err:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let refs = signatures.iter().collect::<Vec<_>>();
+    let aggregate = AggregateSignature::aggregate(&refs, true)
+        .map_err(|err| anyhow!("failed to aggregate signatures: {err:?}"))?;
+    Ok(Bytes::copy_from_slice(&aggregate.to_signature().to_bytes()))
+}
+
+/// Verify that the members at `signers` (indices into `committee.members`) produced `aggregate`
+/// over the attestation of `committee`. Whether enough members signed is up to the caller.
+pub fn verify_aggregate(
+    committee: &CommitteeSol,
+    signers: &[usize],
+    aggregate: &Bytes,
+) -> Result<bool> {
+    ensure!(!signers.is_empty(), "no signers");
+    let keys = signers
+        .iter()
+        .map(|&i| {
+            let member = committee
+                .members
+                .get(i)
+                .with_context(|| format!("signer {i} is not a committee member"))?;
+            PublicKey::from_bytes(&member.sigKey)
+                .map_err(|err| anyhow!("sig key of member {i} is not a BLS key: {err:?}"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let refs = keys.iter().collect::<Vec<_>>();
+    let signature = Signature::from_bytes(aggregate)
+        .map_err(|err| anyhow!("invalid aggregate signature: {err:?}"))?;
+
+    let msg = attestation_message(committee);
+    let result = signature.fast_aggregate_verify(true, msg.as_slice(), DST, &refs);
+    Ok(result == BLST_ERROR::BLST_SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aggregate_signatures, sign_committee, verify_aggregate};
+    use crate::{CommitteeMemberSol, CommitteeSol};
+    use alloy::primitives::{Bytes, U256};
+    use blst::min_pk::SecretKey;
+    use rand::prelude::*;
+
+    #[test]
+    fn test_aggregate_attestation() {
+        let secrets = (0..4)
+            .map(|_| SecretKey::key_gen(&rand::rng().random::<[u8; 32]>(), &[]).unwrap())
+            .collect::<Vec<_>>();
+        let members = secrets
+            .iter()
+            .map(|sk| CommitteeMemberSol {
+                sigKey: Bytes::copy_from_slice(&sk.sk_to_pk().to_bytes()),
+                ..CommitteeMemberSol::random()
+            })
+            .collect();
+        let committee = CommitteeSol {
+            id: 1,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members,
+        };
+
+        let signers = [0, 2, 3];
+        let signatures = signers
+            .iter()
+            .map(|&i| sign_committee(&secrets[i], &committee))
+            .collect::<Vec<_>>();
+        let aggregate = aggregate_signatures(&signatures).unwrap();
+        assert!(verify_aggregate(&committee, &signers, &aggregate).unwrap());
+
+        // wrong signer set, or a different committee
+        assert!(!verify_aggregate(&committee, &[0, 1, 3], &aggregate).unwrap());
+        let mut other = committee.clone();
+        other.effectiveTimestamp = 101;
+        assert!(!verify_aggregate(&other, &signers, &aggregate).unwrap());
+
+        assert!(verify_aggregate(&committee, &[9], &aggregate).is_err());
+    }
+}
@@ -0,0 +1,455 @@
+//! Build committees from operator node descriptions
+
+use std::{collections::HashSet, fmt};
+
+use alloy::{
+    primitives::{Address, Bytes},
+    providers::Provider,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CommitteeMemberError, CommitteeMemberSol,
+    KeyManager::{KeyManagerInstance, setNextCommitteeCall},
+};
+
+/// A node as described in operator configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeDescriptor {
+    pub sig_key: Bytes,
+    pub dh_key: Bytes,
+    pub dkg_key: Bytes,
+    #[serde(with = "crate::address_hex")]
+    pub sig_key_address: Address,
+    /// `ip:port`, `[ipv6]:port` or `hostname:port`, see [`Endpoint`](crate::Endpoint)
+    pub network_address: String,
+    /// `ip:port`, `[ipv6]:port` or `hostname:port`, see [`Endpoint`](crate::Endpoint)
+    pub batch_poster_address: String,
+}
+
+impl From<NodeDescriptor> for CommitteeMemberSol {
+    fn from(node: NodeDescriptor) -> Self {
+        Self {
+            sigKey: node.sig_key,
+            dhKey: node.dh_key,
+            dkgKey: node.dkg_key,
+            sigKeyAddress: node.sig_key_address,
+            networkAddress: node.network_address,
+            batchPosterAddress: node.batch_poster_address,
+        }
+    }
+}
+
+impl From<CommitteeMemberSol> for NodeDescriptor {
+    fn from(member: CommitteeMemberSol) -> Self {
+        Self {
+            sig_key: member.sigKey,
+            dh_key: member.dhKey,
+            dkg_key: member.dkgKey,
+            sig_key_address: member.sigKeyAddress,
+            network_address: member.networkAddress,
+            batch_poster_address: member.batchPosterAddress,
+        }
+    }
+}
+
+/// Order of the members of a built committee, which matters to index-based roles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemberOrder {
+    /// as added
+    #[default]
+    Insertion,
+    /// ascending sig key address
+    SigKeyAddress,
+    /// ascending sig key bytes
+    SigKey,
+}
+
+/// A problem with a committee, nodes are identified by the order they were added in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitteeProblem {
+    /// the committee has no members
+    Empty,
+    /// there's no effective timestamp to build a `setNextCommittee` call with
+    NoEffectiveTimestamp,
+    /// a node the contract would take but nobody could use, see [`CommitteeMemberSol::validate`]
+    Member {
+        index: usize,
+        error: CommitteeMemberError,
+    },
+    ZeroSigKeyAddress {
+        index: usize,
+    },
+    DuplicateSigKey {
+        index: usize,
+        key: Bytes,
+    },
+    DuplicateDhKey {
+        index: usize,
+        key: Bytes,
+    },
+    DuplicateSigKeyAddress {
+        index: usize,
+        address: Address,
+    },
+}
+
+impl fmt::Display for CommitteeProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "committee has no members"),
+            Self::NoEffectiveTimestamp => write!(f, "no effective timestamp"),
+            Self::Member { index, error } => write!(f, "node {index}: {error}"),
+            Self::ZeroSigKeyAddress { index } => write!(f, "node {index}: zero sig key address"),
+            Self::DuplicateSigKey { index, key } => {
+                write!(f, "node {index}: duplicate sig key {key}")
+            }
+            Self::DuplicateDhKey { index, key } => {
+                write!(f, "node {index}: duplicate dh key {key}")
+            }
+            Self::DuplicateSigKeyAddress { index, address } => {
+                write!(f, "node {index}: duplicate sig key address {address}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommitteeProblem {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Member { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Every problem found in a committee, one entry per problem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitteeValidationError {
+    pub errors: Vec<CommitteeProblem>,
+}
+
+impl fmt::Display for CommitteeValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid committee: ")?;
+        for (i, err) in self.errors.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            err.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CommitteeValidationError {}
+
+/// What registering the built committee would result in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitteePreview {
+    /// id the contract will assign, unless another committee is registered first
+    pub id: u64,
+    pub size: usize,
+    /// number of valid signatures `verifyQuorumSignatures` requires
+    pub threshold: usize,
+}
+
+/// Collects node descriptors and turns them into validated committee members
+#[derive(Debug, Clone, Default)]
+pub struct CommitteeBuilder {
+    nodes: Vec<NodeDescriptor>,
+    order: MemberOrder,
+    effective_timestamp: Option<u64>,
+}
+
+impl CommitteeBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_node(mut self, node: NodeDescriptor) -> Self {
+        self.nodes.push(node);
+        self
+    }
+
+    pub fn with_nodes(mut self, nodes: impl IntoIterator<Item = NodeDescriptor>) -> Self {
+        self.nodes.extend(nodes);
+        self
+    }
+
+    pub fn with_member(self, member: CommitteeMemberSol) -> Self {
+        self.with_node(member.into())
+    }
+
+    pub fn with_members(self, members: impl IntoIterator<Item = CommitteeMemberSol>) -> Self {
+        self.with_nodes(members.into_iter().map(Into::into))
+    }
+
+    pub fn with_order(mut self, order: MemberOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn with_effective_timestamp(mut self, timestamp: u64) -> Self {
+        self.effective_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Check all nodes, collecting every error rather than stopping at the first: each node's
+    /// key lengths and endpoints as [`CommitteeMemberSol::validate`] does, then what the
+    /// committee as a whole needs
+    pub fn validate(&self) -> Result<(), CommitteeValidationError> {
+        let mut errors = Vec::new();
+        if self.nodes.is_empty() {
+            errors.push(CommitteeProblem::Empty);
+        }
+
+        let mut sig_keys = HashSet::new();
+        let mut dh_keys = HashSet::new();
+        let mut addrs = HashSet::new();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let member = CommitteeMemberSol::from(node.clone());
+            if let Err(member_errors) = member.validate() {
+                errors.extend(
+                    member_errors
+                        .into_iter()
+                        .map(|error| CommitteeProblem::Member { index, error }),
+                );
+            }
+            if node.sig_key_address.is_zero() {
+                errors.push(CommitteeProblem::ZeroSigKeyAddress { index });
+            }
+            if !node.sig_key.is_empty() && !sig_keys.insert(&node.sig_key) {
+                errors.push(CommitteeProblem::DuplicateSigKey {
+                    index,
+                    key: node.sig_key.clone(),
+                });
+            }
+            if !node.dh_key.is_empty() && !dh_keys.insert(&node.dh_key) {
+                errors.push(CommitteeProblem::DuplicateDhKey {
+                    index,
+                    key: node.dh_key.clone(),
+                });
+            }
+            if !node.sig_key_address.is_zero() && !addrs.insert(node.sig_key_address) {
+                errors.push(CommitteeProblem::DuplicateSigKeyAddress {
+                    index,
+                    address: node.sig_key_address,
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(CommitteeValidationError { errors })
+        }
+    }
+
+    /// Validate and convert the nodes, in the configured [`MemberOrder`]. Node indices in
+    /// validation errors are in the order nodes were added.
+    pub fn build(self) -> Result<Vec<CommitteeMemberSol>, CommitteeValidationError> {
+        self.validate()?;
+        Ok(self.into_members())
+    }
+
+    /// Like [`Self::build`], as the arguments of `setNextCommittee`, which needs an effective
+    /// timestamp to have been set. A missing timestamp is reported after the node problems.
+    pub fn build_call(self) -> Result<setNextCommitteeCall, CommitteeValidationError> {
+        let mut errors = self.validate().err().map(|e| e.errors).unwrap_or_default();
+        if self.effective_timestamp.is_none() {
+            errors.push(CommitteeProblem::NoEffectiveTimestamp);
+        }
+        match self.effective_timestamp {
+            Some(timestamp) if errors.is_empty() => Ok(setNextCommitteeCall {
+                effectiveTimestamp: timestamp,
+                members: self.into_members(),
+            }),
+            _ => Err(CommitteeValidationError { errors }),
+        }
+    }
+
+    /// the nodes as members in the configured [`MemberOrder`], without validating them
+    fn into_members(self) -> Vec<CommitteeMemberSol> {
+        let mut nodes = self.nodes;
+        match self.order {
+            MemberOrder::Insertion => {}
+            MemberOrder::SigKeyAddress => nodes.sort_by_key(|n| n.sig_key_address),
+            MemberOrder::SigKey => nodes.sort_by(|a, b| a.sig_key.cmp(&b.sig_key)),
+        }
+        nodes.into_iter().map(Into::into).collect()
+    }
+
+    /// Id and quorum threshold the committee would get if registered now
+    pub async fn preview<P: Provider>(
+        &self,
+        contract: &KeyManagerInstance<P>,
+    ) -> Result<CommitteePreview, alloy::contract::Error> {
+        let size = self.nodes.len();
+        Ok(CommitteePreview {
+            id: contract.nextCommitteeId().call().await?,
+            size,
+            threshold: 2 * size / 3 + 1,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommitteeBuilder, CommitteeProblem, MemberOrder, NodeDescriptor};
+    use crate::{CommitteeMemberError, CommitteeMemberSol, KeyError};
+    use alloy::{
+        primitives::{Address, Bytes},
+        sol_types::SolCall,
+    };
+
+    fn node(i: u8) -> NodeDescriptor {
+        let m = CommitteeMemberSol::random();
+        NodeDescriptor {
+            sig_key: m.sigKey,
+            dh_key: m.dhKey,
+            dkg_key: m.dkgKey,
+            sig_key_address: Address::with_last_byte(i + 1),
+            network_address: format!("node{i}.example.com:8000"),
+            batch_poster_address: format!("10.0.0.{i}:9000"),
+        }
+    }
+
+    #[test]
+    fn test_committee_builder() {
+        let members = CommitteeBuilder::new()
+            .with_nodes((0..4).map(node))
+            .build()
+            .unwrap();
+        assert_eq!(members.len(), 4);
+        assert_eq!(members[2].networkAddress, "node2.example.com:8000");
+
+        let first = node(0);
+        let dup = NodeDescriptor {
+            sig_key: first.sig_key.clone(),
+            ..node(1)
+        };
+        let bad = NodeDescriptor {
+            dkg_key: Bytes::new(),
+            network_address: "node.example.com".to_string(),
+            batch_poster_address: "10.0.0.1:0".to_string(),
+            ..node(2)
+        };
+        let err = CommitteeBuilder::new()
+            .with_nodes([first, dup, bad])
+            .build()
+            .unwrap_err();
+        assert_eq!(err.errors.len(), 4, "{err}");
+        assert!(matches!(
+            err.errors[0],
+            CommitteeProblem::DuplicateSigKey { index: 1, .. }
+        ));
+        assert!(err.errors[1..].iter().all(|e| matches!(
+            e,
+            CommitteeProblem::Member {
+                index: 2,
+                error: CommitteeMemberError::Key(_) | CommitteeMemberError::Address(_)
+            }
+        )));
+        assert!(err.to_string().contains("node 1: duplicate sig key"));
+
+        // a key of the wrong length would revert on chain
+        let long = NodeDescriptor {
+            dh_key: Bytes::from_static(&[1; 33]),
+            ..node(0)
+        };
+        let err = CommitteeBuilder::new().with_node(long).build().unwrap_err();
+        assert_eq!(
+            err.errors,
+            [CommitteeProblem::Member {
+                index: 0,
+                error: CommitteeMemberError::Key(KeyError {
+                    field: "dhKey",
+                    expected: 32,
+                    actual: 33
+                })
+            }]
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid committee: node 0: dhKey must be 32 bytes, got 33"
+        );
+
+        assert_eq!(
+            CommitteeBuilder::new().build().unwrap_err().errors,
+            [CommitteeProblem::Empty]
+        );
+    }
+
+    #[test]
+    fn test_committee_builder_call() {
+        let nodes = [node(2), node(0), node(1)];
+        let by_address = CommitteeBuilder::new()
+            .with_nodes(nodes.clone())
+            .with_order(MemberOrder::SigKeyAddress)
+            .build()
+            .unwrap();
+        let addrs: Vec<_> = by_address.iter().map(|m| m.sigKeyAddress).collect();
+        assert_eq!(
+            addrs,
+            (1..=3).map(Address::with_last_byte).collect::<Vec<_>>()
+        );
+        let by_key = CommitteeBuilder::new()
+            .with_nodes(nodes.clone())
+            .with_order(MemberOrder::SigKey)
+            .build()
+            .unwrap();
+        assert!(by_key.is_sorted_by(|a, b| a.sigKey <= b.sigKey));
+
+        let members: Vec<CommitteeMemberSol> = nodes.iter().cloned().map(Into::into).collect();
+        let call = CommitteeBuilder::new()
+            .with_members(members.clone())
+            .with_effective_timestamp(100)
+            .build_call()
+            .unwrap();
+        assert_eq!(call.effectiveTimestamp, 100);
+        assert_eq!(call.members, members);
+        let encoded = call.abi_encode();
+        assert_eq!(
+            super::setNextCommitteeCall::abi_decode(&encoded).unwrap(),
+            call
+        );
+
+        let err = CommitteeBuilder::new()
+            .with_members(members.clone())
+            .with_member(members[0].clone())
+            .build_call()
+            .unwrap_err();
+        assert_eq!(err.errors.len(), 4, "{err}");
+        assert_eq!(err.errors[3], CommitteeProblem::NoEffectiveTimestamp);
+        let err = CommitteeBuilder::new()
+            .with_effective_timestamp(100)
+            .build_call()
+            .unwrap_err();
+        assert_eq!(err.errors, [CommitteeProblem::Empty]);
+
+        // key lengths are checked on this path too, with the timestamp set or not
+        let mut short = members[0].clone();
+        short.sigKey = Bytes::from_static(&[1; 47]);
+        let builder = CommitteeBuilder::new().with_member(short);
+        let err = builder.clone().build_call().unwrap_err();
+        assert!(matches!(
+            err.errors[..],
+            [
+                CommitteeProblem::Member {
+                    index: 0,
+                    error: CommitteeMemberError::Key(KeyError {
+                        field: "sigKey",
+                        ..
+                    })
+                },
+                CommitteeProblem::NoEffectiveTimestamp
+            ]
+        ));
+        let err = builder
+            .with_effective_timestamp(100)
+            .build_call()
+            .unwrap_err();
+        assert_eq!(err.errors.len(), 1, "{err}");
+    }
+}
@@ -0,0 +1,432 @@
+//! Durable checkpoints of committee event processing
+//!
+//! A [`StateCheckpoint`] bundles the position of the last processed event with the consumer's
+//! state derived from all events up to it, and is saved and restored as a single blob, so that
+//! after a crash the two can't disagree. Where the blob is kept is up to the [`CheckpointStore`].
+//!
+//! Consumers without state of their own can leave the bookkeeping to a [`CheckpointedConsumer`],
+//! which stores just the cursor.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use alloy::{eips::BlockNumberOrTag, rpc::types::Log};
+use anyhow::{Context, Result};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::provider::LoggedEvent;
+
+/// Position of the last processed event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct EventCursor {
+    pub block_number: u64,
+    pub log_index: u64,
+}
+
+impl EventCursor {
+    /// cursor at `log`, `None` for pending logs without a position
+    pub fn from_log<T>(log: &Log<T>) -> Option<Self> {
+        Some(Self {
+            block_number: log.block_number?,
+            log_index: log.log_index?,
+        })
+    }
+
+    /// block to resume streaming from; it may contain processed events, skip them with
+    /// [`Self::is_processed`]
+    pub fn resume_block(&self) -> BlockNumberOrTag {
+        BlockNumberOrTag::Number(self.block_number)
+    }
+
+    /// whether `log` is at or before the cursor, pending logs never are
+    pub fn is_processed<T>(&self, log: &Log<T>) -> bool {
+        Self::from_log(log).is_some_and(|at| at <= *self)
+    }
+}
+
+/// Storage for the serialized checkpoint. `save` must replace the previous checkpoint
+/// atomically: a `load` after a crash returns either the old or the new one, never a mix.
+pub trait CheckpointStore {
+    fn save(&self, checkpoint: &[u8]) -> Result<()>;
+
+    /// the last saved checkpoint, `None` if nothing was saved yet
+    fn load(&self) -> Result<Option<Vec<u8>>>;
+}
+
+/// A checkpoint file, replaced by writing a temporary sibling file and renaming it over
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl CheckpointStore for FileStore {
+    fn save(&self, checkpoint: &[u8]) -> Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp)
+            .with_context(|| format!("failed to create {}", tmp.display()))?;
+        file.write_all(checkpoint)?;
+        file.sync_all()?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("failed to replace {}", self.path.display()))?;
+        // persist the rename itself
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).with_context(|| format!("failed to read {}", self.path.display())),
+        }
+    }
+}
+
+/// An in-memory store, e.g. for tests
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    checkpoint: Mutex<Option<Vec<u8>>>,
+}
+
+impl CheckpointStore for MemoryStore {
+    fn save(&self, checkpoint: &[u8]) -> Result<()> {
+        *self.checkpoint.lock().unwrap() = Some(checkpoint.to_vec());
+        Ok(())
+    }
+
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self.checkpoint.lock().unwrap().clone())
+    }
+}
+
+/// The event cursor together with the consumer state derived up to and including it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateCheckpoint<S> {
+    pub cursor: EventCursor,
+    pub state: S,
+}
+
+impl<S: Serialize + DeserializeOwned> StateCheckpoint<S> {
+    pub fn new(cursor: EventCursor, state: S) -> Self {
+        Self { cursor, state }
+    }
+
+    /// save cursor and state together, as JSON
+    pub fn save(&self, store: &impl CheckpointStore) -> Result<()> {
+        store.save(&serde_json::to_vec(self)?)
+    }
+
+    /// the last saved checkpoint, `None` to start from scratch
+    pub fn restore(store: &impl CheckpointStore) -> Result<Option<Self>> {
+        store
+            .load()?
+            .map(|bytes| serde_json::from_slice(&bytes).context("corrupt checkpoint"))
+            .transpose()
+    }
+}
+
+/// At-least-once processing of an event stream: each event is passed to a handler, and the
+/// cursor in the store only moves past it once the handler succeeded. After a crash or a failed
+/// handler, processing resumes with the first event not known to be handled, which may thus be
+/// handled twice.
+///
+/// The store holds the cursor as JSON and must not be shared with a [`StateCheckpoint`].
+#[derive(Debug)]
+pub struct CheckpointedConsumer<St> {
+    store: St,
+    start: BlockNumberOrTag,
+}
+
+impl<St: CheckpointStore> CheckpointedConsumer<St> {
+    /// consume from the earliest block, unless `store` holds a cursor
+    pub fn new(store: St) -> Self {
+        Self {
+            store,
+            start: BlockNumberOrTag::Earliest,
+        }
+    }
+
+    /// block to start from without a stored cursor
+    pub fn with_start_block(mut self, block: BlockNumberOrTag) -> Self {
+        self.start = block;
+        self
+    }
+
+    pub fn store(&self) -> &St {
+        &self.store
+    }
+
+    /// the last event handled, `None` if nothing was yet
+    pub fn cursor(&self) -> Result<Option<EventCursor>> {
+        self.store
+            .load()?
+            .map(|bytes| serde_json::from_slice(&bytes).context("corrupt event cursor"))
+            .transpose()
+    }
+
+    /// block to stream from, the stored cursor's or the start block
+    pub fn resume_block(&self) -> Result<BlockNumberOrTag> {
+        Ok(self
+            .cursor()?
+            .map_or(self.start, |cursor| cursor.resume_block()))
+    }
+
+    /// Pass `events` to `handler` in order, storing the cursor after each one handled.
+    ///
+    /// Events at or before the stored cursor are skipped. Logs removed by a reorg are handled
+    /// without moving the cursor, as are pending ones. The first handler or store error is
+    /// returned, with the cursor at the last event handled before it; otherwise the cursor once
+    /// `events` ends.
+    pub async fn process<E, S, F, Fut>(
+        &self,
+        events: S,
+        mut handler: F,
+    ) -> Result<Option<EventCursor>>
+    where
+        S: Stream<Item = LoggedEvent<E>>,
+        F: FnMut(LoggedEvent<E>) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut cursor = self.cursor()?;
+        let mut events = std::pin::pin!(events);
+        while let Some(event) = events.next().await {
+            let at = event.cursor().filter(|_| !event.removed);
+            if let (Some(at), Some(last)) = (at, cursor)
+                && at <= last
+            {
+                continue;
+            }
+            handler(event).await?;
+            if let Some(at) = at {
+                self.store.save(&serde_json::to_vec(&at)?)?;
+                cursor = Some(at);
+            }
+        }
+        Ok(cursor)
+    }
+
+    /// Stream the `E` events of `contract` from [`Self::resume_block`] and [`Self::process`]
+    /// them, until the stream ends or an error.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn run<E, F, Fut>(
+        &self,
+        provider: &crate::provider::PubSubProvider,
+        contract: alloy::primitives::Address,
+        handler: F,
+    ) -> Result<Option<EventCursor>>
+    where
+        E: alloy::sol_types::SolEvent + Send,
+        F: FnMut(LoggedEvent<E>) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let from = self.resume_block()?;
+        let events = provider.logged_event_stream::<E>(contract, from).await?;
+        self.process(events, handler).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CheckpointStore, CheckpointedConsumer, EventCursor, FileStore, MemoryStore, StateCheckpoint,
+    };
+    use crate::{
+        CommitteeMemberSol, KeyManager,
+        KeyManager::CommitteeCreated,
+        provider::{LoggedEvent, PubSubProvider, PubSubProviderConfig},
+    };
+    use alloy::{
+        eips::BlockNumberOrTag,
+        node_bindings::Anvil,
+        primitives::{Address, LogData},
+        providers::{ProviderBuilder, WalletProvider},
+        rpc::types::Log,
+    };
+    use std::{cell::RefCell, collections::BTreeMap};
+
+    fn roundtrip(store: &impl CheckpointStore) {
+        type State = BTreeMap<u64, usize>;
+        assert_eq!(StateCheckpoint::<State>::restore(store).unwrap(), None);
+
+        let cursor = EventCursor {
+            block_number: 10,
+            log_index: 2,
+        };
+        let checkpoint = StateCheckpoint::new(cursor, State::from([(0, 4), (1, 5)]));
+        checkpoint.save(store).unwrap();
+        let mut next = checkpoint.clone();
+        next.cursor.block_number = 11;
+        next.state.insert(2, 3);
+        next.save(store).unwrap();
+        assert_eq!(StateCheckpoint::restore(store).unwrap(), Some(next));
+
+        store.save(b"{").unwrap();
+        assert!(StateCheckpoint::<State>::restore(store).is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_stores() {
+        roundtrip(&MemoryStore::default());
+
+        let path = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        let store = FileStore::new(&path);
+        let _ = std::fs::remove_file(&path);
+        roundtrip(&store);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_cursor_is_processed() {
+        let cursor = EventCursor {
+            block_number: 10,
+            log_index: 2,
+        };
+        let log = |block, index| Log {
+            block_number: Some(block),
+            log_index: Some(index),
+            ..Log::<LogData>::default()
+        };
+        assert!(cursor.is_processed(&log(9, 7)));
+        assert!(cursor.is_processed(&log(10, 2)));
+        assert!(!cursor.is_processed(&log(10, 3)));
+        assert!(!cursor.is_processed(&log(11, 0)));
+        assert!(!cursor.is_processed(&Log::<LogData>::default()));
+    }
+
+    #[tokio::test]
+    async fn test_checkpointed_consumer_process() {
+        let event = |block, index, removed| LoggedEvent {
+            event: (block, index),
+            address: Address::ZERO,
+            block_number: Some(block),
+            block_hash: None,
+            transaction_hash: None,
+            log_index: Some(index),
+            removed,
+        };
+        let events = || {
+            futures::stream::iter([
+                event(1, 0, false),
+                event(1, 1, false),
+                event(2, 0, false),
+                event(2, 0, true),
+                event(3, 0, false),
+            ])
+        };
+        let consumer = CheckpointedConsumer::new(MemoryStore::default())
+            .with_start_block(BlockNumberOrTag::Number(1));
+        assert_eq!(consumer.cursor().unwrap(), None);
+        assert_eq!(
+            consumer.resume_block().unwrap(),
+            BlockNumberOrTag::Number(1)
+        );
+
+        // fails on the first event of block 2
+        let handled = RefCell::new(Vec::new());
+        let err = consumer
+            .process(events(), async |ev| {
+                anyhow::ensure!(ev.event != (2, 0), "handler failed");
+                handled.borrow_mut().push(ev.event);
+                Ok(())
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "handler failed");
+        assert_eq!(handled.take(), [(1, 0), (1, 1)]);
+        let cursor = EventCursor {
+            block_number: 1,
+            log_index: 1,
+        };
+        assert_eq!(consumer.cursor().unwrap(), Some(cursor));
+        assert_eq!(
+            consumer.resume_block().unwrap(),
+            BlockNumberOrTag::Number(1)
+        );
+
+        // resumes with the failed event, handling the removed one without checkpointing it
+        let last = consumer
+            .process(events(), async |ev| {
+                handled.borrow_mut().push(ev.event);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(handled.take(), [(2, 0), (2, 0), (3, 0)]);
+        let cursor = EventCursor {
+            block_number: 3,
+            log_index: 0,
+        };
+        assert_eq!(last, Some(cursor));
+        assert_eq!(consumer.cursor().unwrap(), Some(cursor));
+
+        consumer.store().save(b"[").unwrap();
+        assert!(consumer.cursor().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_checkpointed_consumer_run() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let km_addr = crate::deployer::deploy_key_manager_contract(&provider, manager)
+            .await
+            .unwrap();
+        let contract = KeyManager::new(km_addr, &provider);
+        let register = async |ts| {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        };
+        for ts in [100, 200, 300] {
+            register(ts).await;
+        }
+
+        let pubsub = PubSubProvider::new(PubSubProviderConfig::new(anvil.ws_endpoint_url()))
+            .await
+            .unwrap();
+        let consumer = CheckpointedConsumer::new(MemoryStore::default());
+        // handle ids up to `stop`, which fails
+        let run = async |stop| {
+            let handled = RefCell::new(Vec::new());
+            let err = consumer
+                .run::<CommitteeCreated, _, _>(&pubsub, km_addr, async |ev| {
+                    anyhow::ensure!(ev.event.id != stop, "stop");
+                    handled.borrow_mut().push(ev.event.id);
+                    Ok(())
+                })
+                .await
+                .unwrap_err();
+            assert_eq!(err.to_string(), "stop");
+            handled.take()
+        };
+        assert_eq!(run(2).await, [0, 1]);
+        register(400).await;
+        // restarted after the last handled event
+        assert_eq!(run(3).await, [2]);
+        assert_eq!(run(4).await, [3]);
+    }
+}
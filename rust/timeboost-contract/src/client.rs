@@ -0,0 +1,407 @@
+//! High-level client for a deployed KeyManager
+
+use std::{fmt, time::Duration};
+
+use alloy::{
+    contract::Error as ContractError,
+    primitives::{Address, TxHash},
+    providers::{PendingTransactionBuilder, PendingTransactionError, Provider, WatchTxError},
+    rpc::types::TransactionReceipt,
+    sol_types::{SolInterface, decode_revert_reason},
+};
+
+use crate::{
+    CommitteeMemberSol, CommitteeSol,
+    KeyManager::{self, KeyManagerErrors, KeyManagerInstance},
+    error::KeyManagerError,
+    reader, telemetry,
+};
+
+/// Error of a [`KeyManagerClient`] call
+#[derive(Debug)]
+pub enum ClientError {
+    /// the contract reverted with one of its custom errors
+    Reverted(KeyManagerErrors),
+    /// the call failed otherwise, e.g. on the transport or with an unknown revert
+    Contract(ContractError),
+    /// the transaction was sent but its receipt couldn't be obtained
+    Pending(PendingTransactionError),
+    /// the transaction was mined but reverted
+    Failed(TxHash),
+    /// the transaction was sent but not mined within the receipt timeout
+    Timeout(TxHash),
+    /// a transaction to replace was already mined
+    AlreadyMined(TxHash),
+    /// a transaction to replace was sent by `from`, not by the provider's signer, so a
+    /// transaction of ours at its nonce wouldn't replace it
+    ForeignSender { tx_hash: TxHash, from: Address },
+}
+
+impl ClientError {
+    /// The revert behind the error as a typed [`KeyManagerError`], `None` if it isn't one
+    pub fn key_manager_error(&self) -> Option<KeyManagerError> {
+        match self {
+            Self::Reverted(err) => Some(KeyManagerError::decode(&err.abi_encode())),
+            Self::Contract(err) => KeyManagerError::from_contract_error(err),
+            Self::Pending(_)
+            | Self::Failed(_)
+            | Self::Timeout(_)
+            | Self::AlreadyMined(_)
+            | Self::ForeignSender { .. } => None,
+        }
+    }
+
+    /// Human readable reason of a revert: the KeyManager error, or the reason string of another
+    /// revert. `None` if the error isn't a revert or the reason can't be decoded.
+    pub fn revert_reason(&self) -> Option<String> {
+        match self {
+            Self::Reverted(err) => Some(format!("{err:?}")),
+            Self::Contract(err) => err
+                .as_revert_data()
+                .and_then(|data| decode_revert_reason(&data)),
+            Self::Pending(_)
+            | Self::Failed(_)
+            | Self::Timeout(_)
+            | Self::AlreadyMined(_)
+            | Self::ForeignSender { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reverted(err) => write!(f, "KeyManager reverted: {err:?}"),
+            Self::Contract(err) => write!(f, "contract call failed: {err}"),
+            Self::Pending(err) => write!(f, "failed to get transaction receipt: {err}"),
+            Self::Failed(hash) => write!(f, "transaction {hash} reverted"),
+            Self::Timeout(hash) => write!(f, "timed out waiting for transaction {hash}"),
+            Self::AlreadyMined(hash) => write!(f, "transaction {hash} is already mined"),
+            Self::ForeignSender { tx_hash, from } => {
+                write!(
+                    f,
+                    "transaction {tx_hash} was sent by {from}, not the signer"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Contract(err) => Some(err),
+            Self::Pending(err) => Some(err),
+            Self::Reverted(_)
+            | Self::Failed(_)
+            | Self::Timeout(_)
+            | Self::AlreadyMined(_)
+            | Self::ForeignSender { .. } => None,
+        }
+    }
+}
+
+impl From<ContractError> for ClientError {
+    fn from(err: ContractError) -> Self {
+        match err.as_decoded_interface_error::<KeyManagerErrors>() {
+            Some(reason) => Self::Reverted(reason),
+            None => Self::Contract(err),
+        }
+    }
+}
+
+impl From<PendingTransactionError> for ClientError {
+    fn from(err: PendingTransactionError) -> Self {
+        Self::Pending(err)
+    }
+}
+
+pub type ClientResult<T> = Result<T, ClientError>;
+
+/// Wait for the receipt of `pending`, for at most `timeout` if given, failing with
+/// [`ClientError::Timeout`] past it and with [`ClientError::Failed`] if it reverted
+pub(crate) async fn receipt_within(
+    pending: PendingTransactionBuilder<alloy::network::Ethereum>,
+    timeout: Option<Duration>,
+) -> ClientResult<TransactionReceipt> {
+    let hash = *pending.tx_hash();
+    let receipt = pending
+        .with_timeout(timeout)
+        .get_receipt()
+        .await
+        .map_err(|err| match err {
+            PendingTransactionError::TxWatcher(WatchTxError::Timeout) => ClientError::Timeout(hash),
+            err => ClientError::Pending(err),
+        })?;
+    if !receipt.status() {
+        return Err(ClientError::Failed(hash));
+    }
+    Ok(receipt)
+}
+
+/// The read methods of a KeyManager, for code generic over the real [`KeyManagerClient`] (or the
+/// raw bindings on any [`Provider`]) and test doubles like `mock::MockKeyManager`
+pub trait KeyManagerRead: Sync {
+    fn manager(&self) -> impl Future<Output = ClientResult<Address>> + Send;
+
+    /// id of the committee in effect at the latest block's timestamp
+    fn current_committee_id(&self) -> impl Future<Output = ClientResult<u64>> + Send;
+
+    fn next_committee_id(&self) -> impl Future<Output = ClientResult<u64>> + Send;
+
+    /// committees ever registered, including pruned ones, see [`reader::committee_count`]
+    fn committee_count(&self) -> impl Future<Output = ClientResult<u64>> + Send {
+        self.next_committee_id()
+    }
+
+    fn committee_by_id(&self, id: u64) -> impl Future<Output = ClientResult<CommitteeSol>> + Send;
+
+    /// the committee in effect at `now`, see [`reader::current_committee`]
+    fn current_committee(
+        &self,
+        now: u64,
+    ) -> impl Future<Output = ClientResult<Option<CommitteeSol>>> + Send;
+}
+
+/// Wraps the KeyManager bindings, awaiting calls and receipts and decoding reverts
+#[derive(Debug, Clone)]
+pub struct KeyManagerClient<P> {
+    contract: KeyManagerInstance<P>,
+    /// how long writes wait for their receipt, `None` for as long as it takes
+    receipt_timeout: Option<Duration>,
+}
+
+impl<P: Provider> KeyManagerClient<P> {
+    pub fn new(address: Address, provider: P) -> Self {
+        Self {
+            contract: KeyManager::new(address, provider),
+            receipt_timeout: None,
+        }
+    }
+
+    /// fail writes with [`ClientError::Timeout`] if not mined within `timeout`, e.g. when the
+    /// transaction was dropped or the chain stopped producing blocks
+    pub fn with_receipt_timeout(mut self, timeout: Duration) -> Self {
+        self.receipt_timeout = Some(timeout);
+        self
+    }
+
+    pub fn address(&self) -> Address {
+        *self.contract.address()
+    }
+
+    /// the raw bindings, for anything not covered by the client
+    pub fn inner(&self) -> &KeyManagerInstance<P> {
+        &self.contract
+    }
+
+    pub async fn manager(&self) -> ClientResult<Address> {
+        Ok(self.contract.manager().call().await?)
+    }
+
+    pub async fn current_committee_id(&self) -> ClientResult<u64> {
+        Ok(self.contract.currentCommitteeId().call().await?)
+    }
+
+    pub async fn next_committee_id(&self) -> ClientResult<u64> {
+        Ok(self.contract.nextCommitteeId().call().await?)
+    }
+
+    pub async fn committee_by_id(&self, id: u64) -> ClientResult<CommitteeSol> {
+        Ok(self.contract.getCommitteeById(id).call().await?)
+    }
+
+    /// The committee in effect at `now`, see [`reader::current_committee`]
+    pub async fn current_committee(&self, now: u64) -> ClientResult<Option<CommitteeSol>> {
+        Ok(reader::committee_at_time(&self.contract, now).await?)
+    }
+
+    /// Register the next committee and wait for it to be mined
+    pub async fn set_next_committee(
+        &self,
+        timestamp: u64,
+        members: Vec<CommitteeMemberSol>,
+    ) -> ClientResult<TransactionReceipt> {
+        let call = self.contract.setNextCommittee(timestamp, members);
+        let pending = telemetry::traced(self.address(), call).send().await?;
+        receipt_within(pending, self.receipt_timeout).await
+    }
+}
+
+// wasm's HTTP futures aren't `Send`
+#[cfg(not(target_arch = "wasm32"))]
+impl<P: Provider> KeyManagerRead for KeyManagerClient<P> {
+    async fn manager(&self) -> ClientResult<Address> {
+        KeyManagerClient::manager(self).await
+    }
+
+    async fn current_committee_id(&self) -> ClientResult<u64> {
+        KeyManagerClient::current_committee_id(self).await
+    }
+
+    async fn next_committee_id(&self) -> ClientResult<u64> {
+        KeyManagerClient::next_committee_id(self).await
+    }
+
+    async fn committee_by_id(&self, id: u64) -> ClientResult<CommitteeSol> {
+        KeyManagerClient::committee_by_id(self, id).await
+    }
+
+    async fn current_committee(&self, now: u64) -> ClientResult<Option<CommitteeSol>> {
+        KeyManagerClient::current_committee(self, now).await
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<P: Provider> KeyManagerRead for KeyManagerInstance<P> {
+    async fn manager(&self) -> ClientResult<Address> {
+        Ok(self.manager().call().await?)
+    }
+
+    async fn current_committee_id(&self) -> ClientResult<u64> {
+        Ok(self.currentCommitteeId().call().await?)
+    }
+
+    async fn next_committee_id(&self) -> ClientResult<u64> {
+        Ok(self.nextCommitteeId().call().await?)
+    }
+
+    async fn committee_by_id(&self, id: u64) -> ClientResult<CommitteeSol> {
+        Ok(self.getCommitteeById(id).call().await?)
+    }
+
+    async fn current_committee(&self, now: u64) -> ClientResult<Option<CommitteeSol>> {
+        Ok(reader::committee_at_time(self, now).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientError, KeyManagerClient, KeyManagerRead};
+    use crate::{
+        CommitteeMemberSol, CommitteeSol,
+        KeyManager::{self, KeyManagerErrors},
+        error::KeyManagerError,
+    };
+    use alloy::{
+        primitives::Address,
+        providers::{Provider, WalletProvider},
+    };
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_client() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let manager = provider.default_signer_address();
+        let client = KeyManagerClient::new(addr, &provider);
+        assert_eq!(client.manager().await.unwrap(), manager);
+        assert_eq!(client.next_committee_id().await.unwrap(), 0);
+
+        let members = vec![CommitteeMemberSol::random(), CommitteeMemberSol::random()];
+        let receipt = client
+            .set_next_committee(100, members.clone())
+            .await
+            .unwrap();
+        assert!(receipt.status());
+        assert_eq!(client.next_committee_id().await.unwrap(), 1);
+        assert_eq!(client.current_committee_id().await.unwrap(), 0);
+        assert!(client.current_committee(99).await.unwrap().is_none());
+        assert_eq!(client.current_committee(100).await.unwrap().unwrap().id, 0);
+
+        let committee = client.committee_by_id(0).await.unwrap();
+        assert_eq!(committee.effectiveTimestamp, 100);
+        assert_eq!(committee.members, members);
+        // same as the raw bindings
+        assert_eq!(
+            client.inner().getCommitteeById(0).call().await.unwrap(),
+            committee
+        );
+
+        assert!(matches!(
+            client.committee_by_id(7).await,
+            Err(ClientError::Reverted(
+                KeyManagerErrors::CommitteeIdDoesNotExist(_)
+            ))
+        ));
+        let err = client.set_next_committee(100, vec![]).await.unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Reverted(KeyManagerErrors::EmptyCommitteeMembers(_))
+        ));
+        assert_eq!(
+            err.key_manager_error(),
+            Some(KeyManagerError::EmptyCommittee)
+        );
+    }
+
+    /// everything the trait reads, for comparing implementations
+    async fn read_all(
+        km: &impl KeyManagerRead,
+        at: u64,
+    ) -> (Address, u64, u64, CommitteeSol, Option<CommitteeSol>) {
+        (
+            km.manager().await.unwrap(),
+            km.committee_count().await.unwrap(),
+            km.current_committee_id().await.unwrap(),
+            km.committee_by_id(1).await.unwrap(),
+            km.current_committee(at).await.unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_key_manager_read() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let client = KeyManagerClient::new(addr, &provider);
+        let now = provider
+            .get_block_by_number(alloy::eips::BlockNumberOrTag::Latest)
+            .await
+            .unwrap()
+            .unwrap()
+            .header
+            .timestamp;
+        client
+            .set_next_committee(now, vec![CommitteeMemberSol::random()])
+            .await
+            .unwrap();
+        client
+            .set_next_committee(now + 100, vec![CommitteeMemberSol::random()])
+            .await
+            .unwrap();
+
+        let instance = KeyManager::new(addr, &provider);
+        let read = read_all(&client, now + 50).await;
+        assert_eq!(read.0, provider.default_signer_address());
+        assert_eq!(read.1, 2);
+        assert_eq!(read.2, 0);
+        assert_eq!(read.3.effectiveTimestamp, now + 100);
+        assert_eq!(read.4.as_ref().unwrap().id, 0);
+        assert_eq!(read_all(&instance, now + 50).await, read);
+    }
+
+    #[tokio::test]
+    async fn test_receipt_timeout() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let client =
+            KeyManagerClient::new(addr, &provider).with_receipt_timeout(Duration::from_millis(500));
+        provider
+            .raw_request::<_, ()>("evm_setAutomine".into(), (false,))
+            .await
+            .unwrap();
+
+        let err = client
+            .set_next_committee(100, vec![CommitteeMemberSol::random()])
+            .await
+            .unwrap_err();
+        let ClientError::Timeout(hash) = err else {
+            panic!("expected a timeout, got {err}");
+        };
+        assert!(err.key_manager_error().is_none());
+        assert!(
+            provider
+                .get_transaction_receipt(hash)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
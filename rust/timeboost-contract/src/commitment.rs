@@ -0,0 +1,166 @@
+//! Commitments over committee data for downstream verifiers
+//!
+//! Every scheme commits to the same byte encodings, only the hash function differs:
+//!
+//! - leaf `i`: `H(abi.encode(members[i]))`, members in contract (index) order
+//! - inner node: `H(left || right)`; a node without a sibling is carried up unchanged
+//! - root of an empty committee: 32 zero bytes
+//! - identity: `H(abi.encode(effectiveTimestamp, sortedMembers))`, see
+//!   [`CommitteeSol::identity_hash`]
+//!
+//! For [`CommitmentScheme::Keccak256`] and [`CommitmentScheme::Sha256`], `H` is the plain hash of
+//! the bytes and `left || right` is the 64 byte concatenation of the two digests.
+//!
+//! For `CommitmentScheme::Poseidon` (feature `poseidon`, circom-compatible BN254 parameters),
+//! byte strings are first compressed with keccak256 and the digest is split into its high and low
+//! 128 bits, so `H(bytes) = Poseidon(hi, lo)`. Inner nodes are `Poseidon(left, right)` with both
+//! children taken as field elements. Digests are the big-endian encoding of the field element.
+
+use alloy::{
+    primitives::{B256, keccak256},
+    sol_types::SolValue,
+};
+use sha2::{Digest, Sha256};
+
+use crate::CommitteeSol;
+
+/// Hash function used to commit to committee data
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CommitmentScheme {
+    /// EVM-native, matches `keccak256` in Solidity
+    #[default]
+    Keccak256,
+    Sha256,
+    /// SNARK-friendly Poseidon over the BN254 scalar field
+    #[cfg(feature = "poseidon")]
+    Poseidon,
+}
+
+impl CommitmentScheme {
+    /// `H(bytes)`
+    pub fn hash_bytes(&self, bytes: &[u8]) -> B256 {
+        match self {
+            Self::Keccak256 => keccak256(bytes),
+            Self::Sha256 => B256::from_slice(&Sha256::digest(bytes)),
+            #[cfg(feature = "poseidon")]
+            Self::Poseidon => {
+                let digest = keccak256(bytes);
+                let mut hi = [0; 32];
+                let mut lo = [0; 32];
+                hi[16..].copy_from_slice(&digest[..16]);
+                lo[16..].copy_from_slice(&digest[16..]);
+                poseidon(&hi, &lo)
+            }
+        }
+    }
+
+    /// `H(left || right)`, an inner node of the member merkle tree
+    pub fn hash_pair(&self, left: &B256, right: &B256) -> B256 {
+        match self {
+            Self::Keccak256 | Self::Sha256 => self.hash_bytes(&[left.0, right.0].concat()),
+            #[cfg(feature = "poseidon")]
+            Self::Poseidon => poseidon(&left.0, &right.0),
+        }
+    }
+}
+
+/// Merkle root over the members of `committee`, with the leaf encoding described in the module docs
+pub fn committee_merkle_root(committee: &CommitteeSol, scheme: CommitmentScheme) -> B256 {
+    let mut level = committee
+        .members
+        .iter()
+        .map(|m| scheme.hash_bytes(&m.abi_encode()))
+        .collect::<Vec<_>>();
+    if level.is_empty() {
+        return B256::ZERO;
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => scheme.hash_pair(left, right),
+                [single] => *single,
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+#[cfg(feature = "poseidon")]
+fn poseidon(a: &[u8; 32], b: &[u8; 32]) -> B256 {
+    use ark_bn254::Fr;
+    use light_poseidon::{Poseidon, PoseidonBytesHasher};
+
+    // inputs are either 128 bit values or poseidon outputs, hence always below the modulus
+    let mut hasher = Poseidon::<Fr>::new_circom(2).expect("poseidon supports 2 inputs");
+    B256::from(
+        hasher
+            .hash_bytes_be(&[a, b])
+            .expect("inputs are valid field elements"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommitmentScheme, committee_merkle_root};
+    use crate::{CommitteeMemberSol, CommitteeSol};
+    use alloy::{
+        primitives::{B256, U256, keccak256},
+        sol_types::SolValue,
+    };
+
+    #[test]
+    fn test_committee_merkle_root() {
+        let members = (0..3)
+            .map(|_| CommitteeMemberSol::random())
+            .collect::<Vec<_>>();
+        let mut committee = CommitteeSol {
+            id: 1,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members: members.clone(),
+        };
+
+        // ((l0, l1), l2) with keccak leaves and inner nodes
+        let leaf = |m: &CommitteeMemberSol| keccak256(m.abi_encode());
+        let node = |l: B256, r: B256| keccak256([l.0, r.0].concat());
+        let expected = node(
+            node(leaf(&members[0]), leaf(&members[1])),
+            leaf(&members[2]),
+        );
+        assert_eq!(
+            committee_merkle_root(&committee, CommitmentScheme::Keccak256),
+            expected
+        );
+
+        let sha = committee_merkle_root(&committee, CommitmentScheme::Sha256);
+        assert_ne!(sha, expected);
+        committee.members.swap(0, 1);
+        assert_ne!(
+            committee_merkle_root(&committee, CommitmentScheme::Sha256),
+            sha
+        );
+
+        committee.members.clear();
+        assert_eq!(
+            committee_merkle_root(&committee, CommitmentScheme::Sha256),
+            B256::ZERO
+        );
+    }
+
+    #[cfg(feature = "poseidon")]
+    #[test]
+    fn test_poseidon_pair() {
+        // reference vector of the circom-compatible parameters: Poseidon(1, 2)
+        let one = B256::with_last_byte(1);
+        let two = B256::with_last_byte(2);
+        assert_eq!(
+            CommitmentScheme::Poseidon.hash_pair(&one, &two),
+            "0x115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a"
+                .parse::<B256>()
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,415 @@
+//! Connection lifecycle management for committee members across committee rotations
+
+use std::{collections::BTreeMap, fmt::Display, future::Future, net::SocketAddr, time::Duration};
+
+use alloy::primitives::Bytes;
+use anyhow::Context;
+use futures::{Stream, StreamExt, future::join_all};
+use tokio::net::TcpStream;
+use tracing::{info, warn};
+
+use crate::{CommitteeMemberSol, CommitteeSol, stream::CommitteeChange};
+
+/// Opens a connection to a committee member, dropping the connection closes it.
+pub trait MemberConnector {
+    type Connection;
+    type Error: Display;
+
+    fn connect(
+        &self,
+        member: &CommitteeMemberSol,
+    ) -> impl Future<Output = Result<Self::Connection, Self::Error>> + Send;
+}
+
+/// Outcome of reconciling the live connection set against a new committee
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionUpdate {
+    /// sig keys of newly connected members
+    pub opened: Vec<Bytes>,
+    /// sig keys of members whose connections were closed
+    pub closed: Vec<Bytes>,
+    /// sig keys of members that could not be connected after all retries
+    pub failed: Vec<Bytes>,
+}
+
+struct LiveConnection<T> {
+    network_address: String,
+    conn: T,
+}
+
+/// Maintains one connection per member of the latest committee, keyed by `sigKey`.
+///
+/// Members that stay in the committee keep their connection, new members are connected,
+/// removed members (or members whose `networkAddress` changed) are disconnected. Members are
+/// connected concurrently, each retried on its own.
+pub struct MemberConnectionManager<C: MemberConnector> {
+    connector: C,
+    max_retries: u32,
+    retry_interval: Duration,
+    live: BTreeMap<Bytes, LiveConnection<C::Connection>>,
+}
+
+impl<C: MemberConnector> MemberConnectionManager<C> {
+    pub fn new(connector: C) -> Self {
+        Self {
+            connector,
+            max_retries: 3,
+            retry_interval: Duration::from_secs(1),
+            live: BTreeMap::new(),
+        }
+    }
+
+    /// set the number of retries after a failed connection attempt
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// set the wait between connection attempts
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// the live connection of a member, by its sig key
+    pub fn connection(&self, sig_key: &Bytes) -> Option<&C::Connection> {
+        self.live.get(sig_key).map(|l| &l.conn)
+    }
+
+    /// iterate over the current live connection set
+    pub fn connections(&self) -> impl Iterator<Item = (&Bytes, &C::Connection)> {
+        self.live.iter().map(|(k, l)| (k, &l.conn))
+    }
+
+    pub fn len(&self) -> usize {
+        self.live.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.live.is_empty()
+    }
+
+    /// reconcile the live connections with the members of `committee`
+    pub async fn apply(&mut self, committee: &CommitteeSol) -> ConnectionUpdate {
+        let mut update = ConnectionUpdate::default();
+
+        let stale = self
+            .live
+            .iter()
+            .filter(|(k, l)| {
+                !committee
+                    .members
+                    .iter()
+                    .any(|m| &m.sigKey == *k && m.networkAddress == l.network_address)
+            })
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        for key in stale {
+            self.live.remove(&key);
+            update.closed.push(key);
+        }
+        self.connect_missing(committee, &mut update).await;
+        self.log_update(committee, &update);
+        update
+    }
+
+    /// Apply the membership change of a [`committee_diffs`](crate::stream::committee_diffs)
+    /// item: the connections of removed members and of members whose `networkAddress` changed
+    /// are closed, then every member of the committee without a live connection is connected,
+    /// i.e. new, moved and previously failed members.
+    pub async fn apply_change(&mut self, change: &CommitteeChange) -> ConnectionUpdate {
+        let mut update = ConnectionUpdate::default();
+        let moved = change
+            .diff
+            .changed
+            .iter()
+            .filter(|(previous, current)| previous.networkAddress != current.networkAddress)
+            .map(|(previous, _)| previous);
+        for member in change.diff.removed.iter().chain(moved) {
+            if self.live.remove(&member.sigKey).is_some() {
+                update.closed.push(member.sigKey.clone());
+            }
+        }
+        self.connect_missing(&change.committee, &mut update).await;
+        self.log_update(&change.committee, &update);
+        update
+    }
+
+    /// drive the connection set from a [`committee_diffs`](crate::stream::committee_diffs)
+    /// stream, until the stream ends
+    pub async fn run<S>(&mut self, changes: S)
+    where
+        S: Stream<Item = CommitteeChange>,
+    {
+        let mut changes = std::pin::pin!(changes);
+        while let Some(change) = changes.next().await {
+            self.apply_change(&change).await;
+        }
+    }
+
+    /// connect all members of `committee` without a live connection, concurrently
+    async fn connect_missing(&mut self, committee: &CommitteeSol, update: &mut ConnectionUpdate) {
+        let missing = committee
+            .members
+            .iter()
+            .filter(|m| !self.live.contains_key(&m.sigKey))
+            .collect::<Vec<_>>();
+        let conns = join_all(missing.iter().map(|m| self.connect_with_retry(m))).await;
+        for (member, conn) in missing.into_iter().zip(conns) {
+            match conn {
+                Some(conn) => {
+                    self.live.insert(
+                        member.sigKey.clone(),
+                        LiveConnection {
+                            network_address: member.networkAddress.clone(),
+                            conn,
+                        },
+                    );
+                    update.opened.push(member.sigKey.clone());
+                }
+                None => update.failed.push(member.sigKey.clone()),
+            }
+        }
+    }
+
+    fn log_update(&self, committee: &CommitteeSol, update: &ConnectionUpdate) {
+        info!(
+            committee = %committee.id,
+            opened = %update.opened.len(),
+            closed = %update.closed.len(),
+            failed = %update.failed.len(),
+            "member connections updated"
+        );
+    }
+
+    async fn connect_with_retry(&self, member: &CommitteeMemberSol) -> Option<C::Connection> {
+        for attempt in 0..=self.max_retries {
+            match self.connector.connect(member).await {
+                Ok(conn) => return Some(conn),
+                Err(err) => {
+                    warn!(%err, addr = %member.networkAddress, %attempt, "member connection failed");
+                    if attempt < self.max_retries {
+                        tokio::time::sleep(self.retry_interval).await;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A member's `batchPosterAddress`, parsed with [`CommitteeMemberSol::batch_poster_socket_addr`].
+/// This is the batch poster endpoint, distinct from the member's `networkAddress`.
+pub fn batch_poster_addr(member: &CommitteeMemberSol) -> anyhow::Result<SocketAddr> {
+    member
+        .batch_poster_socket_addr()
+        .with_context(|| format!("member {}", member.sigKey))
+}
+
+/// The batch poster endpoints of all members of `committee`, in member order.
+/// Fails on the first member with an invalid batch poster address.
+pub fn batch_poster_addrs(committee: &CommitteeSol) -> anyhow::Result<Vec<SocketAddr>> {
+    committee.members.iter().map(batch_poster_addr).collect()
+}
+
+/// The subset of `addrs` that don't accept a TCP connection within `timeout`
+pub async fn unreachable_addrs(addrs: &[SocketAddr], timeout: Duration) -> Vec<SocketAddr> {
+    let checks = addrs.iter().map(|&addr| async move {
+        match tokio::time::timeout(timeout, TcpStream::connect(addr)).await {
+            Ok(Ok(_)) => None,
+            Ok(Err(err)) => {
+                warn!(%addr, %err, "endpoint unreachable");
+                Some(addr)
+            }
+            Err(_) => {
+                warn!(%addr, "endpoint connection timed out");
+                Some(addr)
+            }
+        }
+    });
+    join_all(checks).await.into_iter().flatten().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    };
+
+    use super::{
+        MemberConnectionManager, MemberConnector, batch_poster_addr, batch_poster_addrs,
+        unreachable_addrs,
+    };
+    use crate::{CommitteeMemberSol, CommitteeSol, stream::committee_diffs};
+    use alloy::primitives::U256;
+
+    /// fails the first `failures` attempts, then hands out the member's network address
+    struct FlakyConnector {
+        failures: u32,
+        attempts: Arc<AtomicU32>,
+    }
+
+    impl MemberConnector for FlakyConnector {
+        type Connection = String;
+        type Error = &'static str;
+
+        async fn connect(&self, member: &CommitteeMemberSol) -> Result<String, &'static str> {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err("connection refused")
+            } else {
+                Ok(member.networkAddress.clone())
+            }
+        }
+    }
+
+    fn committee(id: u64, members: Vec<CommitteeMemberSol>) -> CommitteeSol {
+        CommitteeSol {
+            id,
+            effectiveTimestamp: id,
+            registeredBlockNumber: U256::from(id),
+            members,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connections_follow_committee_rotation() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let connector = FlakyConnector {
+            failures: 2,
+            attempts: attempts.clone(),
+        };
+        let mut manager = MemberConnectionManager::new(connector)
+            .with_retry_interval(std::time::Duration::from_millis(1));
+
+        let members = (0..3)
+            .map(|_| CommitteeMemberSol::random())
+            .collect::<Vec<_>>();
+        let update = manager.apply(&committee(0, members.clone())).await;
+        assert_eq!(update.opened.len(), 3);
+        assert!(update.failed.is_empty());
+        assert_eq!(attempts.load(Ordering::SeqCst), 5);
+
+        // rotate out the first member, keep the others and add a new one
+        let mut next = members[1..].to_vec();
+        next.push(CommitteeMemberSol::random());
+        let update = manager.apply(&committee(1, next.clone())).await;
+        assert_eq!(update.closed, vec![members[0].sigKey.clone()]);
+        assert_eq!(update.opened, vec![next[2].sigKey.clone()]);
+        assert_eq!(manager.len(), 3);
+        assert!(manager.connection(&members[0].sigKey).is_none());
+
+        // a member moving to a new network address is reconnected
+        next[0].networkAddress = "127.0.0.1:1".to_string();
+        let update = manager.apply(&committee(2, next.clone())).await;
+        assert_eq!(update.closed, vec![next[0].sigKey.clone()]);
+        assert_eq!(update.opened, vec![next[0].sigKey.clone()]);
+        assert_eq!(manager.connection(&next[0].sigKey).unwrap(), "127.0.0.1:1");
+    }
+
+    /// connects after a short wait, recording the most connection attempts in flight at once
+    #[derive(Default)]
+    struct SlowConnector {
+        in_flight: AtomicU32,
+        max_in_flight: AtomicU32,
+    }
+
+    impl MemberConnector for &SlowConnector {
+        type Connection = String;
+        type Error = &'static str;
+
+        async fn connect(&self, member: &CommitteeMemberSol) -> Result<String, &'static str> {
+            let n = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(n, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(member.networkAddress.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_follows_committee_diffs() {
+        let connector = SlowConnector::default();
+        let mut manager = MemberConnectionManager::new(&connector);
+
+        let members = (0..4)
+            .map(|_| CommitteeMemberSol::random())
+            .collect::<Vec<_>>();
+        let mut moved = members[2].clone();
+        moved.networkAddress = "127.0.0.1:1".to_string();
+        let committees = vec![
+            committee(0, members[..3].to_vec()),
+            committee(1, vec![members[1].clone(), moved, members[3].clone()]),
+        ];
+        manager
+            .run(committee_diffs(futures::stream::iter(committees)))
+            .await;
+
+        assert!(connector.max_in_flight.load(Ordering::SeqCst) > 1);
+        assert_eq!(manager.len(), 3);
+        assert!(manager.connection(&members[0].sigKey).is_none());
+        assert_eq!(
+            manager.connection(&members[2].sigKey).unwrap(),
+            "127.0.0.1:1"
+        );
+        assert_eq!(
+            manager.connection(&members[3].sigKey).unwrap(),
+            &members[3].networkAddress
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_poster_addrs() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let open = listener.local_addr().unwrap();
+        // bind and drop to get a port nobody listens on
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let members = [open, closed]
+            .into_iter()
+            .map(|addr| CommitteeMemberSol {
+                batchPosterAddress: addr.to_string(),
+                ..CommitteeMemberSol::random()
+            })
+            .collect::<Vec<_>>();
+        let addrs = batch_poster_addrs(&committee(0, members)).unwrap();
+        assert_eq!(addrs, [open, closed]);
+        assert_eq!(
+            unreachable_addrs(&addrs, std::time::Duration::from_secs(1)).await,
+            [closed]
+        );
+
+        for invalid in [
+            "localhost",
+            "localhost:80",
+            "10.0.0.1",
+            "0.0.0.0:80",
+            "10.0.0.1:0",
+        ] {
+            let member = CommitteeMemberSol {
+                batchPosterAddress: invalid.to_string(),
+                ..CommitteeMemberSol::random()
+            };
+            assert!(batch_poster_addr(&member).is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_gives_up_after_retries() {
+        let connector = FlakyConnector {
+            failures: u32::MAX,
+            attempts: Arc::new(AtomicU32::new(0)),
+        };
+        let mut manager = MemberConnectionManager::new(connector)
+            .with_max_retries(1)
+            .with_retry_interval(std::time::Duration::from_millis(1));
+
+        let member = CommitteeMemberSol::random();
+        let update = manager.apply(&committee(0, vec![member.clone()])).await;
+        assert_eq!(update.failed, vec![member.sigKey]);
+        assert!(manager.is_empty());
+    }
+}
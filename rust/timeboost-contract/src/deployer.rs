@@ -1,66 +1,2664 @@
-//! Contract deployment helpers for testing
-use alloy::{contract::RawCallBuilder, primitives::Address, providers::Provider};
+//! Deploying, upgrading and verifying KeyManager contracts
+//!
+//! [`deploy_key_manager_contract_with_config`] deploys an implementation and its proxy as a
+//! [`DeployConfig`] says, e.g. with a fee ceiling, pinned nonces or more confirmations, and
+//! [`prepare_deployment`] signs the same transactions offline for
+//! [`broadcast_prepared_deployment`]. [`upgrade_key_manager`] moves a proxy to a new
+//! implementation, while [`verify_deployed_bytecode`] and [`ensure_key_manager`] check what is
+//! deployed at an address.
 
-use crate::{ERC1967Proxy, KeyManager};
+use std::{collections::BTreeMap, fmt, ops::Range, time::Duration};
 
-type ContractResult<T> = Result<T, alloy::contract::Error>;
+use alloy::{
+    contract::RawCallBuilder,
+    eips::Encodable2718,
+    hex,
+    network::{Ethereum, NetworkWallet, TransactionBuilder},
+    primitives::{Address, B256, Bytes, TxHash, address, b256, keccak256},
+    providers::{PendingTransactionBuilder, PendingTransactionError, Provider, WatchTxError},
+    rpc::types::{
+        TransactionReceipt, TransactionRequest,
+        state::{AccountOverride, StateOverride},
+    },
+    sol_types::{SolCall, SolConstructor, SolValue},
+};
+use anyhow::Context;
+use futures::future::try_join_all;
+use serde::{Deserialize, Serialize};
+use tracing::{Instrument, Span, field};
 
-/// Deploy a contract (with logging)
-pub(crate) async fn deploy<P: Provider>(
+#[cfg(not(target_arch = "wasm32"))]
+use alloy::transports::http::reqwest::Url;
+
+use crate::{
+    CommitteeMemberSol, ERC1967Proxy, KeyManager,
+    KeyManager::CommitteeCreated,
+    bindings::i_beacon::IBeacon,
+    error::{Result, TimeboostContractError},
+    telemetry,
+};
+
+/// A mined contract creation, see [`deploy_contract`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deployed {
+    pub addr: Address,
+    pub tx_hash: TxHash,
+    pub gas_used: u64,
+    pub block_number: Option<u64>,
+}
+
+/// What a KeyManager deployment created, where and at what cost.
+///
+/// Handed to other tooling as a JSON artifact, see [`Self::to_json`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentReport {
+    pub chain_id: u64,
+    /// the manager the proxy was initialized with, `None` for other initializer calldata
+    #[serde(with = "crate::address_hex::option", default)]
+    pub manager: Option<Address>,
+    #[serde(with = "crate::address_hex")]
+    pub implementation_addr: Address,
+    #[serde(with = "crate::address_hex")]
+    pub proxy_addr: Address,
+    /// the beacon of a [`ProxyKind::Beacon`] proxy
+    #[serde(with = "crate::address_hex::option", default)]
+    pub beacon_addr: Option<Address>,
+    pub impl_tx_hash: TxHash,
+    pub proxy_tx_hash: TxHash,
+    /// gas used by the implementation, beacon and proxy deployments combined
+    pub total_gas_used: u64,
+    /// block of the proxy deployment, from which on the KeyManager is usable
+    pub deployed_block: Option<u64>,
+    /// random id of the deployment, the `deployment_id` of its `deploy_key_manager` tracing span
+    pub deployment_id: String,
+}
+
+/// Version of the JSON artifact [`DeploymentReport::to_json`] writes
+pub const DEPLOYMENT_ARTIFACT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct DeploymentArtifact<'a> {
+    version: u32,
+    #[serde(flatten)]
+    report: &'a DeploymentReport,
+}
+
+#[derive(Deserialize)]
+struct ArtifactVersion {
+    version: u32,
+}
+
+/// Why a deployment artifact couldn't be read
+#[derive(Debug)]
+pub enum DeploymentArtifactError {
+    /// written by a newer (or foreign) tool
+    UnknownVersion(u32),
+    /// not JSON, or missing fields
+    Json(serde_json::Error),
+    Io(std::io::Error),
+}
+
+impl fmt::Display for DeploymentArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownVersion(v) => write!(
+                f,
+                "unknown deployment artifact version {v}, expected {DEPLOYMENT_ARTIFACT_VERSION}"
+            ),
+            Self::Json(err) => write!(f, "malformed deployment artifact: {err}"),
+            Self::Io(err) => write!(f, "failed to read deployment artifact: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DeploymentArtifactError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::UnknownVersion(_) => None,
+            Self::Json(err) => Some(err),
+            Self::Io(err) => Some(err),
+        }
+    }
+}
+
+impl DeploymentReport {
+    /// The report as a JSON object with a `version` field next to the report's fields, addresses
+    /// checksummed
+    pub fn to_json(&self) -> String {
+        let artifact = DeploymentArtifact {
+            version: DEPLOYMENT_ARTIFACT_VERSION,
+            report: self,
+        };
+        serde_json::to_string_pretty(&artifact).expect("report serializes")
+    }
+
+    /// A report written by [`Self::to_json`], e.g. to pass its `proxy_addr` to
+    /// [`ensure_key_manager`] when re-running a deployment
+    pub fn from_json(json: &str) -> Result<Self, DeploymentArtifactError> {
+        let ArtifactVersion { version } =
+            serde_json::from_str(json).map_err(DeploymentArtifactError::Json)?;
+        if version != DEPLOYMENT_ARTIFACT_VERSION {
+            return Err(DeploymentArtifactError::UnknownVersion(version));
+        }
+        serde_json::from_str(json).map_err(DeploymentArtifactError::Json)
+    }
+
+    /// Write [`Self::to_json`] to `path`, replacing the file if it exists
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn write_to_file(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_json())
+    }
+
+    /// Read a report written by [`Self::write_to_file`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_from_file(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, DeploymentArtifactError> {
+        let json = std::fs::read_to_string(path).map_err(DeploymentArtifactError::Io)?;
+        Self::from_json(&json)
+    }
+}
+
+/// A `deploy_key_manager` span with a random `deployment_id`, wrapping all transactions of one
+/// KeyManager deployment, so that concurrent deployments can be told apart in the logs.
+///
+/// The `implementation` and `proxy` addresses and the total `gas_used` are recorded once known.
+fn deployment_span() -> (String, Span) {
+    let id = format!("{:016x}", rand::random::<u64>());
+    let span = tracing::info_span!(
+        "deploy_key_manager",
+        deployment_id = %id,
+        implementation = field::Empty,
+        proxy = field::Empty,
+        gas_used = field::Empty,
+    );
+    (id, span)
+}
+
+/// Confirmations deployments wait for unless told otherwise: just being mined
+pub const DEFAULT_CONFIRMATIONS: u64 = 1;
+
+/// How deployments wait for their receipts and what they may cost, see
+/// [`deploy_key_manager_contract_with_config`]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct DeployConfig {
+    /// confirmations of each deployment, itself counting as the first
+    pub confirmations: u64,
+    /// give up on a receipt after this long, see [`is_receipt_timeout`]
+    pub receipt_timeout: Option<Duration>,
+    /// poll for receipts with this backoff, at the provider's poll interval if unset
+    pub receipt_backoff: Option<ReceiptBackoff>,
+    /// read `manager()` back from the new proxy, see [`PostDeployCheckFailed`]
+    pub verify_manager: bool,
+    /// nonce of the implementation deployment, the beacon's and proxy's being the next;
+    /// assigned by the provider's nonce filler if unset
+    pub nonce: Option<u64>,
+    /// the proxy in front of the implementation, an [`ERC1967Proxy`] by default
+    pub proxy: ProxyKind,
+    /// send nothing if the network gas price is above this, see [`check_gas_price`]
+    pub max_gas_price: Option<u128>,
+    /// send nothing if the [`deployment_cost_estimate`] in wei is above this
+    pub max_wei: Option<u128>,
+}
+
+impl DeployConfig {
+    pub fn new() -> Self {
+        Self {
+            confirmations: DEFAULT_CONFIRMATIONS,
+            receipt_timeout: None,
+            receipt_backoff: None,
+            verify_manager: true,
+            nonce: None,
+            proxy: ProxyKind::default(),
+            max_gas_price: None,
+            max_wei: None,
+        }
+    }
+
+    pub fn with_confirmations(mut self, n: u64) -> Self {
+        self.confirmations = n;
+        self
+    }
+
+    pub fn with_receipt_timeout(mut self, d: Duration) -> Self {
+        self.receipt_timeout = Some(d);
+        self
+    }
+
+    pub fn with_receipt_backoff(mut self, backoff: ReceiptBackoff) -> Self {
+        self.receipt_backoff = Some(backoff);
+        self
+    }
+
+    pub fn with_verify_manager(mut self, verify: bool) -> Self {
+        self.verify_manager = verify;
+        self
+    }
+
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    pub fn with_proxy(mut self, proxy: ProxyKind) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    pub fn with_max_gas_price(mut self, max_gas_price: u128) -> Self {
+        self.max_gas_price = Some(max_gas_price);
+        self
+    }
+
+    pub fn with_max_wei(mut self, max_wei: u128) -> Self {
+        self.max_wei = Some(max_wei);
+        self
+    }
+}
+
+impl Default for DeployConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The proxy a KeyManager deployment puts in front of its implementation, see
+/// [`DeployConfig::with_proxy`].
+///
+/// Only the [`ERC1967Proxy`] is compiled into this crate. The OpenZeppelin (v4) transparent and
+/// beacon proxies are given as creation code without constructor args, e.g. from a forge
+/// artifact. Which helpers apply:
+///
+/// | | `Erc1967` | `Transparent` | `Beacon` |
+/// |---|---|---|---|
+/// | [`upgrade_key_manager`] | yes | yes, unless sent by the admin | no, upgrade the beacon |
+/// | [`read_implementation`] | yes | yes | no, see [`read_beacon`] |
+/// | [`read_admin`] | zero | the admin | zero |
+/// | [`PubSubProvider::upgrade_stream`](crate::provider::PubSubProvider::upgrade_stream) | yes | yes | no |
+///
+/// Offline and CREATE2 deployments, e.g. [`prepare_deployment`], always use an [`ERC1967Proxy`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ProxyKind {
+    /// the UUPS proxy, upgraded through the implementation by its owner
+    #[default]
+    Erc1967,
+    /// `TransparentUpgradeableProxy(logic, admin, data)`, also upgradeable by `admin` through the
+    /// proxy; calls from `admin` never reach the KeyManager, so it shouldn't be the manager
+    Transparent { bytecode: Bytes, admin: Address },
+    /// `BeaconProxy(beacon, data)`, reading its implementation from a new
+    /// `UpgradeableBeacon(implementation)` owned by the deployer, who upgrades it there
+    Beacon {
+        proxy_bytecode: Bytes,
+        beacon_bytecode: Bytes,
+    },
+}
+
+impl ProxyKind {
+    /// Creation code of the proxy with its constructor args, calling `init_data` on `target`: the
+    /// implementation, or the beacon of a [`Self::Beacon`]
+    pub fn init_code(&self, target: Address, init_data: Bytes) -> Bytes {
+        let (code, args) = match self {
+            Self::Erc1967 => {
+                let args = ERC1967Proxy::constructorCall {
+                    _logic: target,
+                    _data: init_data,
+                };
+                (&ERC1967Proxy::BYTECODE, args.abi_encode())
+            }
+            Self::Transparent { bytecode, admin } => {
+                (bytecode, (target, *admin, init_data).abi_encode_params())
+            }
+            Self::Beacon { proxy_bytecode, .. } => {
+                (proxy_bytecode, (target, init_data).abi_encode_params())
+            }
+        };
+        [code.as_ref(), &args].concat().into()
+    }
+}
+
+/// Exponential backoff between receipt polls: quick on fast chains, easy on the RPC on slow ones
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ReceiptBackoff {
+    /// wait before the second poll
+    pub initial_interval: Duration,
+    /// longest wait between polls
+    pub max_interval: Duration,
+    /// each wait is this many times longer than the one before
+    pub multiplier: f64,
+}
+
+impl ReceiptBackoff {
+    pub fn new() -> Self {
+        Self {
+            initial_interval: Duration::from_millis(250),
+            max_interval: Duration::from_secs(10),
+            multiplier: 2.0,
+        }
+    }
+
+    pub fn with_initial_interval(mut self, d: Duration) -> Self {
+        self.initial_interval = d;
+        self
+    }
+
+    pub fn with_max_interval(mut self, d: Duration) -> Self {
+        self.max_interval = d;
+        self
+    }
+
+    /// multipliers below 1 are treated as 1, i.e. a constant interval
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// wait after poll number `poll` (starting at 0)
+    fn interval(&self, poll: u32) -> Duration {
+        let factor = self
+            .multiplier
+            .max(1.0)
+            .powi(poll.min(i32::MAX as u32) as i32);
+        Duration::try_from_secs_f64(self.initial_interval.as_secs_f64() * factor)
+            .unwrap_or(Duration::MAX)
+            .min(self.max_interval)
+    }
+}
+
+impl Default for ReceiptBackoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Deploy any contract from its creation transaction, e.g. the `deploy_builder` of a `sol!`
+/// binding such as [`ERC1967Proxy::deploy_builder`], logging it as `name` like the KeyManager
+/// deployments (which use this) and recording its gas in the deployment metrics.
+///
+/// Waits until the receipt's block has `cfg.confirmations` confirmations, itself counting as the
+/// first. On chains that only mine on demand, e.g. anvil without a block time, more than one
+/// confirmation waits for other transactions to be mined. Fails if that doesn't happen within
+/// `cfg.receipt_timeout`, see [`is_receipt_timeout`]. `cfg.nonce` and `cfg.verify_manager` only
+/// apply to KeyManager deployments, set the nonce on `tx` instead.
+pub async fn deploy_contract<P: Provider>(
     name: &str,
     tx: RawCallBuilder<P>,
-) -> ContractResult<Address> {
+    cfg: &DeployConfig,
+) -> Result<Deployed> {
     tracing::info!("deploying {name}");
-    let pending_tx = tx.send().await?;
+    let stopwatch = telemetry::Stopwatch::start();
+    let pending_tx = tx
+        .send()
+        .await
+        .map_err(TimeboostContractError::Deployment)?;
+    wait_deployed(name, pending_tx, cfg, stopwatch)
+        .await
+        .map_err(TimeboostContractError::Deployment)
+}
+
+/// Wait for the contract creation `pending_tx` like [`deploy_contract`]
+async fn wait_deployed(
+    name: &str,
+    pending_tx: PendingTransactionBuilder<Ethereum>,
+    cfg: &DeployConfig,
+    stopwatch: telemetry::Stopwatch,
+) -> Result<Deployed, alloy::contract::Error> {
+    let tx_hash = *pending_tx.tx_hash();
+    let receipt = wait_confirmed(pending_tx, cfg).await?;
+    let addr = receipt
+        .contract_address
+        .ok_or(alloy::contract::Error::ContractNotDeployed)?;
+
+    tracing::info!("deployed {name} at {addr:#x}");
+    telemetry::deployed(name, receipt.gas_used, stopwatch);
+    Ok(Deployed {
+        addr,
+        tx_hash,
+        gas_used: receipt.gas_used,
+        block_number: receipt.block_number,
+    })
+}
+
+/// Wait for the receipt of `pending_tx` with the confirmations, timeout and backoff of `cfg`
+async fn wait_confirmed(
+    pending_tx: PendingTransactionBuilder<Ethereum>,
+    cfg: &DeployConfig,
+) -> Result<TransactionReceipt, alloy::contract::Error> {
+    let confirmations = cfg.confirmations.max(1);
     let tx_hash = *pending_tx.tx_hash();
-    tracing::info!(%tx_hash, "waiting for tx to be mined");
+    tracing::info!(%tx_hash, confirmations, "waiting for tx to be mined");
+
+    let receipt = match &cfg.receipt_backoff {
+        Some(backoff) => {
+            let provider = pending_tx.provider().clone();
+            poll_receipt(
+                &provider,
+                tx_hash,
+                confirmations,
+                cfg.receipt_timeout,
+                backoff,
+            )
+            .await?
+        }
+        None => {
+            pending_tx
+                .with_required_confirmations(confirmations)
+                .with_timeout(cfg.receipt_timeout)
+                .get_receipt()
+                .await?
+        }
+    };
+    tracing::info!(%receipt.gas_used, %tx_hash, "tx mined");
+    Ok(receipt)
+}
+
+/// Poll for the receipt of `tx_hash` with `backoff` until its block has `confirmations`
+/// confirmations, failing like alloy's watcher once `timeout` has passed
+async fn poll_receipt<P: Provider>(
+    provider: &P,
+    tx_hash: TxHash,
+    confirmations: u64,
+    timeout: Option<Duration>,
+    backoff: &ReceiptBackoff,
+) -> Result<TransactionReceipt, alloy::contract::Error> {
+    let deadline = timeout.map(|t| crate::time::Instant::now() + t);
+    for poll in 0.. {
+        if let Some(receipt) = provider.get_transaction_receipt(tx_hash).await?
+            && let Some(block) = receipt.block_number
+            && provider.get_block_number().await? + 1 >= block + confirmations
+        {
+            return Ok(receipt);
+        }
+        let mut wait = backoff.interval(poll);
+        if let Some(deadline) = deadline {
+            let left = deadline.saturating_duration_since(crate::time::Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            wait = wait.min(left);
+        }
+        tracing::debug!(%tx_hash, ?wait, "receipt not confirmed yet");
+        crate::time::sleep(wait).await;
+    }
+    Err(alloy::contract::Error::PendingTransactionError(
+        PendingTransactionError::TxWatcher(WatchTxError::Timeout),
+    ))
+}
+
+/// A freshly deployed proxy doesn't report the manager it was initialized with, i.e. its
+/// initializer didn't take effect.
+///
+/// Checked by the deployments initializing a manager unless
+/// [`DeployConfig::verify_manager`] is off, returned as a
+/// [`TimeboostContractError::PostDeployCheck`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PostDeployCheckFailed {
+    pub proxy: Address,
+    pub expected_manager: Address,
+    pub actual_manager: Address,
+}
+
+impl fmt::Display for PostDeployCheckFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "KeyManager proxy {} reports manager {} after deployment, expected {}",
+            self.proxy, self.actual_manager, self.expected_manager
+        )
+    }
+}
+
+impl std::error::Error for PostDeployCheckFailed {}
+
+/// Fail with a [`PostDeployCheckFailed`] unless the KeyManager proxy at `proxy` has `manager`
+async fn verify_manager<P: Provider>(provider: &P, proxy: Address, manager: Address) -> Result<()> {
+    let actual = KeyManager::new(proxy, provider).manager().call().await?;
+    if actual != manager {
+        let failed = PostDeployCheckFailed {
+            proxy,
+            expected_manager: manager,
+            actual_manager: actual,
+        };
+        tracing::error!(%failed, "post-deployment check failed");
+        return Err(failed.into());
+    }
+    Ok(())
+}
+
+/// Whether `err` is a deployment giving up on its receipt after the timeout, e.g. of
+/// [`DeployConfig::receipt_timeout`]
+pub fn is_receipt_timeout(err: &TimeboostContractError) -> bool {
+    matches!(
+        err,
+        TimeboostContractError::Deployment(alloy::contract::Error::PendingTransactionError(
+            PendingTransactionError::TxWatcher(WatchTxError::Timeout)
+        ))
+    )
+}
+
+/// Given a chain provider/connector, deploy a new KeyManager contract
+pub async fn deploy_key_manager_contract<P>(provider: &P, manager: Address) -> Result<Address>
+where
+    P: Provider,
+{
+    let cfg = DeployConfig::new();
+    let report = deploy_key_manager_contract_with_config(provider, manager, &cfg).await?;
+    Ok(report.proxy_addr)
+}
+
+/// Deploy only the KeyManager implementation, without a proxy. Returns the implementation
+/// address.
+///
+/// For tests of the contract in isolation, e.g. inspecting its storage without the proxy's.
+/// KeyManager disables its initializers in the constructor, so the implementation stays
+/// uninitialized: no owner or manager, and every manager-only call reverts. Use
+/// [`deploy_key_manager_contract`] for anything production-like.
+pub async fn deploy_key_manager_implementation_only<P>(provider: &P) -> Result<Address>
+where
+    P: Provider,
+{
+    let tx = KeyManager::deploy_builder(provider);
+    let implementation = deploy_contract("KeyManager", tx, &DeployConfig::new()).await?;
+    Ok(implementation.addr)
+}
+
+/// Deploy a new KeyManager like [`deploy_key_manager_contract`], waiting for `confirmations` of
+/// both the implementation and the proxy deployment and reporting everything about the
+/// deployment worth recording
+pub async fn deploy_key_manager_contract_with_report<P>(
+    provider: &P,
+    manager: Address,
+    confirmations: u64,
+) -> Result<DeploymentReport>
+where
+    P: Provider,
+{
+    let cfg = DeployConfig::new().with_confirmations(confirmations);
+    deploy_key_manager_contract_with_config(provider, manager, &cfg).await
+}
+
+/// Deploy a new KeyManager like [`deploy_key_manager_contract`] as `cfg` says, reporting
+/// everything about the deployment worth recording.
+///
+/// Each deployment's receipt is waited for with the confirmations, timeout and polling backoff of
+/// `cfg`; a receipt not mined within the timeout fails with an error satisfying
+/// [`is_receipt_timeout`], e.g. as the chain stopped producing blocks.
+///
+/// With a [`DeployConfig::max_gas_price`], nothing is sent if the network gas price is above it
+/// and the error is a [`GasPriceTooHigh`], so that an unattended deployment can retry later
+/// rather than overpay. To also keep either transaction from bidding more, send through a
+/// provider whose [`FeeConfig`](crate::provider::FeeConfig) has the same `max_gas_price`, e.g.
+/// one from [`build_provider_with_fees`](crate::provider::build_provider_with_fees). With a
+/// [`DeployConfig::max_wei`], nothing is sent if the [`deployment_cost_estimate`] (of an
+/// [`ERC1967Proxy`] deployment) is above it and the error is a [`CostAboveBudget`]. Both are
+/// checked once up front, a price spike before the transactions are sent isn't caught.
+pub async fn deploy_key_manager_contract_with_config<P>(
+    provider: &P,
+    manager: Address,
+    cfg: &DeployConfig,
+) -> Result<DeploymentReport>
+where
+    P: Provider,
+{
+    check_limits(provider, manager, cfg).await?;
+    deploy_with_init(
+        provider,
+        key_manager_init_calldata(manager),
+        Some(manager),
+        cfg,
+    )
+    .await
+}
+
+/// Fail if the gas price or the cost of deploying a KeyManager for `manager` is above the
+/// [`DeployConfig::max_gas_price`] or [`DeployConfig::max_wei`]
+async fn check_limits<P: Provider>(
+    provider: &P,
+    manager: Address,
+    cfg: &DeployConfig,
+) -> Result<()> {
+    if let Some(max_gas_price) = cfg.max_gas_price {
+        check_gas_price(provider, max_gas_price).await?;
+    }
+    if let Some(max_wei) = cfg.max_wei {
+        let estimate = deployment_cost_estimate(provider, manager).await?;
+        if estimate.wei_cost > max_wei {
+            tracing::warn!(?estimate, %max_wei, "deployment cost above the budget");
+            return Err(CostAboveBudget { estimate, max_wei }.into());
+        }
+    }
+    Ok(())
+}
+
+/// The network gas price is above the ceiling a deployment was given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasPriceTooHigh {
+    pub gas_price: u128,
+    pub max_gas_price: u128,
+}
+
+impl fmt::Display for GasPriceTooHigh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gas price {} wei is above the maximum of {} wei",
+            self.gas_price, self.max_gas_price
+        )
+    }
+}
+
+impl std::error::Error for GasPriceTooHigh {}
+
+/// The current network gas price (`eth_gasPrice`), failing with a [`GasPriceTooHigh`] if it
+/// exceeds `max_gas_price`
+pub async fn check_gas_price<P: Provider>(provider: &P, max_gas_price: u128) -> Result<u128> {
+    let gas_price = provider.get_gas_price().await?;
+    if gas_price > max_gas_price {
+        tracing::warn!(%gas_price, %max_gas_price, "gas price above the maximum");
+        return Err(GasPriceTooHigh {
+            gas_price,
+            max_gas_price,
+        }
+        .into());
+    }
+    Ok(gas_price)
+}
+
+/// Projected cost of a KeyManager deployment at the current gas price
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostEstimate {
+    /// of the implementation and the proxy deployment, the latter including `initialize`
+    pub total_gas: u64,
+    /// wei per gas
+    pub gas_price: u128,
+    /// `total_gas * gas_price`
+    pub wei_cost: u128,
+}
+
+/// Stands in for the implementation when estimating the proxy deployment, all nonzero bytes
+/// like a real contract address so that the calldata costs as much
+const PLACEHOLDER_IMPLEMENTATION: Address = address!("0x1111111111111111111111111111111111111111");
+
+/// Estimate what [`deploy_key_manager_contract`] with `manager` would cost right now: the
+/// `eth_estimateGas` of the implementation and of the proxy deployment (which runs
+/// `initialize(manager)`), times the network gas price (`eth_gasPrice`).
+///
+/// The implementation doesn't exist yet when estimating, so the proxy is estimated against the
+/// KeyManager runtime code overridden at a placeholder address, which requires the RPC node to
+/// support state overrides in `eth_estimateGas`. What the deployment actually costs can differ
+/// as the gas price moves.
+pub async fn deployment_cost_estimate<P>(provider: &P, manager: Address) -> Result<CostEstimate>
+where
+    P: Provider,
+{
+    let implementation = KeyManager::deploy_builder(provider).into_transaction_request();
+    let implementation_gas = provider
+        .estimate_gas(implementation)
+        .await
+        .context("estimating the implementation deployment")?;
+
+    let proxy = ERC1967Proxy::deploy_builder(
+        provider,
+        PLACEHOLDER_IMPLEMENTATION,
+        key_manager_init_calldata(manager),
+    )
+    .into_transaction_request();
+    let overrides = StateOverride::from_iter([(
+        PLACEHOLDER_IMPLEMENTATION,
+        AccountOverride::default().with_code(KeyManager::DEPLOYED_BYTECODE.clone()),
+    )]);
+    let proxy_gas = provider
+        .estimate_gas(proxy)
+        .overrides(overrides)
+        .await
+        .context("estimating the proxy deployment")?;
+
+    let gas_price = provider.get_gas_price().await?;
+    let total_gas = implementation_gas + proxy_gas;
+    let estimate = CostEstimate {
+        total_gas,
+        gas_price,
+        wei_cost: u128::from(total_gas).saturating_mul(gas_price),
+    };
+    tracing::debug!(?estimate, "estimated deployment cost");
+    Ok(estimate)
+}
+
+/// The projected cost of a deployment is above the budget it was given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CostAboveBudget {
+    pub estimate: CostEstimate,
+    pub max_wei: u128,
+}
+
+impl fmt::Display for CostAboveBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "deployment would cost {} wei ({} gas at {} wei), above the budget of {} wei",
+            self.estimate.wei_cost, self.estimate.total_gas, self.estimate.gas_price, self.max_wei
+        )
+    }
+}
+
+impl std::error::Error for CostAboveBudget {}
+
+/// Deploy a new KeyManager like [`deploy_key_manager_contract`] and register committee 0 right
+/// away, waiting for all receipts. The provider's signer has to be the `manager`.
+///
+/// Returns the proxy address and the `CommitteeCreated` event of the registration; fails if the
+/// registration reverts.
+pub async fn deploy_key_manager_with_committee<P>(
+    provider: &P,
+    manager: Address,
+    timestamp: u64,
+    members: Vec<CommitteeMemberSol>,
+) -> Result<(Address, CommitteeCreated)>
+where
+    P: Provider,
+{
+    let addr = deploy_key_manager_contract(provider, manager).await?;
+    let contract = KeyManager::new(addr, provider);
+    let call = contract.setNextCommittee(timestamp, members);
+    let receipt = telemetry::traced(addr, call)
+        .send()
+        .await
+        .context("failed to register the initial committee")?
+        .get_receipt()
+        .await?;
+    if !receipt.status() {
+        let tx_hash = receipt.transaction_hash;
+        let err = anyhow::anyhow!("initial committee registration {tx_hash} reverted");
+        return Err(err.into());
+    }
+    let created = receipt
+        .logs()
+        .iter()
+        .find_map(|log| log.log_decode::<CommitteeCreated>().ok())
+        .context("no CommitteeCreated event in the registration receipt")?;
+    tracing::info!(id = %created.data().id, "registered initial committee");
+    Ok((addr, created.inner.data))
+}
+
+/// `initialize(manager)`, the calldata the proxy calls the implementation with on deployment,
+/// e.g. to verify a deployment or build the proxy's constructor args
+pub fn key_manager_init_calldata(manager: Address) -> Bytes {
+    KeyManager::initializeCall {
+        initialManager: manager,
+    }
+    .abi_encode()
+    .into()
+}
+
+/// Deploy a new KeyManager like [`deploy_key_manager_contract`], with the proxy calling the
+/// implementation with `init_calldata` rather than `initialize(manager)`, e.g. for another
+/// initializer. Empty calldata leaves the proxy uninitialized.
+pub async fn deploy_key_manager_with_init<P>(provider: &P, init_calldata: Bytes) -> Result<Address>
+where
+    P: Provider,
+{
+    let report = deploy_with_init(provider, init_calldata, None, &DeployConfig::new()).await?;
+    Ok(report.proxy_addr)
+}
+
+async fn deploy_with_init<P>(
+    provider: &P,
+    init_data: Bytes,
+    manager: Option<Address>,
+    cfg: &DeployConfig,
+) -> Result<DeploymentReport>
+where
+    P: Provider,
+{
+    let (deployment_id, span) = deployment_span();
+    async move {
+        let chain_id = provider.get_chain_id().await?;
+        // first deploy the implementation contract
+        let mut tx = KeyManager::deploy_builder(&provider);
+        if let Some(nonce) = cfg.nonce {
+            tx = tx.nonce(nonce);
+        }
+        let implementation = deploy_contract("KeyManager", tx, cfg).await?;
+        Span::current().record("implementation", field::display(implementation.addr));
+
+        // then deploy the proxy, point to the implementation contract and initialize it
+        let (proxy, beacon) = deploy_proxy(&provider, implementation.addr, init_data, cfg).await?;
+        if let Some(manager) = manager
+            && cfg.verify_manager
+        {
+            verify_manager(&provider, proxy.addr, manager).await?;
+        }
+        let beacon_gas_used = beacon.map_or(0, |beacon| beacon.gas_used);
+        let total_gas_used = implementation.gas_used + beacon_gas_used + proxy.gas_used;
+        Span::current()
+            .record("proxy", field::display(proxy.addr))
+            .record("gas_used", total_gas_used);
+        tracing::info!("deployed KeyManagerProxy at {:#x}", proxy.addr);
+        Ok(DeploymentReport {
+            chain_id,
+            manager,
+            implementation_addr: implementation.addr,
+            proxy_addr: proxy.addr,
+            beacon_addr: beacon.map(|beacon| beacon.addr),
+            impl_tx_hash: implementation.tx_hash,
+            proxy_tx_hash: proxy.tx_hash,
+            total_gas_used,
+            deployed_block: proxy.block_number,
+            deployment_id,
+        })
+    }
+    .instrument(span)
+    .await
+}
+
+/// Deploy the `cfg.proxy` in front of `implementation`, initialized with `init_data`, after the
+/// beacon it reads the implementation from for a [`ProxyKind::Beacon`], returned second
+async fn deploy_proxy<P: Provider>(
+    provider: &P,
+    implementation: Address,
+    init_data: Bytes,
+    cfg: &DeployConfig,
+) -> Result<(Deployed, Option<Deployed>)> {
+    let mut nonce = cfg.nonce.map(|nonce| nonce + 1);
+    let beacon = match &cfg.proxy {
+        ProxyKind::Beacon {
+            beacon_bytecode, ..
+        } => {
+            let code = [beacon_bytecode.as_ref(), &implementation.abi_encode()].concat();
+            let mut tx = RawCallBuilder::new_raw_deploy(provider, code.into());
+            if let Some(nonce) = nonce.as_mut() {
+                tx = tx.nonce(*nonce);
+                *nonce += 1;
+            }
+            Some(deploy_contract("KeyManagerBeacon", tx, cfg).await?)
+        }
+        _ => None,
+    };
+    let target = beacon.map_or(implementation, |beacon| beacon.addr);
+    let mut tx = RawCallBuilder::new_raw_deploy(provider, cfg.proxy.init_code(target, init_data));
+    if let Some(nonce) = nonce {
+        tx = tx.nonce(nonce);
+    }
+    let proxy = deploy_contract("KeyManagerProxy", tx, cfg).await?;
+    Ok((proxy, beacon))
+}
+
+/// Gas and fees of the transactions of [`prepare_deployment`], which can't be filled in from a
+/// node when signing offline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OfflineTxParams {
+    pub chain_id: u64,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+    /// gas limit of the implementation deployment, see [`deployment_cost_estimate`]
+    pub implementation_gas: u64,
+    /// gas limit of the proxy deployment
+    pub proxy_gas: u64,
+}
+
+/// A KeyManager deployment signed ahead of time, to be sent with [`broadcast_prepared_deployment`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreparedDeployment {
+    pub chain_id: u64,
+    /// the signing account
+    #[serde(with = "crate::address_hex")]
+    pub deployer: Address,
+    #[serde(with = "crate::address_hex")]
+    pub manager: Address,
+    /// nonce of the implementation deployment, the proxy's being the next
+    pub nonce: u64,
+    #[serde(with = "crate::address_hex")]
+    pub implementation_addr: Address,
+    #[serde(with = "crate::address_hex")]
+    pub proxy_addr: Address,
+    /// EIP-2718 encoded signed transactions
+    pub implementation_tx: Bytes,
+    pub proxy_tx: Bytes,
+}
+
+/// Sign the transactions of a KeyManager deployment managed by `manager` without a node, e.g. on
+/// an air-gapped machine holding the key, for [`broadcast_prepared_deployment`] to send later.
+///
+/// The implementation is created with `nonce` and the proxy with the next one, so both addresses
+/// are known up front; the deployment only succeeds if these are the wallet's next nonces when
+/// broadcast.
+pub async fn prepare_deployment<W>(
+    wallet: &W,
+    manager: Address,
+    nonce: u64,
+    params: &OfflineTxParams,
+) -> Result<PreparedDeployment>
+where
+    W: NetworkWallet<Ethereum>,
+{
+    let deployer = wallet.default_signer_address();
+    let implementation_addr = deployer.create(nonce);
+    let proxy_addr = deployer.create(nonce + 1);
+    let args = ERC1967Proxy::constructorCall {
+        _logic: implementation_addr,
+        _data: key_manager_init_calldata(manager),
+    }
+    .abi_encode();
+    let proxy_code: Bytes = [ERC1967Proxy::BYTECODE.as_ref(), &args].concat().into();
+
+    let sign = async |code: Bytes, nonce, gas| {
+        let tx = TransactionRequest::default()
+            .with_from(deployer)
+            .with_deploy_code(code)
+            .with_chain_id(params.chain_id)
+            .with_nonce(nonce)
+            .with_gas_limit(gas)
+            .with_max_fee_per_gas(params.max_fee_per_gas)
+            .with_max_priority_fee_per_gas(params.max_priority_fee_per_gas);
+        let signed = tx
+            .build(wallet)
+            .await
+            .context("failed to sign deployment")?;
+        anyhow::Ok(Bytes::from(signed.encoded_2718()))
+    };
+    let implementation_tx = sign(
+        KeyManager::BYTECODE.clone(),
+        nonce,
+        params.implementation_gas,
+    )
+    .await?;
+    let proxy_tx = sign(proxy_code, nonce + 1, params.proxy_gas).await?;
+    Ok(PreparedDeployment {
+        chain_id: params.chain_id,
+        deployer,
+        manager,
+        nonce,
+        implementation_addr,
+        proxy_addr,
+        implementation_tx,
+        proxy_tx,
+    })
+}
+
+/// The deployer's next nonce isn't the one a [`PreparedDeployment`] was signed with, e.g. as
+/// another transaction was sent from it in the meantime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NonceMismatch {
+    pub deployer: Address,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for NonceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "next nonce of {} is {}, the deployment was prepared for {}",
+            self.deployer, self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for NonceMismatch {}
+
+/// Send the transactions of `prepared` and wait for them like
+/// [`deploy_key_manager_contract_with_config`].
+///
+/// Fails without sending anything if `provider` serves another chain (with a
+/// [`TimeboostContractError::ChainIdMismatch`]) or the deployer's next nonce isn't the prepared
+/// one (with a [`TimeboostContractError::NonceMismatch`]).
+pub async fn broadcast_prepared_deployment<P>(
+    provider: &P,
+    prepared: &PreparedDeployment,
+    cfg: &DeployConfig,
+) -> Result<DeploymentReport>
+where
+    P: Provider,
+{
+    let (deployment_id, span) = deployment_span();
+    async move {
+        let chain_id = provider.get_chain_id().await?;
+        if chain_id != prepared.chain_id {
+            return Err(crate::provider::ChainIdMismatch {
+                expected: prepared.chain_id,
+                actual: chain_id,
+            }
+            .into());
+        }
+        let next_nonce = provider.get_transaction_count(prepared.deployer).await?;
+        if next_nonce != prepared.nonce {
+            return Err(NonceMismatch {
+                deployer: prepared.deployer,
+                expected: prepared.nonce,
+                actual: next_nonce,
+            }
+            .into());
+        }
+
+        let mut deployed = Vec::with_capacity(2);
+        for (name, raw) in [
+            ("KeyManager", &prepared.implementation_tx),
+            ("KeyManagerProxy", &prepared.proxy_tx),
+        ] {
+            tracing::info!("broadcasting {name}");
+            let stopwatch = telemetry::Stopwatch::start();
+            let pending_tx = provider.send_raw_transaction(raw).await?;
+            deployed.push(wait_deployed(name, pending_tx, cfg, stopwatch).await?);
+        }
+        let [implementation, proxy] = deployed[..] else {
+            unreachable!("two deployments")
+        };
+        if cfg.verify_manager {
+            verify_manager(&provider, proxy.addr, prepared.manager).await?;
+        }
+        let total_gas_used = implementation.gas_used + proxy.gas_used;
+        Span::current()
+            .record("implementation", field::display(implementation.addr))
+            .record("proxy", field::display(proxy.addr))
+            .record("gas_used", total_gas_used);
+        Ok(DeploymentReport {
+            chain_id,
+            manager: Some(prepared.manager),
+            implementation_addr: implementation.addr,
+            proxy_addr: proxy.addr,
+            beacon_addr: None,
+            impl_tx_hash: implementation.tx_hash,
+            proxy_tx_hash: proxy.tx_hash,
+            total_gas_used,
+            deployed_block: proxy.block_number,
+            deployment_id,
+        })
+    }
+    .instrument(span)
+    .await
+}
+
+/// Deploy one KeyManager per entry of `managers` concurrently, like
+/// [`deploy_key_manager_contract_with_config`], returning the reports in the order of `managers`.
+///
+/// All transactions come from the provider's signer, their nonces are assigned by its nonce
+/// filler, which has to be caching (as the recommended fillers' is) for concurrent sends not to
+/// reuse nonces. The first failed deployment fails the whole batch, dropping the others, though
+/// transactions already sent may still be mined.
+pub async fn deploy_key_managers<P>(
+    provider: &P,
+    managers: &[Address],
+) -> Result<Vec<DeploymentReport>>
+where
+    P: Provider,
+{
+    let cfg = DeployConfig::new();
+    try_join_all(
+        managers
+            .iter()
+            .map(|manager| deploy_key_manager_contract_with_config(provider, *manager, &cfg)),
+    )
+    .await
+}
+
+/// The KeyManager proxy at `existing` if it is one managed by `manager`, otherwise a newly
+/// deployed one (see [`deploy_key_manager_contract`]), making deployment scripts safe to re-run.
+///
+/// `existing` is kept if there is code at it and its `manager()` returns `manager`; failing that,
+/// e.g. for an EOA, another contract or a KeyManager with a different manager, a new one is
+/// deployed. Transport errors while checking are returned rather than taken as a failed check,
+/// so that a flaky RPC doesn't orphan a live KeyManager.
+pub async fn ensure_key_manager<P: Provider>(
+    provider: &P,
+    existing: Option<Address>,
+    manager: Address,
+) -> Result<Address> {
+    if let Some(addr) = existing {
+        if is_key_manager_of(provider, addr, manager).await? {
+            tracing::info!(%addr, "reusing deployed KeyManager");
+            return Ok(addr);
+        }
+        tracing::warn!(%addr, "not a KeyManager of {manager}, deploying a new one");
+    }
+    deploy_key_manager_contract(provider, manager).await
+}
+
+/// whether `addr` is a KeyManager proxy whose `manager()` is `manager`
+async fn is_key_manager_of<P: Provider>(
+    provider: &P,
+    addr: Address,
+    manager: Address,
+) -> Result<bool> {
+    Ok(proxied_manager(provider, addr).await? == Some(manager))
+}
+
+/// Whether `addr` is a KeyManager behind an EIP-1967 proxy: it holds code, its implementation
+/// slot (or that of its beacon) points at code, and `manager()` answers through it. Anything
+/// else, e.g. an account without code, a bare implementation or a proxy of another contract, is
+/// `false`.
+///
+/// Like [`ensure_key_manager`], transport errors are returned rather than taken as a failed
+/// check.
+pub async fn is_key_manager<P: Provider>(provider: &P, addr: Address) -> Result<bool> {
+    Ok(proxied_manager(provider, addr).await?.is_some())
+}
+
+/// `manager()` of the KeyManager proxy at `addr`, `None` if it isn't one
+async fn proxied_manager<P: Provider>(provider: &P, addr: Address) -> Result<Option<Address>> {
+    if provider.get_code_at(addr).await?.is_empty() {
+        return Ok(None);
+    }
+    let mut implementation = read_implementation(provider, addr).await?;
+    if implementation.is_zero() {
+        implementation = match beacon_implementation(provider, addr).await? {
+            Some(implementation) => implementation,
+            None => return Ok(None),
+        };
+    }
+    if implementation.is_zero() || provider.get_code_at(implementation).await?.is_empty() {
+        return Ok(None);
+    }
+    match KeyManager::new(addr, provider).manager().call().await {
+        Ok(manager) => Ok(Some(manager)),
+        Err(err @ alloy::contract::Error::TransportError(_)) if err.as_revert_data().is_none() => {
+            Err(err.into())
+        }
+        // reverted or returned something else than an address
+        Err(_) => Ok(None),
+    }
+}
+
+/// The CREATE2 factory at the same address on most chains: Arachnid's deterministic deployment
+/// proxy, deployed by a keyless pre-signed transaction. Called with `salt ++ init_code` as
+/// calldata, it CREATE2s the init code with that salt. Anvil has it preinstalled, on other chains
+/// it has to be deployed first if missing.
+pub const CREATE2_FACTORY: Address = address!("0x4e59b44847b379578588920cA78FbF26c0B4956C");
+
+/// Creation code of the implementation and the proxy initialized with `manager`, for CREATE2
+/// through `factory` with `salt`
+fn key_manager_init_codes(factory: Address, salt: B256, manager: Address) -> (Bytes, Bytes) {
+    let implementation = KeyManager::BYTECODE.clone();
+    let implementation_addr = factory.create2(salt, keccak256(&implementation));
+    let args = ERC1967Proxy::constructorCall {
+        _logic: implementation_addr,
+        _data: key_manager_init_calldata(manager),
+    }
+    .abi_encode();
+    let proxy = [ERC1967Proxy::BYTECODE.as_ref(), &args].concat().into();
+    (implementation, proxy)
+}
+
+/// Address the KeyManager proxy gets from [`deploy_key_manager_contract_create2`] through the
+/// CREATE2 factory `deployer` (usually [`CREATE2_FACTORY`]) with `salt` and `manager`.
+///
+/// Depends only on these and the contract bytecode, not on the chain or the sending account, so
+/// the same inputs give the same address on every chain.
+pub fn predict_key_manager_address(deployer: Address, salt: B256, manager: Address) -> Address {
+    let (_, proxy) = key_manager_init_codes(deployer, salt, manager);
+    deployer.create2(salt, keccak256(&proxy))
+}
+
+/// Why a deployment through the [`CREATE2_FACTORY`] couldn't be made
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Create2Error {
+    /// there's no factory contract at the address
+    NoFactory(Address),
+    /// a KeyManager proxy for this salt and manager is already deployed at the address
+    AlreadyDeployed(Address),
+    /// the factory call was mined without creating the contract, e.g. as it reverted
+    NotCreated {
+        name: &'static str,
+        addr: Address,
+        tx_hash: TxHash,
+    },
+    /// CREATE2 deployments always use an [`ERC1967Proxy`], see [`ProxyKind`]
+    UnsupportedProxy,
+}
+
+impl fmt::Display for Create2Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoFactory(addr) => write!(f, "no CREATE2 factory at {addr}"),
+            Self::AlreadyDeployed(addr) => write!(f, "KeyManager proxy already deployed at {addr}"),
+            Self::NotCreated {
+                name,
+                addr,
+                tx_hash,
+            } => write!(
+                f,
+                "CREATE2 deployment of {name} in {tx_hash} created nothing at {addr}"
+            ),
+            Self::UnsupportedProxy => write!(f, "CREATE2 deployments only support an ERC1967Proxy"),
+        }
+    }
+}
+
+impl std::error::Error for Create2Error {}
+
+/// Deploy a new KeyManager through the [`CREATE2_FACTORY`], so that the proxy lands at
+/// [`predict_key_manager_address`] on every chain. An implementation already deployed with the
+/// same salt is reused.
+pub async fn deploy_key_manager_contract_create2<P: Provider>(
+    provider: &P,
+    manager: Address,
+    salt: B256,
+) -> Result<Address> {
+    let cfg = DeployConfig::new();
+    deploy_key_manager_contract_create2_with_config(provider, manager, salt, &cfg).await
+}
+
+/// Deploy a new KeyManager like [`deploy_key_manager_contract_create2`] as `cfg` says: its
+/// receipts are waited for, its limits checked and its manager verified like those of
+/// [`deploy_key_manager_contract_with_config`]. `cfg.nonce` is the nonce of the first factory call
+/// actually sent, and `cfg.proxy` has to be the default [`ProxyKind::Erc1967`], otherwise the
+/// error is a [`Create2Error::UnsupportedProxy`].
+pub async fn deploy_key_manager_contract_create2_with_config<P: Provider>(
+    provider: &P,
+    manager: Address,
+    salt: B256,
+    cfg: &DeployConfig,
+) -> Result<Address> {
+    if cfg.proxy != ProxyKind::Erc1967 {
+        return Err(Create2Error::UnsupportedProxy.into());
+    }
+    let factory = CREATE2_FACTORY;
+    if provider.get_code_at(factory).await?.is_empty() {
+        return Err(Create2Error::NoFactory(factory).into());
+    }
+    let (implementation, proxy) = key_manager_init_codes(factory, salt, manager);
+    let implementation_addr = factory.create2(salt, keccak256(&implementation));
+    let proxy_addr = factory.create2(salt, keccak256(&proxy));
+    if !provider.get_code_at(proxy_addr).await?.is_empty() {
+        return Err(Create2Error::AlreadyDeployed(proxy_addr).into());
+    }
+    check_limits(provider, manager, cfg).await?;
+
+    let (_, span) = deployment_span();
+    span.record("implementation", field::display(implementation_addr))
+        .record("proxy", field::display(proxy_addr));
+    async move {
+        let mut gas_used = 0;
+        let mut nonce = cfg.nonce;
+        for (name, addr, init_code) in [
+            ("KeyManager", implementation_addr, implementation),
+            ("KeyManagerProxy", proxy_addr, proxy),
+        ] {
+            if !provider.get_code_at(addr).await?.is_empty() {
+                tracing::info!("{name} already deployed at {addr:#x}");
+                continue;
+            }
+            tracing::info!("deploying {name} via CREATE2");
+            let stopwatch = telemetry::Stopwatch::start();
+            let input = [salt.as_slice(), &init_code].concat();
+            let mut tx = RawCallBuilder::new_raw(provider, input.into()).to(factory);
+            if let Some(nonce) = nonce.as_mut() {
+                tx = tx.nonce(*nonce);
+                *nonce += 1;
+            }
+            let pending = telemetry::traced(factory, tx)
+                .send()
+                .await
+                .map_err(TimeboostContractError::Deployment)?;
+            let receipt = wait_confirmed(pending, cfg)
+                .await
+                .map_err(TimeboostContractError::Deployment)?;
+            if !receipt.status() || provider.get_code_at(addr).await?.is_empty() {
+                let tx_hash = receipt.transaction_hash;
+                return Err(Create2Error::NotCreated {
+                    name,
+                    addr,
+                    tx_hash,
+                }
+                .into());
+            }
+            gas_used += receipt.gas_used;
+            tracing::info!("deployed {name} at {addr:#x}");
+            telemetry::deployed(name, receipt.gas_used, stopwatch);
+        }
+        if cfg.verify_manager {
+            verify_manager(provider, proxy_addr, manager).await?;
+        }
+        Span::current().record("gas_used", gas_used);
+        Ok::<_, TimeboostContractError>(proxy_addr)
+    }
+    .instrument(span)
+    .await
+}
+
+/// Resolve the library placeholders of unlinked creation bytecode, as emitted by solc >= 0.5
+/// (e.g. in a forge artifact's `bytecode.object`).
+///
+/// Linking format: the hex bytecode contains, for each reference to a library, the 40 character
+/// placeholder `__$<h>$__` where `<h>` is the first 34 hex characters of the keccak256 of the
+/// library's fully qualified name, e.g. `src/lib/Sort.sol:Sort`. `libraries` maps fully qualified
+/// names to deployed addresses, each placeholder is replaced with the corresponding address.
+///
+/// Errors listing every placeholder left unresolved, by its hash, since the name itself isn't
+/// recoverable from the bytecode.
+pub fn link_bytecode(unlinked: &str, libraries: &BTreeMap<String, Address>) -> Result<Bytes> {
+    let placeholder = |name: &str| format!("__${}$__", &hex::encode(keccak256(name))[..34]);
+    let mut code = unlinked.strip_prefix("0x").unwrap_or(unlinked).to_string();
+    for (name, addr) in libraries {
+        code = code.replace(&placeholder(name), &hex::encode(addr));
+    }
+
+    let mut missing = code
+        .match_indices("__$")
+        .filter_map(|(i, _)| code.get(i..i + 40))
+        .collect::<Vec<_>>();
+    missing.sort_unstable();
+    missing.dedup();
+    if !missing.is_empty() {
+        let err = anyhow::anyhow!("unresolved library placeholders: {}", missing.join(", "));
+        return Err(TimeboostContractError::Config(err));
+    }
+    let code = hex::decode(code).map_err(|err| anyhow::anyhow!("invalid bytecode: {err}"))?;
+    Ok(code.into())
+}
+
+/// Deploy a contract from unlinked creation bytecode, resolving its library references with
+/// [`link_bytecode`] first. `constructor_args` are the ABI-encoded constructor arguments.
+pub async fn deploy_linked_contract<P: Provider>(
+    provider: &P,
+    name: &str,
+    unlinked: &str,
+    libraries: &BTreeMap<String, Address>,
+    constructor_args: &[u8],
+) -> Result<Address> {
+    let mut code = link_bytecode(unlinked, libraries)?.to_vec();
+    code.extend_from_slice(constructor_args);
+    let tx = RawCallBuilder::new_raw_deploy(provider, code.into());
+    Ok(deploy_contract(name, tx, &DeployConfig::new()).await?.addr)
+}
+
+/// Run the full KeyManager deployment against a local Anvil fork of the chain at `fork_url`,
+/// without touching the real chain, to learn its gas cost and whether any step reverts.
+///
+/// The fork deploys from Anvil's first dev account, so the reported addresses are the ones that
+/// account would get, they only carry over if the real deployer has the same address and nonce.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn dry_run_deployment_on_fork(
+    fork_url: Url,
+    manager: Address,
+) -> Result<DeploymentReport> {
+    let provider = crate::TestChainBuilder::new().with_fork(fork_url).spawn()?;
+    match deploy_key_manager_contract_with_config(&provider, manager, &DeployConfig::new()).await {
+        Ok(report) => {
+            tracing::info!(?report, "deployment dry run succeeded");
+            Ok(report)
+        }
+        Err(err) => {
+            let reason = err
+                .contract_error()
+                .and_then(|err| err.as_decoded_interface_error::<KeyManager::KeyManagerErrors>());
+            tracing::error!(%err, ?reason, "deployment dry run reverted");
+            Err(err)
+        }
+    }
+}
+
+/// Resolve a manager given either as an address or as an ENS name (e.g. `team.eth`), using the
+/// ENS registry of the chain `provider` is connected to.
+/// Fails on chains without the ENS registry, or if the name has no address record.
+#[cfg(feature = "ens")]
+pub async fn resolve_manager<P: Provider>(
+    provider: &P,
+    manager: &alloy::ens::NameOrAddress,
+) -> Result<Address> {
+    use alloy::ens::{ENS_ADDRESS, NameOrAddress, ProviderEnsExt};
+    use anyhow::anyhow;
+
+    let name = match manager {
+        NameOrAddress::Address(addr) => return Ok(*addr),
+        NameOrAddress::Name(name) => name,
+    };
+    if provider.get_code_at(ENS_ADDRESS).await?.is_empty() {
+        let chain_id = provider.get_chain_id().await?;
+        let err = anyhow!("cannot resolve manager {name:?}: no ENS registry on chain {chain_id}");
+        return Err(err.into());
+    }
+    let addr = provider
+        .resolve_name(name)
+        .await
+        .with_context(|| format!("failed to resolve manager {name:?}"))?;
+    if addr.is_zero() {
+        return Err(anyhow!("manager {name:?} resolves to the zero address").into());
+    }
+    tracing::info!(%name, %addr, "resolved manager ENS name");
+    Ok(addr)
+}
+
+/// Keccak hash of the KeyManager runtime bytecode the bindings were generated from,
+/// with immutable variables left zeroed as emitted by the compiler.
+pub fn key_manager_runtime_code_hash() -> B256 {
+    keccak256(&KeyManager::DEPLOYED_BYTECODE)
+}
+
+/// EIP-1967 storage slot of a proxy's implementation address
+pub const IMPLEMENTATION_SLOT: B256 =
+    b256!("0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc");
+
+/// The implementation the proxy at `proxy_addr` currently delegates to
+pub async fn read_implementation<P: Provider>(
+    provider: &P,
+    proxy_addr: Address,
+) -> Result<Address> {
+    let word = provider
+        .get_storage_at(proxy_addr, IMPLEMENTATION_SLOT.into())
+        .await?;
+    Ok(Address::from_word(word.into()))
+}
+
+/// EIP-1967 storage slot of a proxy's admin address
+pub const ADMIN_SLOT: B256 =
+    b256!("0xb53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103");
+
+/// The admin of the proxy at `proxy_addr`. UUPS proxies like the KeyManager's are upgraded by the
+/// implementation's owner and leave this unset, i.e. the zero address.
+pub async fn read_admin<P: Provider>(provider: &P, proxy_addr: Address) -> Result<Address> {
+    let word = provider
+        .get_storage_at(proxy_addr, ADMIN_SLOT.into())
+        .await?;
+    Ok(Address::from_word(word.into()))
+}
+
+/// EIP-1967 storage slot of a beacon proxy's beacon address
+pub const BEACON_SLOT: B256 =
+    b256!("0xa3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50");
+
+/// The beacon the proxy at `proxy_addr` reads its implementation from, the zero address unless it
+/// is a beacon proxy, e.g. of a [`ProxyKind::Beacon`] deployment
+pub async fn read_beacon<P: Provider>(provider: &P, proxy_addr: Address) -> Result<Address> {
+    let word = provider
+        .get_storage_at(proxy_addr, BEACON_SLOT.into())
+        .await?;
+    Ok(Address::from_word(word.into()))
+}
+
+/// `implementation()` of the beacon of the proxy at `proxy_addr`, `None` without a beacon
+async fn beacon_implementation<P: Provider>(
+    provider: &P,
+    proxy_addr: Address,
+) -> Result<Option<Address>> {
+    let beacon = read_beacon(provider, proxy_addr).await?;
+    if beacon.is_zero() || provider.get_code_at(beacon).await?.is_empty() {
+        return Ok(None);
+    }
+    match IBeacon::new(beacon, provider).implementation().call().await {
+        Ok(implementation) => Ok(Some(implementation)),
+        Err(err) if err.as_revert_data().is_none() => Err(err.into()),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Deploy `new_impl_bytecode` (creation code without constructor arguments) and upgrade the
+/// KeyManager proxy at `proxy_addr` to it, calling `migration` on the new implementation through
+/// the proxy as part of the upgrade if given. The provider's signer has to be the owner.
+///
+/// Returns the new implementation address, after checking that the proxy's implementation slot
+/// points at it.
+pub async fn upgrade_key_manager<P: Provider>(
+    provider: &P,
+    proxy_addr: Address,
+    new_impl_bytecode: Bytes,
+    migration: Option<Bytes>,
+) -> Result<Address> {
+    let previous = read_implementation(provider, proxy_addr).await?;
+    let tx = RawCallBuilder::new_raw_deploy(provider, new_impl_bytecode);
+    let implementation = deploy_contract("KeyManager", tx, &DeployConfig::new()).await?;
+
+    let proxy = KeyManager::new(proxy_addr, provider);
+    let pending = match migration {
+        Some(data) => {
+            let call = proxy.upgradeToAndCall(implementation.addr, data);
+            telemetry::traced(proxy_addr, call).send().await
+        }
+        None => {
+            let call = proxy.upgradeTo(implementation.addr);
+            telemetry::traced(proxy_addr, call).send().await
+        }
+    };
+    let receipt = pending
+        .map_err(|err| {
+            let reason = err.as_decoded_interface_error::<KeyManager::KeyManagerErrors>();
+            tracing::error!(%err, ?reason, "upgrade reverted");
+            err
+        })?
+        .get_receipt()
+        .await?;
+    if !receipt.status() {
+        let tx_hash = receipt.transaction_hash;
+        return Err(anyhow::anyhow!("upgrade transaction {tx_hash} reverted").into());
+    }
+
+    let current = read_implementation(provider, proxy_addr).await?;
+    if current != implementation.addr {
+        let err =
+            anyhow::anyhow!("proxy at {proxy_addr} still points at {current} after the upgrade");
+        return Err(err.into());
+    }
+    tracing::info!(%proxy_addr, %previous, %current, "upgraded KeyManager");
+    Ok(current)
+}
+
+/// Check that the runtime bytecode at `impl_addr` is the KeyManager implementation of this crate.
+///
+/// The only immutable in KeyManager is `UUPSUpgradeable.__self`, the implementation's own address,
+/// so every word equal to the left-padded `impl_addr` is zeroed before hashing.
+/// A `false` result means the deployment doesn't match this crate's version.
+pub async fn verify_deployed_bytecode<P: Provider>(
+    provider: &P,
+    impl_addr: Address,
+) -> Result<bool> {
+    let mut code = provider.get_code_at(impl_addr).await?.to_vec();
+    let self_word = B256::left_padding_from(impl_addr.as_slice());
+    let mut i = 0;
+    while i + 32 <= code.len() {
+        if code[i..i + 32] == self_word[..] {
+            code[i..i + 32].fill(0);
+            i += 32;
+        } else {
+            i += 1;
+        }
+    }
+
+    let matches = keccak256(&code) == key_manager_runtime_code_hash();
+    if !matches {
+        tracing::warn!(%impl_addr, "deployed bytecode doesn't match KeyManager artifact");
+    }
+    Ok(matches)
+}
+
+/// Byte ranges [`verify_implementation_bytecode`] leaves out of the comparison
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BytecodeMask {
+    /// offsets into the runtime code, e.g. immutable references from the compiler output
+    pub ranges: Vec<Range<usize>>,
+    /// whether to ignore the trailing CBOR metadata (with the source hash) of both codes
+    pub metadata: bool,
+}
+
+impl BytecodeMask {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_range(mut self, range: Range<usize>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+
+    pub fn with_metadata(mut self) -> Self {
+        self.metadata = true;
+        self
+    }
+
+    /// whether byte `i` is compared
+    fn covers(&self, i: usize) -> bool {
+        !self.ranges.iter().any(|r| r.contains(&i))
+    }
+}
+
+/// `code` without the trailing CBOR metadata, whose length the last two bytes give
+fn strip_metadata(code: &[u8]) -> &[u8] {
+    let Some((rest, len_bytes)) = code.split_last_chunk::<2>() else {
+        return code;
+    };
+    let len = u16::from_be_bytes(*len_bytes) as usize;
+    rest.len().checked_sub(len).map_or(code, |end| &code[..end])
+}
+
+/// The runtime code at an address differs from the expected code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytecodeMismatch {
+    pub addr: Address,
+    /// lengths as compared, i.e. without masked metadata
+    pub expected_len: usize,
+    pub actual_len: usize,
+    /// first offset at which unmasked bytes differ, `None` if one is a prefix of the other
+    pub first_difference: Option<usize>,
+    /// number of unmasked differing bytes within the common length
+    pub differing_bytes: usize,
+}
+
+impl fmt::Display for BytecodeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "runtime code at {} doesn't match: expected {} bytes, found {}",
+            self.addr, self.expected_len, self.actual_len
+        )?;
+        if let Some(offset) = self.first_difference {
+            write!(
+                f,
+                ", {} bytes differ, the first at offset {offset}",
+                self.differing_bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BytecodeMismatch {}
+
+/// Check that the runtime code at `impl_addr` is `expected_runtime`, apart from the bytes `mask`
+/// covers, to catch build drift or a tampered deployment. On a mismatch the error is a
+/// [`BytecodeMismatch`] (downcast for the details), also if there is no code at all.
+///
+/// See [`verify_deployed_bytecode`] for checking a KeyManager implementation against the
+/// bindings' bytecode without knowing its immutable references.
+pub async fn verify_implementation_bytecode<P: Provider>(
+    provider: &P,
+    impl_addr: Address,
+    expected_runtime: &[u8],
+    mask: &BytecodeMask,
+) -> Result<()> {
+    let code = provider.get_code_at(impl_addr).await?;
+    let (expected, actual) = if mask.metadata {
+        (strip_metadata(expected_runtime), strip_metadata(&code))
+    } else {
+        (expected_runtime, &code[..])
+    };
+    let mut differing = (0..expected.len().min(actual.len()))
+        .filter(|&i| mask.covers(i) && expected[i] != actual[i]);
+    let first_difference = differing.next();
+    if first_difference.is_none() && expected.len() == actual.len() {
+        return Ok(());
+    }
+    let mismatch = BytecodeMismatch {
+        addr: impl_addr,
+        expected_len: expected.len(),
+        actual_len: actual.len(),
+        first_difference,
+        differing_bytes: first_difference.map_or(0, |_| 1 + differing.count()),
+    };
+    tracing::warn!(%mismatch, "unexpected runtime code");
+    Err(mismatch.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BytecodeMask, Create2Error, DeployConfig, Deployed, DeploymentArtifactError,
+        DeploymentReport, GasPriceTooHigh, NonceMismatch, OfflineTxParams, PostDeployCheckFailed,
+        PreparedDeployment, ProxyKind, ReceiptBackoff, broadcast_prepared_deployment,
+        check_gas_price, deploy_contract, deploy_key_manager_contract,
+        deploy_key_manager_contract_create2, deploy_key_manager_contract_create2_with_config,
+        deploy_key_manager_contract_with_config, deploy_key_manager_contract_with_report,
+        deploy_key_manager_implementation_only, deploy_key_manager_with_committee,
+        deploy_key_manager_with_init, deploy_key_managers, deployment_cost_estimate,
+        ensure_key_manager, is_key_manager, is_receipt_timeout, key_manager_init_calldata,
+        link_bytecode, predict_key_manager_address, prepare_deployment, read_admin, read_beacon,
+        read_implementation, strip_metadata, upgrade_key_manager, verify_deployed_bytecode,
+        verify_implementation_bytecode, verify_manager,
+    };
+    use crate::{
+        CommitteeMemberSol, CommitteeSol, KeyManager, TimeboostContractError,
+        provider::{
+            ChainIdMismatch, PubSubProvider, PubSubProviderConfig, wait_for_block,
+            wait_for_committee_from_id,
+        },
+    };
+    use alloy::{
+        consensus::{Transaction, TxEnvelope},
+        eips::Decodable2718,
+        network::EthereumWallet,
+        node_bindings::Anvil,
+        primitives::{Address, B256, Bytes, U256},
+        providers::{Provider, ProviderBuilder, WalletProvider},
+        signers::local::PrivateKeySigner,
+        sol_types::{SolCall, SolConstructor, SolValue},
+    };
+    use rand::prelude::*;
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+        time::Duration,
+    };
+    use tracing::{
+        Event, Metadata, Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+
+    /// the fields of a span, and the messages of the events inside it
+    #[derive(Debug, Default)]
+    struct SpanLog {
+        fields: BTreeMap<String, String>,
+        messages: Vec<String>,
+    }
+
+    /// keeps every span with its fields and events, assuming a single thread
+    #[derive(Default)]
+    struct SpanRecorder {
+        next_id: AtomicU64,
+        spans: Arc<Mutex<BTreeMap<u64, SpanLog>>>,
+        entered: Mutex<Vec<u64>>,
+    }
+
+    struct Fields<'a>(&'a mut BTreeMap<String, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_owned(), format!("{value:?}"));
+        }
+    }
+
+    impl Subscriber for SpanRecorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+            let mut log = SpanLog::default();
+            attrs.record(&mut Fields(&mut log.fields));
+            self.spans.lock().unwrap().insert(id, log);
+            Id::from_u64(id)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock().unwrap();
+            values.record(&mut Fields(
+                &mut spans.get_mut(&span.into_u64()).unwrap().fields,
+            ));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = BTreeMap::new();
+            event.record(&mut Fields(&mut fields));
+            let Some(current) = self.entered.lock().unwrap().last().copied() else {
+                return;
+            };
+            let mut spans = self.spans.lock().unwrap();
+            let log = spans.get_mut(&current).unwrap();
+            log.messages.extend(fields.remove("message"));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.entered.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, _: &Id) {
+            self.entered.lock().unwrap().pop();
+        }
+    }
+
+    #[test]
+    fn test_key_manager_init_calldata() {
+        let manager = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+            .parse::<Address>()
+            .unwrap();
+        let calldata = key_manager_init_calldata(manager);
+        // `initialize(address)`, the address left-padded to a word
+        assert_eq!(
+            alloy::hex::encode(&calldata),
+            "c4d66de8\
+             0000000000000000000000005aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+        );
+        let decoded = KeyManager::initializeCall::abi_decode(&calldata).unwrap();
+        assert_eq!(decoded.initialManager, manager);
+    }
+
+    #[test]
+    fn test_link_bytecode() {
+        let placeholder = |name: &str| {
+            let hash = alloy::hex::encode(alloy::primitives::keccak256(name));
+            format!("__${}$__", &hash[..34])
+        };
+        let (a, b) = (placeholder("src/A.sol:A"), placeholder("src/B.sol:B"));
+        let unlinked = format!("0x6073{a}60{b}00{a}");
+
+        let addr_a = Address::with_last_byte(0xaa);
+        let mut libraries = BTreeMap::from([("src/A.sol:A".to_string(), addr_a)]);
+        let err = link_bytecode(&unlinked, &libraries).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("unresolved library placeholders: {b}")
+        );
+
+        let addr_b = Address::with_last_byte(0xbb);
+        libraries.insert("src/B.sol:B".to_string(), addr_b);
+        let linked = link_bytecode(&unlinked, &libraries).unwrap();
+        let expected = [
+            &[0x60, 0x73][..],
+            addr_a.as_slice(),
+            &[0x60],
+            addr_b.as_slice(),
+            &[0x00],
+            addr_a.as_slice(),
+        ]
+        .concat();
+        assert_eq!(linked.to_vec(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_deploy_key_manager_with_committee() {
+        let anvil = alloy::node_bindings::Anvil::new().spawn();
+        let provider = alloy::providers::ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let members = vec![CommitteeMemberSol::random(), CommitteeMemberSol::random()];
+
+        let (addr, created) =
+            deploy_key_manager_with_committee(&provider, manager, 100, members.clone())
+                .await
+                .unwrap();
+        assert_eq!(created.id, 0);
+        let committee = KeyManager::new(addr, &provider)
+            .getCommitteeById(0)
+            .call()
+            .await
+            .unwrap();
+        assert_eq!(committee.members, members);
+
+        // the signer is not the manager, or the committee is invalid
+        let other = Address::with_last_byte(1);
+        assert!(
+            deploy_key_manager_with_committee(&provider, other, 100, members)
+                .await
+                .is_err()
+        );
+        assert!(
+            deploy_key_manager_with_committee(&provider, manager, 100, vec![])
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create2_deployment() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let salt = B256::with_last_byte(1);
+
+        let predicted = predict_key_manager_address(super::CREATE2_FACTORY, salt, manager);
+        let addr = deploy_key_manager_contract_create2(&provider, manager, salt)
+            .await
+            .unwrap();
+        assert_eq!(addr, predicted);
+        let contract = KeyManager::new(addr, &provider);
+        assert_eq!(contract.manager().call().await.unwrap(), manager);
+
+        // same salt, other manager: new proxy over the same implementation
+        let other = Address::with_last_byte(1);
+        let addr_other = deploy_key_manager_contract_create2(&provider, other, salt)
+            .await
+            .unwrap();
+        assert_eq!(
+            addr_other,
+            predict_key_manager_address(super::CREATE2_FACTORY, salt, other)
+        );
+        assert_ne!(addr_other, addr);
+        let err = deploy_key_manager_contract_create2(&provider, manager, salt)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, TimeboostContractError::Create2(Create2Error::AlreadyDeployed(a)) if a == addr)
+        );
+
+        let proxy = ProxyKind::Transparent {
+            bytecode: Bytes::new(),
+            admin: other,
+        };
+        let cfg = DeployConfig::new().with_proxy(proxy);
+        let salt = B256::with_last_byte(2);
+        let err = deploy_key_manager_contract_create2_with_config(&provider, manager, salt, &cfg)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TimeboostContractError::Create2(Create2Error::UnsupportedProxy)
+        ));
+
+        // a new salt with the config path: pinned nonces and the manager checked
+        let nonce = provider.get_transaction_count(manager).await.unwrap();
+        let cfg = DeployConfig::new().with_nonce(nonce).with_confirmations(1);
+        let addr = deploy_key_manager_contract_create2_with_config(&provider, manager, salt, &cfg)
+            .await
+            .unwrap();
+        assert_eq!(
+            addr,
+            predict_key_manager_address(super::CREATE2_FACTORY, salt, manager)
+        );
+        assert_eq!(
+            provider.get_transaction_count(manager).await.unwrap(),
+            nonce + 2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deployment_confirmations() {
+        // blocks have to keep coming for confirmations beyond the first
+        let anvil = Anvil::new().block_time(1).spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let report = deploy_key_manager_contract_with_report(&provider, manager, 3)
+            .await
+            .unwrap();
+        let head = provider.get_block_number().await.unwrap();
+        assert!(head >= report.deployed_block.unwrap() + 2);
+
+        let cfg = DeployConfig::new().with_confirmations(2);
+        let report = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap();
+        let km = KeyManager::new(report.proxy_addr, &provider);
+        assert_eq!(km.manager().call().await.unwrap(), manager);
+    }
+
+    #[tokio::test]
+    async fn test_ensure_key_manager() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+
+        let addr = ensure_key_manager(&provider, None, manager).await.unwrap();
+        let again = ensure_key_manager(&provider, Some(addr), manager)
+            .await
+            .unwrap();
+        assert_eq!(again, addr);
+
+        // a different manager, an account without code, and the uninitialized implementation
+        let other = Address::with_last_byte(7);
+        let implementation = read_implementation(&provider, addr).await.unwrap();
+        for existing in [addr, other, implementation] {
+            let expected = if existing == addr { other } else { manager };
+            let new = ensure_key_manager(&provider, Some(existing), expected)
+                .await
+                .unwrap();
+            assert_ne!(new, existing);
+            let km = KeyManager::new(new, &provider);
+            assert_eq!(km.manager().call().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_is_key_manager() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+
+        let addr = deploy_key_manager_contract(&provider, manager)
+            .await
+            .unwrap();
+        assert!(is_key_manager(&provider, addr).await.unwrap());
+
+        // an unrelated contract, both bare and behind a proxy
+        let library = crate::bindings::address::Address::deploy(&provider)
+            .await
+            .unwrap();
+        let proxy = crate::ERC1967Proxy::deploy(&provider, *library.address(), Bytes::new())
+            .await
+            .unwrap();
+        let implementation = read_implementation(&provider, addr).await.unwrap();
+        for other in [
+            *library.address(),
+            *proxy.address(),
+            implementation,
+            manager,
+            Address::with_last_byte(7),
+        ] {
+            assert!(!is_key_manager(&provider, other).await.unwrap(), "{other}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_gas_price() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let gas_price = provider.get_gas_price().await.unwrap();
+        assert_eq!(
+            check_gas_price(&provider, gas_price).await.unwrap(),
+            gas_price
+        );
+
+        let cfg = DeployConfig::new().with_max_gas_price(gas_price - 1);
+        let err = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TimeboostContractError::GasPriceTooHigh(got) if got == GasPriceTooHigh {
+                gas_price,
+                max_gas_price: gas_price - 1
+            }
+        ));
+        // nothing was sent
+        assert_eq!(provider.get_transaction_count(manager).await.unwrap(), 0);
+
+        let fees = crate::provider::FeeConfig::default().with_max_gas_price(2 * gas_price);
+        let mnemonic = "test test test test test test test test test test test junk".to_string();
+        let capped =
+            crate::provider::build_provider_with_fees(mnemonic, 0, anvil.endpoint_url(), fees)
+                .unwrap();
+        let cfg = DeployConfig::new().with_max_gas_price(2 * gas_price);
+        let report = deploy_key_manager_contract_with_config(&capped, manager, &cfg)
+            .await
+            .unwrap();
+        let contract = KeyManager::new(report.proxy_addr, &provider);
+        assert_eq!(contract.manager().call().await.unwrap(), manager);
+    }
+
+    #[tokio::test]
+    async fn test_deployment_cost_estimate() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+
+        let estimate = deployment_cost_estimate(&provider, manager).await.unwrap();
+        assert_eq!(estimate.gas_price, provider.get_gas_price().await.unwrap());
+        assert_eq!(
+            estimate.wei_cost,
+            u128::from(estimate.total_gas) * estimate.gas_price
+        );
+
+        let cfg = DeployConfig::new().with_max_wei(estimate.wei_cost - 1);
+        let err = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap_err();
+        let TimeboostContractError::CostAboveBudget(over) = err else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(over.max_wei, estimate.wei_cost - 1);
+        assert_eq!(over.estimate.total_gas, estimate.total_gas);
+        // nothing was sent
+        assert_eq!(provider.get_transaction_count(manager).await.unwrap(), 0);
+
+        // the estimate covers what the deployment uses
+        let cfg = DeployConfig::new().with_max_wei(2 * estimate.wei_cost);
+        let report = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap();
+        assert!(report.total_gas_used <= estimate.total_gas);
+        let contract = KeyManager::new(report.proxy_addr, &provider);
+        assert_eq!(contract.manager().call().await.unwrap(), manager);
+    }
+
+    #[tokio::test]
+    async fn test_deployment_timeout() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let cfg = DeployConfig::new().with_receipt_timeout(Duration::from_millis(500));
+        deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap();
+
+        // a chain that stopped mining
+        provider
+            .raw_request::<_, ()>("evm_setAutomine".into(), (false,))
+            .await
+            .unwrap();
+        let err = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap_err();
+        assert!(is_receipt_timeout(&err), "{err}");
+    }
+
+    #[test]
+    fn test_receipt_backoff() {
+        let backoff = ReceiptBackoff::new()
+            .with_initial_interval(Duration::from_millis(100))
+            .with_max_interval(Duration::from_secs(1))
+            .with_multiplier(3.0);
+        assert_eq!(backoff.interval(0), Duration::from_millis(100));
+        assert_eq!(backoff.interval(2), Duration::from_millis(900));
+        assert_eq!(backoff.interval(3), Duration::from_secs(1));
+        assert_eq!(backoff.interval(u32::MAX), Duration::from_secs(1));
+        let constant = backoff.with_multiplier(0.5);
+        assert_eq!(constant.interval(5), Duration::from_millis(100));
+
+        let cfg = DeployConfig::default();
+        assert_eq!(cfg.confirmations, 1);
+        assert!(cfg.receipt_timeout.is_none() && cfg.receipt_backoff.is_none());
+        assert!(cfg.max_gas_price.is_none() && cfg.max_wei.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_deployment_receipt_backoff() {
+        let anvil = Anvil::new().block_time(1).spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let backoff = ReceiptBackoff::new()
+            .with_initial_interval(Duration::from_millis(50))
+            .with_max_interval(Duration::from_millis(400));
+        let cfg = DeployConfig::new()
+            .with_confirmations(2)
+            .with_receipt_backoff(backoff);
+        let report = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap();
+        let head = provider.get_block_number().await.unwrap();
+        assert!(head > report.deployed_block.unwrap());
+        let km = KeyManager::new(report.proxy_addr, &provider);
+        assert_eq!(km.manager().call().await.unwrap(), manager);
+
+        // a chain that stopped mining
+        provider
+            .raw_request::<_, ()>("evm_setAutomine".into(), (false,))
+            .await
+            .unwrap();
+        provider
+            .raw_request::<_, ()>("evm_setIntervalMining".into(), (0,))
+            .await
+            .unwrap();
+        let cfg = cfg.with_receipt_timeout(Duration::from_millis(500));
+        let err = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap_err();
+        assert!(is_receipt_timeout(&err), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_deploy_key_manager_with_init() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+
+        let default = deploy_key_manager_contract(&provider, manager)
+            .await
+            .unwrap();
+        let km = KeyManager::new(default, &provider);
+        assert_eq!(km.manager().call().await.unwrap(), manager);
+
+        let other = Address::with_last_byte(7);
+        let custom = deploy_key_manager_with_init(&provider, key_manager_init_calldata(other))
+            .await
+            .unwrap();
+        let km = KeyManager::new(custom, &provider);
+        assert_eq!(km.manager().call().await.unwrap(), other);
+        assert_eq!(km.owner().call().await.unwrap(), manager);
+
+        // without init calldata anyone may initialize later
+        let uninit = deploy_key_manager_with_init(&provider, Bytes::new())
+            .await
+            .unwrap();
+        let km = KeyManager::new(uninit, &provider);
+        assert_eq!(km.manager().call().await.unwrap(), Address::ZERO);
+        km.initialize(other)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        assert_eq!(km.manager().call().await.unwrap(), other);
+    }
 
-    let receipt = pending_tx.get_receipt().await?;
-    tracing::info!(%receipt.gas_used, %tx_hash, "tx mined");
-    let addr = receipt
-        .contract_address
-        .ok_or(alloy::contract::Error::ContractNotDeployed)?;
+    #[tokio::test]
+    async fn test_deploy_key_managers() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let managers: Vec<_> = (1..=4).map(Address::with_last_byte).collect();
+        let reports = deploy_key_managers(&provider, &managers).await.unwrap();
+        assert_eq!(reports.len(), managers.len());
 
-    tracing::info!("deployed {name} at {addr:#x}");
-    Ok(addr)
-}
+        for (report, manager) in reports.iter().zip(&managers) {
+            let km = KeyManager::new(report.proxy_addr, &provider);
+            assert_eq!(km.manager().call().await.unwrap(), *manager);
+        }
+        let proxies: std::collections::HashSet<_> = reports.iter().map(|r| r.proxy_addr).collect();
+        assert_eq!(proxies.len(), managers.len());
+        assert!(
+            deploy_key_managers(&provider, &[])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
 
-/// Given a chain provider/connector, deploy a new KeyManager contract
-pub async fn deploy_key_manager_contract<P>(
-    provider: &P,
-    manager: Address,
-) -> ContractResult<Address>
-where
-    P: Provider,
-{
-    // first deploy the implementation contract
-    let tx = KeyManager::deploy_builder(&provider);
-    let impl_addr = deploy("KeyManager", tx).await?;
-    let km = KeyManager::new(impl_addr, provider);
+    #[tokio::test]
+    async fn test_deploy_key_manager_implementation_only() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let addr = deploy_key_manager_implementation_only(&provider)
+            .await
+            .unwrap();
+        // no proxy in front
+        assert_eq!(
+            read_implementation(&provider, addr).await.unwrap(),
+            Address::ZERO
+        );
+        assert!(!provider.get_code_at(addr).await.unwrap().is_empty());
 
-    // then deploy the proxy, point to the implementation contract and initialize it
-    let init_data = km.initialize(manager).calldata().to_owned();
-    let tx = ERC1967Proxy::deploy_builder(&provider, impl_addr, init_data);
-    let proxy_addr = deploy("KeyManagerProxy", tx).await?;
-    tracing::info!("deployed KeyManagerProxy at {proxy_addr:#x}");
-    Ok(proxy_addr)
-}
+        // the constructor disables the initializer, so it stays uninitialized
+        let km = KeyManager::new(addr, &provider);
+        assert!(km.initialize(manager).call().await.is_err());
+        assert_eq!(km.manager().call().await.unwrap(), Address::ZERO);
+        assert_eq!(km.nextCommitteeId().call().await.unwrap(), 0);
+        assert!(
+            km.setNextCommittee(100, vec![CommitteeMemberSol::random()])
+                .call()
+                .await
+                .is_err()
+        );
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::deploy_key_manager_contract;
-    use crate::{CommitteeMemberSol, CommitteeSol, KeyManager, KeyManager::CommitteeCreated};
-    use alloy::{
-        eips::BlockNumberOrTag,
-        node_bindings::Anvil,
-        primitives::U256,
-        providers::{Provider, ProviderBuilder, WalletProvider},
-        rpc::types::Filter,
-        sol_types::{SolEvent, SolValue},
-        transports::ws::WsConnect,
+    #[tokio::test]
+    async fn test_deployment_span() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let recorder = SpanRecorder::default();
+        let spans = recorder.spans.clone();
+        let _guard = tracing::subscriber::set_default(recorder);
+
+        // concurrent deployments, whose logs interleave
+        let managers: Vec<_> = (1..=2).map(Address::with_last_byte).collect();
+        let reports = deploy_key_managers(&provider, &managers).await.unwrap();
+        assert_ne!(reports[0].deployment_id, reports[1].deployment_id);
+
+        let spans = spans.lock().unwrap();
+        for report in &reports {
+            let mut matching = spans
+                .values()
+                .filter(|s| s.fields["deployment_id"] == report.deployment_id);
+            let span = matching.next().unwrap();
+            assert!(matching.next().is_none());
+            assert_eq!(
+                span.fields["implementation"],
+                report.implementation_addr.to_string()
+            );
+            assert_eq!(span.fields["proxy"], report.proxy_addr.to_string());
+            assert_eq!(span.fields["gas_used"], report.total_gas_used.to_string());
+            for name in ["KeyManager", "KeyManagerProxy"] {
+                assert!(span.messages.contains(&format!("deploying {name}")));
+            }
+        }
+    }
+
+    #[test]
+    fn test_post_deploy_check_failed() {
+        let failed = PostDeployCheckFailed {
+            proxy: Address::with_last_byte(1),
+            expected_manager: Address::with_last_byte(2),
+            actual_manager: Address::ZERO,
+        };
+        let err = TimeboostContractError::from(anyhow::Error::from(failed));
+        assert!(matches!(err, TimeboostContractError::PostDeployCheck(got) if got == failed));
+        assert_eq!(
+            failed.to_string(),
+            "KeyManager proxy 0x0000000000000000000000000000000000000001 reports manager \
+             0x0000000000000000000000000000000000000000 after deployment, expected \
+             0x0000000000000000000000000000000000000002"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_deploy_check() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let cfg = DeployConfig::new();
+        assert!(cfg.verify_manager);
+        let report = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap();
+        verify_manager(&provider, report.proxy_addr, manager)
+            .await
+            .unwrap();
+
+        // a proxy whose initializer never ran
+        let proxy = deploy_key_manager_with_init(&provider, Bytes::new())
+            .await
+            .unwrap();
+        let err = verify_manager(&provider, proxy, manager).await.unwrap_err();
+        assert!(matches!(
+            err,
+            TimeboostContractError::PostDeployCheck(failed) if failed == PostDeployCheckFailed {
+                proxy,
+                expected_manager: manager,
+                actual_manager: Address::ZERO,
+            }
+        ));
+        assert!(err.to_string().contains("expected"), "{err}");
+        assert!(!is_receipt_timeout(&err));
+    }
+
+    const OFFLINE_PARAMS: OfflineTxParams = OfflineTxParams {
+        chain_id: 31337,
+        max_fee_per_gas: 10_000_000_000,
+        max_priority_fee_per_gas: 1_000_000_000,
+        implementation_gas: 5_000_000,
+        proxy_gas: 1_000_000,
     };
-    use futures::StreamExt;
-    use rand::prelude::*;
+
+    #[tokio::test]
+    async fn test_prepare_deployment() {
+        let signer = PrivateKeySigner::from_bytes(&B256::repeat_byte(1)).unwrap();
+        let deployer = signer.address();
+        let wallet = EthereumWallet::from(signer);
+        let manager = Address::with_last_byte(7);
+        let prepared = prepare_deployment(&wallet, manager, 5, &OFFLINE_PARAMS)
+            .await
+            .unwrap();
+        assert_eq!(prepared.deployer, deployer);
+        assert_eq!(prepared.implementation_addr, deployer.create(5));
+        assert_eq!(prepared.proxy_addr, deployer.create(6));
+
+        let decode = |raw: &Bytes| TxEnvelope::decode_2718(&mut raw.as_ref()).unwrap();
+        let implementation = decode(&prepared.implementation_tx);
+        let proxy = decode(&prepared.proxy_tx);
+        for (tx, nonce, gas) in [(&implementation, 5, 5_000_000), (&proxy, 6, 1_000_000)] {
+            assert_eq!(tx.chain_id(), Some(31337));
+            assert_eq!(tx.nonce(), nonce);
+            assert_eq!(tx.gas_limit(), gas);
+            assert_eq!(tx.max_fee_per_gas(), 10_000_000_000);
+            assert!(tx.kind().is_create());
+        }
+        assert_eq!(implementation.input(), &KeyManager::BYTECODE);
+        // the proxy points at the implementation and initializes the manager
+        let args = crate::ERC1967Proxy::constructorCall {
+            _logic: prepared.implementation_addr,
+            _data: key_manager_init_calldata(manager),
+        }
+        .abi_encode();
+        assert_eq!(
+            proxy.input(),
+            &[crate::ERC1967Proxy::BYTECODE.as_ref(), &args].concat()
+        );
+
+        let json = serde_json::to_string(&prepared).unwrap();
+        assert_eq!(
+            serde_json::from_str::<PreparedDeployment>(&json).unwrap(),
+            prepared
+        );
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_prepared_deployment() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let signer = PrivateKeySigner::from(anvil.keys()[1].clone());
+        let deployer = signer.address();
+        let wallet = EthereumWallet::from(signer);
+
+        let prepared = prepare_deployment(&wallet, manager, 0, &OFFLINE_PARAMS)
+            .await
+            .unwrap();
+        let report = broadcast_prepared_deployment(&provider, &prepared, &DeployConfig::new())
+            .await
+            .unwrap();
+        assert_eq!(report.implementation_addr, prepared.implementation_addr);
+        assert_eq!(report.proxy_addr, prepared.proxy_addr);
+        assert_eq!(report.manager, Some(manager));
+        let km = KeyManager::new(report.proxy_addr, &provider);
+        assert_eq!(km.manager().call().await.unwrap(), manager);
+
+        // sent again, the nonce is stale
+        let err = broadcast_prepared_deployment(&provider, &prepared, &DeployConfig::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TimeboostContractError::NonceMismatch(got) if got == NonceMismatch {
+                deployer,
+                expected: 0,
+                actual: 2,
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            format!("next nonce of {deployer} is 2, the deployment was prepared for 0")
+        );
+        let params = OfflineTxParams {
+            chain_id: 1,
+            ..OFFLINE_PARAMS
+        };
+        let other_chain = prepare_deployment(&wallet, manager, 2, &params)
+            .await
+            .unwrap();
+        let err = broadcast_prepared_deployment(&provider, &other_chain, &DeployConfig::new())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            TimeboostContractError::ChainIdMismatch(ChainIdMismatch {
+                expected: 1,
+                actual: 31337
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_deploy_contract() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let cfg = DeployConfig::new();
+        let implementation =
+            deploy_contract("KeyManager", KeyManager::deploy_builder(&provider), &cfg)
+                .await
+                .unwrap();
+        let tx = crate::ERC1967Proxy::deploy_builder(
+            &provider,
+            implementation.addr,
+            key_manager_init_calldata(manager),
+        );
+        let proxy = deploy_contract("ERC1967Proxy", tx, &cfg).await.unwrap();
+        let receipt = provider
+            .get_transaction_receipt(proxy.tx_hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            proxy,
+            Deployed {
+                addr: receipt.contract_address.unwrap(),
+                tx_hash: receipt.transaction_hash,
+                gas_used: receipt.gas_used,
+                block_number: receipt.block_number,
+            }
+        );
+        let km = KeyManager::new(proxy.addr, &provider);
+        assert_eq!(km.manager().call().await.unwrap(), manager);
+        assert_eq!(
+            read_implementation(&provider, proxy.addr).await.unwrap(),
+            implementation.addr
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deploy_with_nonce() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        deploy_key_manager_contract(&provider, manager)
+            .await
+            .unwrap();
+
+        let cfg = DeployConfig::new().with_nonce(2);
+        let report = deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+            .await
+            .unwrap();
+        assert_eq!(report.implementation_addr, manager.create(2));
+        assert_eq!(report.proxy_addr, manager.create(3));
+        // already used
+        let cfg = DeployConfig::new().with_nonce(0);
+        assert!(
+            deploy_key_manager_contract_with_config(&provider, manager, &cfg)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deployment_report() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let report =
+            deploy_key_manager_contract_with_config(&provider, manager, &DeployConfig::new())
+                .await
+                .unwrap();
+
+        // implementation and proxy each take a block
+        assert_eq!(report.deployed_block, Some(2));
+        let receipt = |hash| {
+            let provider = &provider;
+            async move {
+                provider
+                    .get_transaction_receipt(hash)
+                    .await
+                    .unwrap()
+                    .unwrap()
+            }
+        };
+        let impl_receipt = receipt(report.impl_tx_hash).await;
+        let proxy_receipt = receipt(report.proxy_tx_hash).await;
+        assert_eq!(
+            impl_receipt.contract_address,
+            Some(report.implementation_addr)
+        );
+        assert_eq!(proxy_receipt.contract_address, Some(report.proxy_addr));
+        assert_eq!(
+            report.total_gas_used,
+            impl_receipt.gas_used + proxy_receipt.gas_used
+        );
+        assert_eq!(report.chain_id, anvil.chain_id());
+        assert_eq!(report.manager, Some(manager));
+        assert_eq!(
+            DeploymentReport::from_json(&report.to_json()).unwrap(),
+            report
+        );
+    }
+
+    #[test]
+    fn test_proxy_init_code() {
+        let (target, admin) = (Address::with_last_byte(1), Address::with_last_byte(2));
+        let init = key_manager_init_calldata(Address::with_last_byte(3));
+        assert_eq!(DeployConfig::new().proxy, ProxyKind::Erc1967);
+        let args = crate::ERC1967Proxy::constructorCall {
+            _logic: target,
+            _data: init.clone(),
+        };
+        assert_eq!(
+            ProxyKind::Erc1967.init_code(target, init.clone()),
+            Bytes::from([crate::ERC1967Proxy::BYTECODE.as_ref(), &args.abi_encode()].concat())
+        );
+
+        // constructor args are appended to the given creation code
+        let code = Bytes::from_static(&[0x60, 0x80]);
+        let transparent = ProxyKind::Transparent {
+            bytecode: code.clone(),
+            admin,
+        };
+        let init_code = transparent.init_code(target, init.clone());
+        assert_eq!(&init_code[..2], &code[..]);
+        let decoded = <(Address, Address, Bytes)>::abi_decode_params(&init_code[2..]).unwrap();
+        assert_eq!(decoded, (target, admin, init.clone()));
+
+        let beacon = ProxyKind::Beacon {
+            proxy_bytecode: code.clone(),
+            beacon_bytecode: Bytes::new(),
+        };
+        let init_code = beacon.init_code(target, init.clone());
+        let decoded = <(Address, Bytes)>::abi_decode_params(&init_code[2..]).unwrap();
+        assert_eq!(decoded, (target, init));
+    }
+
+    #[tokio::test]
+    async fn test_read_beacon() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        assert_eq!(read_beacon(&provider, addr).await.unwrap(), Address::ZERO);
+        let report = deploy_key_manager_contract_with_config(
+            &provider,
+            provider.default_signer_address(),
+            &DeployConfig::new().with_proxy(ProxyKind::Erc1967),
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.beacon_addr, None);
+        assert!(is_key_manager(&provider, report.proxy_addr).await.unwrap());
+    }
+
+    #[test]
+    fn test_deployment_artifact() {
+        let report = DeploymentReport {
+            chain_id: 31337,
+            manager: Some(Address::with_last_byte(1)),
+            implementation_addr: Address::with_last_byte(2),
+            proxy_addr: Address::with_last_byte(3),
+            beacon_addr: None,
+            impl_tx_hash: B256::with_last_byte(4),
+            proxy_tx_hash: B256::with_last_byte(5),
+            total_gas_used: 3_000_000,
+            deployed_block: Some(2),
+            deployment_id: "00000000000000aa".into(),
+        };
+        let json = report.to_json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["chain_id"], 31337);
+        assert_eq!(
+            value["proxy_addr"],
+            "0x0000000000000000000000000000000000000003"
+        );
+        assert_eq!(DeploymentReport::from_json(&json).unwrap(), report);
+        // artifacts from before beacon proxies
+        let mut old = value.clone();
+        old.as_object_mut().unwrap().remove("beacon_addr");
+        assert_eq!(
+            DeploymentReport::from_json(&old.to_string()).unwrap(),
+            report
+        );
+
+        let path = std::env::temp_dir().join(format!("deployment-{}.json", std::process::id()));
+        report.write_to_file(&path).unwrap();
+        assert_eq!(DeploymentReport::read_from_file(&path).unwrap(), report);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            DeploymentReport::read_from_file(&path),
+            Err(DeploymentArtifactError::Io(_))
+        ));
+
+        let mut future = value.clone();
+        future["version"] = 2.into();
+        let err = DeploymentReport::from_json(&future.to_string()).unwrap_err();
+        assert!(matches!(err, DeploymentArtifactError::UnknownVersion(2)));
+        let mut incomplete = value;
+        incomplete.as_object_mut().unwrap().remove("proxy_addr");
+        assert!(matches!(
+            DeploymentReport::from_json(&incomplete.to_string()),
+            Err(DeploymentArtifactError::Json(_))
+        ));
+    }
 
     #[tokio::test]
     async fn test_key_manager_deployment() {
@@ -87,6 +2685,12 @@ mod tests {
             .await
             .unwrap();
 
+        // deploy takes first 2 blocks: deploying implementation contract and proxy contract
+        // setNextCommittee is the 3rd tx, thus in 3rd block
+        wait_for_block(contract.provider(), 3, Duration::from_secs(5))
+            .await
+            .unwrap();
+
         // make sure next committee is correctly registered
         assert_eq!(
             contract
@@ -95,8 +2699,6 @@ mod tests {
                 .await
                 .unwrap()
                 .abi_encode_sequence(),
-            // deploy takes first 2 blocks: deploying implementation contract and proxy contract
-            // setNextCommittee is the 3rd tx, thus in 3rd block
             CommitteeSol {
                 id: 0,
                 registeredBlockNumber: U256::from(3),
@@ -107,6 +2709,193 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_read_proxy_slots() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let deployer = provider.default_signer_address();
+        let proxy_addr = deploy_key_manager_contract(&provider, deployer)
+            .await
+            .unwrap();
+
+        // the implementation is the deployer's first contract
+        let implementation = deployer.create(0);
+        assert_eq!(
+            read_implementation(&provider, proxy_addr).await.unwrap(),
+            implementation
+        );
+        assert_eq!(
+            read_admin(&provider, proxy_addr).await.unwrap(),
+            Address::ZERO
+        );
+        // the implementation itself isn't a proxy
+        assert_eq!(
+            read_implementation(&provider, implementation)
+                .await
+                .unwrap(),
+            Address::ZERO
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_key_manager() {
+        let (provider, proxy_addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(proxy_addr, &provider);
+        let members = vec![CommitteeMemberSol::random()];
+        contract
+            .setNextCommittee(100, members.clone())
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let before = read_implementation(&provider, proxy_addr).await.unwrap();
+
+        let after = upgrade_key_manager(&provider, proxy_addr, KeyManager::BYTECODE.clone(), None)
+            .await
+            .unwrap();
+        assert_ne!(after, before);
+        assert_eq!(
+            read_implementation(&provider, proxy_addr).await.unwrap(),
+            after
+        );
+
+        // storage is preserved
+        let committee = contract.getCommitteeById(0).call().await.unwrap();
+        assert_eq!(committee.members, members);
+        assert_eq!(
+            contract.manager().call().await.unwrap(),
+            provider.default_signer_address()
+        );
+
+        // with a migration call, here rotating the manager
+        let new_manager = Address::with_last_byte(1);
+        let migration = contract.setManager(new_manager).calldata().clone();
+        upgrade_key_manager(
+            &provider,
+            proxy_addr,
+            KeyManager::BYTECODE.clone(),
+            Some(migration),
+        )
+        .await
+        .unwrap();
+        assert_eq!(contract.manager().call().await.unwrap(), new_manager);
+    }
+
+    #[tokio::test]
+    async fn test_verify_deployed_bytecode() {
+        let (provider, proxy_addr) = crate::init_test_chain().await.unwrap();
+        let impl_addr = read_implementation(&provider, proxy_addr).await.unwrap();
+
+        assert!(
+            verify_deployed_bytecode(&provider, impl_addr)
+                .await
+                .unwrap()
+        );
+        // the proxy runs different code
+        assert!(
+            !verify_deployed_bytecode(&provider, proxy_addr)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_strip_metadata() {
+        let code = [0x60, 0x80, 0xa2, 0x64, 0x00, 0x03];
+        assert_eq!(strip_metadata(&code), [0x60]);
+        // a length beyond the code isn't metadata
+        assert_eq!(strip_metadata(&[0x60, 0x00, 0x09]), [0x60, 0x00, 0x09]);
+        assert_eq!(strip_metadata(&[0x01]), [0x01]);
+        let artifact = &KeyManager::DEPLOYED_BYTECODE[..];
+        assert!(strip_metadata(artifact).len() < artifact.len());
+    }
+
+    #[tokio::test]
+    async fn test_verify_implementation_bytecode() {
+        let (provider, proxy_addr) = crate::init_test_chain().await.unwrap();
+        let impl_addr = read_implementation(&provider, proxy_addr).await.unwrap();
+        let expected = KeyManager::DEPLOYED_BYTECODE.to_vec();
+        let verify = async |addr, expected: &[u8], mask| {
+            let err = verify_implementation_bytecode(&provider, addr, expected, mask).await;
+            err.map_err(|err| match err {
+                TimeboostContractError::BytecodeMismatch(err) => err,
+                err => panic!("unexpected error {err}"),
+            })
+        };
+
+        // the immutable `__self` is zeroed in the artifact
+        let no_mask = BytecodeMask::new();
+        let mismatch = verify(impl_addr, &expected, &no_mask).await.unwrap_err();
+        assert_eq!(mismatch.expected_len, mismatch.actual_len);
+        assert!(mismatch.differing_bytes > 0);
+
+        let code = provider.get_code_at(impl_addr).await.unwrap();
+        let self_word = B256::left_padding_from(impl_addr.as_slice());
+        let mask = (0..code.len() - 31)
+            .filter(|&i| code[i..i + 32] == self_word[..])
+            .fold(BytecodeMask::new().with_metadata(), |mask, i| {
+                mask.with_range(i..i + 32)
+            });
+        verify(impl_addr, &expected, &mask).await.unwrap();
+
+        let mut tampered = expected.clone();
+        tampered[10] ^= 0xff;
+        let mismatch = verify(impl_addr, &tampered, &mask).await.unwrap_err();
+        assert_eq!(mismatch.first_difference, Some(10));
+        assert_eq!(mismatch.differing_bytes, 1);
+
+        // no code at all
+        let mismatch = verify(Address::with_last_byte(9), &expected, &mask)
+            .await
+            .unwrap_err();
+        assert_eq!((mismatch.actual_len, mismatch.first_difference), (0, None));
+    }
+
+    #[cfg(feature = "ens")]
+    #[tokio::test]
+    async fn test_resolve_manager() {
+        use super::resolve_manager;
+        use alloy::ens::NameOrAddress;
+
+        let (provider, _) = crate::init_test_chain().await.unwrap();
+        let manager = provider.default_signer_address();
+        assert_eq!(
+            resolve_manager(&provider, &manager.into()).await.unwrap(),
+            manager
+        );
+
+        // a pristine anvil chain has no ENS registry
+        let name: NameOrAddress = "team.eth".parse().unwrap();
+        let err = resolve_manager(&provider, &name).await.unwrap_err();
+        assert!(err.to_string().contains("no ENS registry"));
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_deployment_on_fork() {
+        // fork a local chain, so the test doesn't depend on a remote RPC
+        let origin = Anvil::new().spawn();
+        let manager = origin.addresses()[1];
+        let report = super::dry_run_deployment_on_fork(origin.endpoint_url(), manager)
+            .await
+            .unwrap();
+        assert!(report.total_gas_used > 0);
+
+        // the origin chain is untouched
+        let provider = ProviderBuilder::new().connect_http(origin.endpoint_url());
+        assert!(
+            provider
+                .get_code_at(report.proxy_addr)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(provider.get_block_number().await.unwrap(), 0);
+    }
+
     #[tokio::test]
     async fn test_event_stream() {
         let anvil = Anvil::new().spawn();
@@ -114,10 +2903,10 @@ mod tests {
         let provider = ProviderBuilder::new()
             .wallet(wallet)
             .connect_http(anvil.endpoint_url());
-        let pubsub_provider = ProviderBuilder::new()
-            .connect_pubsub_with(WsConnect::new(anvil.ws_endpoint_url()))
-            .await
-            .unwrap();
+        let pubsub_provider =
+            PubSubProvider::new(PubSubProviderConfig::new(anvil.ws_endpoint_url()))
+                .await
+                .unwrap();
         assert_eq!(
             pubsub_provider.get_chain_id().await.unwrap(),
             provider.get_chain_id().await.unwrap()
@@ -129,17 +2918,6 @@ mod tests {
             .unwrap();
         let contract = KeyManager::new(km_addr, &provider);
 
-        // setup event stream
-        let filter = Filter::new()
-            .address(km_addr)
-            .event(KeyManager::CommitteeCreated::SIGNATURE)
-            .from_block(BlockNumberOrTag::Latest);
-        let mut events = pubsub_provider
-            .subscribe_logs(&filter)
-            .await
-            .unwrap()
-            .into_stream();
-
         // register some committees on the contract, which emit events
         let rng = &mut rand::rng();
         let c0_timestamp = rng.random::<u64>();
@@ -159,9 +2937,11 @@ mod tests {
                 .unwrap();
 
             // Read the corresponding event
-            let log = events.next().await.unwrap();
-            let typed_log = log.log_decode_validate::<CommitteeCreated>().unwrap();
-            assert_eq!(typed_log.data().id, i);
+            let created =
+                wait_for_committee_from_id(&pubsub_provider, km_addr, i, Duration::from_secs(10))
+                    .await
+                    .unwrap();
+            assert_eq!(created.id, i);
         }
     }
 }
@@ -0,0 +1,535 @@
+//! Typed errors
+//!
+//! [`KeyManagerError`] names the failure behind a revert of any KeyManager call: the contract's
+//! own custom errors, the reason strings of the OpenZeppelin base contracts (e.g. "Ownable:
+//! caller is not the owner") and, as a last resort, the raw revert data.
+//!
+//! [`TimeboostContractError`] is the error of the public API of [`provider`](crate::provider),
+//! [`deployer`](crate::deployer), [`reader`](crate::reader) and the test chain, telling apart why
+//! a call failed without downcasting.
+
+use std::fmt;
+
+use alloy::{
+    contract::Error as ContractError,
+    primitives::{Address, Bytes, U256},
+    providers::{MulticallError, PendingTransactionError},
+    signers::local::LocalSignerError,
+    sol_types::{SolInterface, decode_revert_reason},
+    transports::TransportError,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use alloy::node_bindings::NodeError;
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::provider::KeystoreError;
+use crate::{
+    CommitteeError,
+    KeyManager::KeyManagerErrors,
+    deployer::{
+        BytecodeMismatch, CostAboveBudget, Create2Error, GasPriceTooHigh, NonceMismatch,
+        PostDeployCheckFailed,
+    },
+    provider::{
+        BlockWaitTimeout, ChainIdMismatch, CommitteeEventTimeout, ConnectCancelled, ConnectTimeout,
+        Disconnected, ProbeTimeout,
+    },
+    reader::{CommitteeWaitTimeout, HistoricalStateUnavailable, StateOverrideUnsupported},
+};
+
+/// `Result` of the public API, see [`TimeboostContractError`]
+pub type Result<T, E = TimeboostContractError> = std::result::Result<T, E>;
+
+/// Why a KeyManager call reverted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyManagerError {
+    /// the caller isn't the manager
+    Unauthorized {
+        caller: Address,
+    },
+    /// the zero address was given as manager
+    InvalidAddress,
+    ThresholdEncryptionKeyAlreadySet,
+    CommitteeNotFound {
+        id: u64,
+    },
+    EmptyCommittee,
+    /// the effective timestamp doesn't increase over the latest committee's
+    InvalidTimestamp {
+        timestamp: u64,
+        last: u64,
+    },
+    NoCommitteeScheduled,
+    CommitteeIdOverflow,
+    /// pruning would remove a committee effective within the last 10 minutes
+    CannotPruneRecent,
+    InvalidPruneRange {
+        up_to: u64,
+        oldest_stored: u64,
+        next_id: u64,
+    },
+    EmptyDataHash,
+    EmptySignatures,
+    SignatureCountMismatch {
+        committee: U256,
+        signatures: U256,
+    },
+    /// a revert with a reason string or panic code, e.g. from an OpenZeppelin base contract
+    Revert(String),
+    /// revert data matching no known error
+    Raw(Bytes),
+}
+
+impl KeyManagerError {
+    /// Decode the data of a revert
+    pub fn decode(data: &[u8]) -> Self {
+        if let Ok(err) = KeyManagerErrors::abi_decode(data) {
+            return err.into();
+        }
+        match decode_revert_reason(data).filter(|_| !data.is_empty()) {
+            Some(reason) => Self::Revert(reason),
+            None => Self::Raw(Bytes::copy_from_slice(data)),
+        }
+    }
+
+    /// The revert behind `err`, `None` if it isn't one, e.g. a transport error
+    pub fn from_contract_error(err: &ContractError) -> Option<Self> {
+        err.as_revert_data().map(|data| Self::decode(&data))
+    }
+}
+
+impl From<KeyManagerErrors> for KeyManagerError {
+    fn from(err: KeyManagerErrors) -> Self {
+        use KeyManagerErrors as E;
+        match err {
+            E::NotManager(e) => Self::Unauthorized { caller: e.caller },
+            E::InvalidAddress(_) => Self::InvalidAddress,
+            E::ThresholdEncryptionKeyAlreadySet(_) => Self::ThresholdEncryptionKeyAlreadySet,
+            E::CommitteeIdDoesNotExist(e) => Self::CommitteeNotFound { id: e.committeeId },
+            E::EmptyCommitteeMembers(_) => Self::EmptyCommittee,
+            E::InvalidEffectiveTimestamp(e) => Self::InvalidTimestamp {
+                timestamp: e.effectiveTimestamp,
+                last: e.lastEffectiveTimestamp,
+            },
+            E::NoCommitteeScheduled(_) => Self::NoCommitteeScheduled,
+            E::CommitteeIdOverflow(_) => Self::CommitteeIdOverflow,
+            E::CannotRemoveRecentCommittees(_) => Self::CannotPruneRecent,
+            E::InvalidPruneRange(e) => Self::InvalidPruneRange {
+                up_to: e.upToCommitteeId,
+                oldest_stored: e.oldestStored,
+                next_id: e.nextCommitteeId,
+            },
+            E::EmptyDataHash(_) => Self::EmptyDataHash,
+            E::EmptySignatures(_) => Self::EmptySignatures,
+            E::CommitteeAndSignatureLengthMismatch(e) => Self::SignatureCountMismatch {
+                committee: e.committeeLength,
+                signatures: e.signatureLength,
+            },
+        }
+    }
+}
+
+impl fmt::Display for KeyManagerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unauthorized { caller } => write!(f, "caller {caller} is not the manager"),
+            Self::InvalidAddress => write!(f, "invalid zero address"),
+            Self::ThresholdEncryptionKeyAlreadySet => {
+                write!(f, "threshold encryption key is already set")
+            }
+            Self::CommitteeNotFound { id } => write!(f, "committee {id} does not exist"),
+            Self::EmptyCommittee => write!(f, "committee has no members"),
+            Self::InvalidTimestamp { timestamp, last } => write!(
+                f,
+                "effective timestamp {timestamp} is not after the latest committee's {last}"
+            ),
+            Self::NoCommitteeScheduled => write!(f, "no committee is scheduled"),
+            Self::CommitteeIdOverflow => write!(f, "committee id overflow"),
+            Self::CannotPruneRecent => write!(f, "cannot prune recently effective committees"),
+            Self::InvalidPruneRange {
+                up_to,
+                oldest_stored,
+                next_id,
+            } => write!(
+                f,
+                "cannot prune up to committee {up_to}, stored are {oldest_stored}..{next_id}"
+            ),
+            Self::EmptyDataHash => write!(f, "empty data hash"),
+            Self::EmptySignatures => write!(f, "no signatures"),
+            Self::SignatureCountMismatch {
+                committee,
+                signatures,
+            } => write!(
+                f,
+                "{signatures} signatures for a committee of {committee} members"
+            ),
+            Self::Revert(reason) => write!(f, "reverted: {reason}"),
+            Self::Raw(data) => write!(f, "reverted with unknown data {data}"),
+        }
+    }
+}
+
+impl std::error::Error for KeyManagerError {}
+
+/// Why a call of the public API failed, the underlying error being the source
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TimeboostContractError {
+    /// deploying a contract failed, or it wasn't mined in time (see
+    /// [`is_receipt_timeout`](crate::deployer::is_receipt_timeout))
+    Deployment(ContractError),
+    /// a contract call or read failed, e.g. it reverted
+    Contract(ContractError),
+    /// an RPC request failed on the transport or with an error response
+    Transport(TransportError),
+    /// no signer could be built from the mnemonic or private key
+    Signer(LocalSignerError),
+    #[cfg(not(target_arch = "wasm32"))]
+    Keystore(KeystoreError),
+    /// ABI data didn't decode
+    Decode(alloy::sol_types::Error),
+    ChainIdMismatch(ChainIdMismatch),
+    ConnectTimeout(ConnectTimeout),
+    ConnectCancelled(ConnectCancelled),
+    /// the pubsub provider was disconnected
+    Disconnected(Disconnected),
+    ProbeTimeout(ProbeTimeout),
+    BlockWaitTimeout(BlockWaitTimeout),
+    CommitteeEventTimeout(CommitteeEventTimeout),
+    GasPriceTooHigh(GasPriceTooHigh),
+    CostAboveBudget(CostAboveBudget),
+    BytecodeMismatch(BytecodeMismatch),
+    /// a deployed proxy doesn't report the manager it was initialized with
+    PostDeployCheck(PostDeployCheckFailed),
+    NonceMismatch(NonceMismatch),
+    Create2(Create2Error),
+    /// a KeyManager call reverted where there's no [`ContractError`] to decode, e.g. within a
+    /// Multicall3 batch
+    KeyManager(KeyManagerError),
+    /// a committee read from the contract doesn't convert into a [`Committee`](crate::Committee)
+    Committee(CommitteeError),
+    HistoricalStateUnavailable(HistoricalStateUnavailable),
+    StateOverrideUnsupported(StateOverrideUnsupported),
+    CommitteeWaitTimeout(CommitteeWaitTimeout),
+    /// the local Anvil chain couldn't be spawned
+    #[cfg(not(target_arch = "wasm32"))]
+    TestChain(NodeError),
+    /// invalid configuration, e.g. a missing url or a header that isn't valid HTTP
+    Config(anyhow::Error),
+    /// anything else
+    Other(anyhow::Error),
+}
+
+impl fmt::Display for TimeboostContractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Deployment(err) => write!(f, "deployment failed: {err}"),
+            Self::Contract(err) => write!(f, "contract call failed: {err}"),
+            Self::Transport(err) => write!(f, "rpc request failed: {err}"),
+            Self::Signer(err) => write!(f, "invalid signer: {err}"),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Keystore(err) => err.fmt(f),
+            Self::Decode(err) => write!(f, "failed to decode: {err}"),
+            Self::ChainIdMismatch(err) => err.fmt(f),
+            Self::ConnectTimeout(err) => err.fmt(f),
+            Self::ConnectCancelled(err) => err.fmt(f),
+            Self::Disconnected(err) => err.fmt(f),
+            Self::ProbeTimeout(err) => err.fmt(f),
+            Self::BlockWaitTimeout(err) => err.fmt(f),
+            Self::CommitteeEventTimeout(err) => err.fmt(f),
+            Self::GasPriceTooHigh(err) => err.fmt(f),
+            Self::CostAboveBudget(err) => err.fmt(f),
+            Self::BytecodeMismatch(err) => err.fmt(f),
+            Self::PostDeployCheck(err) => err.fmt(f),
+            Self::NonceMismatch(err) => err.fmt(f),
+            Self::Create2(err) => err.fmt(f),
+            Self::KeyManager(err) => write!(f, "KeyManager call reverted: {err}"),
+            Self::Committee(err) => write!(f, "invalid committee: {err}"),
+            Self::HistoricalStateUnavailable(err) => err.fmt(f),
+            Self::StateOverrideUnsupported(err) => err.fmt(f),
+            Self::CommitteeWaitTimeout(err) => err.fmt(f),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::TestChain(err) => write!(f, "failed to spawn the test chain: {err}"),
+            Self::Config(err) | Self::Other(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for TimeboostContractError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Deployment(err) | Self::Contract(err) => Some(err),
+            Self::Transport(err) => Some(err),
+            Self::Signer(err) => Some(err),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::Keystore(err) => Some(err),
+            Self::Decode(err) => Some(err),
+            Self::ChainIdMismatch(err) => Some(err),
+            Self::ConnectTimeout(err) => Some(err),
+            Self::ConnectCancelled(err) => Some(err),
+            Self::Disconnected(err) => Some(err),
+            Self::ProbeTimeout(err) => Some(err),
+            Self::BlockWaitTimeout(err) => Some(err),
+            Self::CommitteeEventTimeout(err) => Some(err),
+            Self::GasPriceTooHigh(err) => Some(err),
+            Self::CostAboveBudget(err) => Some(err),
+            Self::BytecodeMismatch(err) => Some(err),
+            Self::PostDeployCheck(err) => Some(err),
+            Self::NonceMismatch(err) => Some(err),
+            Self::Create2(err) => Some(err),
+            Self::KeyManager(err) => Some(err),
+            Self::Committee(err) => Some(err),
+            Self::HistoricalStateUnavailable(err) => Some(err),
+            Self::StateOverrideUnsupported(err) => Some(err),
+            Self::CommitteeWaitTimeout(err) => Some(err),
+            #[cfg(not(target_arch = "wasm32"))]
+            Self::TestChain(err) => Some(err),
+            Self::Config(err) | Self::Other(err) => Some(err.as_ref()),
+        }
+    }
+}
+
+impl TimeboostContractError {
+    /// the contract error of a [`Deployment`](Self::Deployment) or [`Contract`](Self::Contract)
+    /// failure, e.g. to decode a revert with [`KeyManagerError::from_contract_error`]
+    pub fn contract_error(&self) -> Option<&ContractError> {
+        match self {
+            Self::Deployment(err) | Self::Contract(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// a [`Contract`](TimeboostContractError::Contract) error, deploy paths map theirs to
+/// [`Deployment`](TimeboostContractError::Deployment) explicitly
+impl From<ContractError> for TimeboostContractError {
+    fn from(err: ContractError) -> Self {
+        Self::Contract(err)
+    }
+}
+
+/// a transaction of a contract call wasn't mined, as a
+/// [`Contract`](TimeboostContractError::Contract) error
+impl From<PendingTransactionError> for TimeboostContractError {
+    fn from(err: PendingTransactionError) -> Self {
+        Self::Contract(err.into())
+    }
+}
+
+/// a Multicall3 batch failed as a whole, the results of single calls are decoded by their callers
+impl From<MulticallError> for TimeboostContractError {
+    fn from(err: MulticallError) -> Self {
+        match err {
+            MulticallError::TransportError(err) => Self::Transport(err),
+            MulticallError::DecodeError(err) => Self::Decode(err),
+            err => Self::Other(err.into()),
+        }
+    }
+}
+
+macro_rules! from_errors {
+    ($($(#[$attr:meta])* $variant:ident($ty:ty)),* $(,)?) => {
+        $(
+            $(#[$attr])*
+            impl From<$ty> for TimeboostContractError {
+                fn from(err: $ty) -> Self {
+                    Self::$variant(err)
+                }
+            }
+        )*
+
+        /// the variant of the error `err` wraps, if any, [`TimeboostContractError::Other`]
+        /// otherwise
+        impl From<anyhow::Error> for TimeboostContractError {
+            fn from(err: anyhow::Error) -> Self {
+                let err = match err.downcast::<Self>() {
+                    Ok(err) => return err,
+                    Err(err) => err,
+                };
+                let err = match err.downcast::<ContractError>() {
+                    Ok(err) => return err.into(),
+                    Err(err) => err,
+                };
+                $(
+                    $(#[$attr])*
+                    let err = match err.downcast::<$ty>() {
+                        Ok(err) => return err.into(),
+                        Err(err) => err,
+                    };
+                )*
+                Self::Other(err)
+            }
+        }
+    };
+}
+
+from_errors!(
+    Transport(TransportError),
+    Signer(LocalSignerError),
+    #[cfg(not(target_arch = "wasm32"))]
+    Keystore(KeystoreError),
+    Decode(alloy::sol_types::Error),
+    ChainIdMismatch(ChainIdMismatch),
+    ConnectTimeout(ConnectTimeout),
+    ConnectCancelled(ConnectCancelled),
+    Disconnected(Disconnected),
+    ProbeTimeout(ProbeTimeout),
+    BlockWaitTimeout(BlockWaitTimeout),
+    CommitteeEventTimeout(CommitteeEventTimeout),
+    GasPriceTooHigh(GasPriceTooHigh),
+    CostAboveBudget(CostAboveBudget),
+    BytecodeMismatch(BytecodeMismatch),
+    PostDeployCheck(PostDeployCheckFailed),
+    NonceMismatch(NonceMismatch),
+    Create2(Create2Error),
+    KeyManager(KeyManagerError),
+    Committee(CommitteeError),
+    HistoricalStateUnavailable(HistoricalStateUnavailable),
+    StateOverrideUnsupported(StateOverrideUnsupported),
+    CommitteeWaitTimeout(CommitteeWaitTimeout),
+    #[cfg(not(target_arch = "wasm32"))]
+    TestChain(NodeError),
+);
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyManagerError, TimeboostContractError};
+    use crate::KeyManager::{self, InvalidEffectiveTimestamp, NotManager};
+    use crate::provider::{ChainIdMismatch, Disconnected};
+    use alloy::{
+        primitives::{Address, Bytes, U256},
+        providers::WalletProvider,
+        signers::local::LocalSignerError,
+        sol_types::{Revert, SolError},
+    };
+    use std::error::Error as _;
+
+    #[test]
+    fn test_decode() {
+        let data = NotManager {
+            caller: Address::with_last_byte(1),
+        }
+        .abi_encode();
+        assert_eq!(
+            KeyManagerError::decode(&data),
+            KeyManagerError::Unauthorized {
+                caller: Address::with_last_byte(1)
+            }
+        );
+
+        let err = KeyManagerError::decode(
+            &InvalidEffectiveTimestamp {
+                effectiveTimestamp: 100,
+                lastEffectiveTimestamp: 200,
+            }
+            .abi_encode(),
+        );
+        assert_eq!(
+            err.to_string(),
+            "effective timestamp 100 is not after the latest committee's 200"
+        );
+
+        let data = Revert::from("Ownable: caller is not the owner").abi_encode();
+        assert_eq!(
+            KeyManagerError::decode(&data),
+            KeyManagerError::Revert("revert: Ownable: caller is not the owner".into())
+        );
+
+        let data = [0xde, 0xad, 0xbe, 0xef, 1];
+        assert_eq!(
+            KeyManagerError::decode(&data),
+            KeyManagerError::Raw(Bytes::copy_from_slice(&data))
+        );
+        assert!(matches!(
+            KeyManagerError::decode(&[]),
+            KeyManagerError::Raw(_)
+        ));
+        assert_eq!(
+            KeyManagerError::SignatureCountMismatch {
+                committee: U256::from(4),
+                signatures: U256::from(3)
+            }
+            .to_string(),
+            "3 signatures for a committee of 4 members"
+        );
+    }
+
+    #[test]
+    fn test_timeboost_contract_error() {
+        let mismatch = ChainIdMismatch {
+            expected: 1,
+            actual: 2,
+        };
+        let err = TimeboostContractError::from(anyhow::Error::from(mismatch));
+        assert!(matches!(err, TimeboostContractError::ChainIdMismatch(m) if m == mismatch));
+        assert_eq!(err.to_string(), mismatch.to_string());
+        assert!(err.source().unwrap().is::<ChainIdMismatch>());
+
+        let hex_err = "0x1234".parse::<alloy::signers::local::PrivateKeySigner>();
+        let err = TimeboostContractError::from(hex_err.unwrap_err());
+        assert!(matches!(err, TimeboostContractError::Signer(_)));
+        assert!(err.source().unwrap().is::<LocalSignerError>());
+
+        // context doesn't hide the error
+        let err = TimeboostContractError::from(
+            anyhow::Error::from(mismatch).context("checking the endpoint"),
+        );
+        assert!(matches!(err, TimeboostContractError::ChainIdMismatch(_)));
+
+        let err = TimeboostContractError::from(anyhow::Error::from(Disconnected));
+        assert!(matches!(err, TimeboostContractError::Disconnected(_)));
+
+        let err = TimeboostContractError::from(anyhow::anyhow!("something else"));
+        assert!(matches!(err, TimeboostContractError::Other(_)));
+        assert_eq!(err.to_string(), "something else");
+    }
+
+    #[tokio::test]
+    async fn test_from_contract_error() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+
+        let err = contract
+            .initialize(Address::with_last_byte(1))
+            .call()
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            KeyManagerError::from_contract_error(&err),
+            Some(KeyManagerError::Revert(reason)) if reason.contains("already initialized")
+        ));
+
+        let err = contract
+            .setNextCommittee(100, vec![])
+            .call()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            KeyManagerError::from_contract_error(&err),
+            Some(KeyManagerError::EmptyCommittee)
+        );
+        let err = TimeboostContractError::from(err);
+        assert!(matches!(err, TimeboostContractError::Contract(_)));
+        assert_eq!(
+            err.contract_error()
+                .and_then(KeyManagerError::from_contract_error),
+            Some(KeyManagerError::EmptyCommittee)
+        );
+
+        let stranger = Address::with_last_byte(9);
+        let err = contract
+            .setThresholdEncryptionKey(Bytes::from_static(b"key"))
+            .from(stranger)
+            .call()
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            KeyManagerError::from_contract_error(&err),
+            Some(KeyManagerError::Unauthorized { caller: stranger })
+        );
+        assert_ne!(stranger, provider.default_signer_address());
+    }
+}
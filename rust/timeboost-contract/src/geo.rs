@@ -0,0 +1,216 @@
+//! Geographic diversity of committee members, from offline MaxMind GeoIP2 / GeoLite2 databases
+
+use std::{
+    collections::BTreeMap,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+};
+
+use alloy::primitives::Bytes;
+use maxminddb::{Reader, geoip2};
+use tracing::warn;
+
+use crate::CommitteeSol;
+
+/// Offline lookups of country and (optionally) autonomous system by IP address
+pub struct GeoIpResolver {
+    country: Reader<Vec<u8>>,
+    asn: Option<Reader<Vec<u8>>>,
+}
+
+impl GeoIpResolver {
+    /// open a `*-Country.mmdb` (or `*-City.mmdb`) database, without ASN lookups
+    pub fn open(country_db: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            country: Reader::open_readfile(country_db)?,
+            asn: None,
+        })
+    }
+
+    /// additionally resolve autonomous systems from a `*-ASN.mmdb` database
+    pub fn with_asn_db(mut self, asn_db: impl AsRef<Path>) -> anyhow::Result<Self> {
+        self.asn = Some(Reader::open_readfile(asn_db)?);
+        Ok(self)
+    }
+
+    fn lookup(&self, ip: IpAddr) -> Location {
+        let country = self
+            .country
+            .lookup(ip)
+            .and_then(|r| r.decode::<geoip2::Country>())
+            .unwrap_or_else(|err| {
+                warn!(%ip, %err, "country lookup failed");
+                None
+            })
+            .and_then(|c| c.country.iso_code.map(str::to_string));
+        let asn = self.asn.as_ref().and_then(|db| {
+            db.lookup(ip)
+                .and_then(|r| r.decode::<geoip2::Asn>())
+                .unwrap_or_else(|err| {
+                    warn!(%ip, %err, "ASN lookup failed");
+                    None
+                })
+        });
+        Location {
+            country,
+            asn: asn.as_ref().and_then(|a| a.autonomous_system_number),
+            asn_org: asn
+                .and_then(|a| a.autonomous_system_organization)
+                .map(str::to_string),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Location {
+    country: Option<String>,
+    asn: Option<u32>,
+    asn_org: Option<String>,
+}
+
+/// Where a single member is located, fields are `None` where the lookup had no answer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberGeo {
+    pub sig_key: Bytes,
+    pub network_address: String,
+    /// `None` if the network address is not an IP literal (e.g. a DNS name)
+    pub ip: Option<IpAddr>,
+    /// ISO 3166-1 alpha-2 country code
+    pub country: Option<String>,
+    pub asn: Option<u32>,
+    pub asn_org: Option<String>,
+}
+
+/// Distribution of a committee's members over countries and autonomous systems
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeoReport {
+    pub committee_id: u64,
+    pub members: Vec<MemberGeo>,
+    /// number of members per country code
+    pub by_country: BTreeMap<String, usize>,
+    /// number of members per autonomous system number
+    pub by_asn: BTreeMap<u32, usize>,
+    /// members resolved to neither a country nor an ASN
+    pub unresolved: usize,
+}
+
+impl GeoReport {
+    /// share of all members in the most common country, 0 for an empty committee
+    pub fn max_country_share(&self) -> f64 {
+        max_share(&self.by_country, self.members.len())
+    }
+
+    /// share of all members in the most common autonomous system, 0 for an empty committee
+    pub fn max_asn_share(&self) -> f64 {
+        max_share(&self.by_asn, self.members.len())
+    }
+}
+
+fn max_share<K>(counts: &BTreeMap<K, usize>, total: usize) -> f64 {
+    match counts.values().max() {
+        Some(max) if total > 0 => *max as f64 / total as f64,
+        _ => 0.0,
+    }
+}
+
+/// Resolve each member's `networkAddress` and report the geographic distribution of `committee`.
+/// Addresses that aren't IP literals, or that the databases don't know, count as unresolved.
+pub fn member_geo_report(committee: &CommitteeSol, resolver: &GeoIpResolver) -> GeoReport {
+    build_report(committee, |ip| resolver.lookup(ip))
+}
+
+fn build_report(committee: &CommitteeSol, lookup: impl Fn(IpAddr) -> Location) -> GeoReport {
+    let mut report = GeoReport {
+        committee_id: committee.id,
+        ..Default::default()
+    };
+    for member in &committee.members {
+        let ip = parse_ip(&member.networkAddress);
+        let location = ip.map(&lookup).unwrap_or_default();
+        if let Some(country) = &location.country {
+            *report.by_country.entry(country.clone()).or_default() += 1;
+        }
+        if let Some(asn) = location.asn {
+            *report.by_asn.entry(asn).or_default() += 1;
+        }
+        if location.country.is_none() && location.asn.is_none() {
+            report.unresolved += 1;
+        }
+        report.members.push(MemberGeo {
+            sig_key: member.sigKey.clone(),
+            network_address: member.networkAddress.clone(),
+            ip,
+            country: location.country,
+            asn: location.asn,
+            asn_org: location.asn_org,
+        });
+    }
+    report
+}
+
+/// IP of an `ip:port`, `[ipv6]:port` or bare IP network address
+fn parse_ip(addr: &str) -> Option<IpAddr> {
+    addr.parse::<SocketAddr>()
+        .map(|s| s.ip())
+        .or_else(|_| addr.parse::<IpAddr>())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Location, build_report, parse_ip};
+    use crate::{CommitteeMemberSol, CommitteeSol};
+    use alloy::primitives::U256;
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_parse_ip() {
+        assert_eq!(parse_ip("10.0.0.1:8000"), Some("10.0.0.1".parse().unwrap()));
+        assert_eq!(parse_ip("[2001:db8::1]:8000"), "2001:db8::1".parse().ok());
+        assert_eq!(parse_ip("10.0.0.1"), "10.0.0.1".parse().ok());
+        assert_eq!(parse_ip("node.example.com:8000"), None);
+    }
+
+    #[test]
+    fn test_build_report() {
+        let members = [
+            "1.0.0.1:80",
+            "1.0.0.2:80",
+            "2.0.0.1:80",
+            "node.example.com:80",
+        ]
+        .into_iter()
+        .map(|addr| CommitteeMemberSol {
+            networkAddress: addr.to_string(),
+            ..CommitteeMemberSol::random()
+        })
+        .collect();
+        let committee = CommitteeSol {
+            id: 3,
+            effectiveTimestamp: 0,
+            registeredBlockNumber: U256::ZERO,
+            members,
+        };
+
+        // 1.x is in one datacenter, 2.x only has a country record
+        let report = build_report(&committee, |ip: IpAddr| match ip.to_string().as_str() {
+            ip if ip.starts_with("1.") => Location {
+                country: Some("DE".to_string()),
+                asn: Some(64500),
+                asn_org: Some("Example Hosting".to_string()),
+            },
+            _ => Location {
+                country: Some("US".to_string()),
+                ..Default::default()
+            },
+        });
+
+        assert_eq!(report.committee_id, 3);
+        assert_eq!(report.by_country.get("DE"), Some(&2));
+        assert_eq!(report.by_country.get("US"), Some(&1));
+        assert_eq!(report.by_asn.get(&64500), Some(&2));
+        assert_eq!(report.unresolved, 1);
+        assert_eq!(report.members[3].ip, None);
+        assert_eq!(report.max_country_share(), 0.5);
+    }
+}
@@ -1,31 +1,541 @@
 //! Timeboost Contract Bindings, Deployer and API bridges.
 //!
 //! This crate provides Rust bindings and API to interact with smart contracts,
+//!
+//! It builds for `wasm32-unknown-unknown` with the `wasm` feature, e.g. to read committees from a
+//! browser over HTTP. Everything needing a native runtime is left out there: the test chain,
+//! Anvil and WS providers, keystores, raw TCP probes and the `Send` committee sources. What's
+//! left, the polling waits, retries and receipt timeouts included, sleeps on the browser's
+//! timers; the probes and waits bounded by a timeout (`probe_within`,
+//! `wait_for_next_committee`) are native-only.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
 
+#[cfg(not(target_arch = "wasm32"))]
 use alloy::{
-    primitives::Address,
-    providers::{ProviderBuilder, WalletProvider},
+    primitives::{Address, U256},
+    providers::{Provider, ProviderBuilder, WalletProvider},
+    signers::local::PrivateKeySigner,
+    transports::http::reqwest::Url,
 };
-use anyhow::Result;
 
 #[rustfmt::skip]
 #[allow(unused)]
 mod bindings;
 mod sol_types;
+mod time;
 
+pub mod abi;
+pub mod address_hex;
+#[cfg(feature = "bls")]
+pub mod bls;
+pub mod builder;
+pub mod checkpoint;
+pub mod client;
+pub mod commitment;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod connection;
 pub mod deployer;
+pub mod error;
+#[cfg(feature = "geoip")]
+pub mod geo;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod projection;
 pub mod provider;
+pub mod reader;
+pub mod safe;
+pub mod snapshot;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod source;
+pub mod stream;
+pub mod telemetry;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+pub mod timeline;
+pub mod timelock;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tracker;
+#[cfg(feature = "webhook")]
+pub mod webhook;
+pub mod writer;
 
 pub use sol_types::*;
 
-use crate::provider::TestProviderWithWallet;
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+compile_error!("building for wasm32 requires the `wasm` feature");
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{error::TimeboostContractError, provider::TestProviderWithWallet};
+
+#[cfg(not(target_arch = "wasm32"))]
+type Result<T> = std::result::Result<T, TimeboostContractError>;
 
 /// Spawn a local test blockchain and deploy KeyManager contract.
 /// Returns a WalletProvider to the chain and the deployed contract address.
+#[cfg(not(target_arch = "wasm32"))]
 pub async fn init_test_chain() -> Result<(TestProviderWithWallet, Address)> {
-    // this provider wraps both the test chain instance (exit on drop), and the wallet provider
-    let provider = ProviderBuilder::new().connect_anvil_with_wallet();
-    let km_addr =
-        deployer::deploy_key_manager_contract(&provider, provider.default_signer_address()).await?;
-    Ok((provider, km_addr))
+    TestChainBuilder::new().build().await
+}
+
+/// Spawn a local test blockchain forking the chain at `fork_url` as of `fork_block` (its latest
+/// block if `None`) and deploy a fresh KeyManager on top, like [`init_test_chain`].
+///
+/// Unlike a pristine chain, the fork has the origin's state: its contracts (e.g. Multicall3 or an
+/// existing KeyManager to upgrade), balances and base fee. To fork without deploying, use
+/// [`TestChainBuilder::spawn`].
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn init_forked_test_chain(
+    fork_url: Url,
+    fork_block: Option<u64>,
+) -> Result<(TestProviderWithWallet, Address)> {
+    let mut builder = TestChainBuilder::new().with_fork(fork_url);
+    if let Some(block) = fork_block {
+        builder = builder.with_fork_block(block);
+    }
+    builder.build().await
+}
+
+/// Spawn a local test blockchain and deploy a KeyManager like [`init_test_chain`], then register
+/// `num_committees` committees of `members_per_committee` members each. Returns the registered
+/// committees in id order, as stored.
+///
+/// Committee `i` takes effect at `100 * (i + 1)`, i.e. long ago, so the last one is current. Its
+/// members come from [`MemberGenerator::seeded`](test_util::MemberGenerator::seeded) with
+/// `seed`, valid and the same on every run.
+#[cfg(all(not(target_arch = "wasm32"), any(test, feature = "test-util")))]
+pub async fn seeded_test_chain(
+    num_committees: usize,
+    members_per_committee: usize,
+    seed: u64,
+) -> Result<(TestProviderWithWallet, Address, Vec<CommitteeSol>)> {
+    let (provider, addr) = init_test_chain().await?;
+    let contract = KeyManager::new(addr, &provider);
+    let mut members = test_util::MemberGenerator::seeded(seed).with_ports(1..=u16::MAX);
+    let mut committees = Vec::with_capacity(num_committees);
+    for i in 0..num_committees as u64 {
+        contract
+            .setNextCommittee(100 * (i + 1), members.members(members_per_committee))
+            .send()
+            .await?
+            .get_receipt()
+            .await
+            .map_err(|err| TimeboostContractError::Other(err.into()))?;
+        committees.push(contract.getCommitteeById(i).call().await?);
+    }
+    Ok((provider, addr, committees))
+}
+
+/// Mnemonic anvil derives its pre-funded accounts from unless given another one
+#[cfg(not(target_arch = "wasm32"))]
+pub const ANVIL_MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+/// Number of pre-funded accounts anvil derives from its mnemonic
+#[cfg(not(target_arch = "wasm32"))]
+pub const ANVIL_ACCOUNT_COUNT: u32 = 10;
+
+/// The pre-funded accounts of the chain spawned by [`init_test_chain`], the first being the
+/// provider's default signer (and KeyManager manager).
+///
+/// To act as another account, e.g. a caller that isn't the manager, wrap the test provider:
+/// `ProviderBuilder::new().wallet(signer).connect_provider(provider.clone())`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn test_accounts() -> Result<Vec<PrivateKeySigner>> {
+    TestChainBuilder::new().accounts()
+}
+
+/// Configures the chain spawned by [`init_test_chain`], anvil's defaults where unset.
+///
+/// Extra anvil args are passed after those of the builder. The builder always sets `--port` (a
+/// free one unless [`Self::with_port`]), and `--mnemonic`, `--chain-id`, `--block-time`,
+/// `--balance`, `--fork-url` and `--fork-block-number` when configured: pass them through the
+/// builder, not as extra args. The spawned node's output must stay anvil's, e.g. no `--silent`,
+/// for the builder to find out that it's ready.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Default)]
+pub struct TestChainBuilder {
+    chain_id: Option<u64>,
+    /// seconds between blocks, mines on demand if unset
+    block_time: Option<u64>,
+    port: Option<u16>,
+    mnemonic: Option<String>,
+    /// KeyManager manager, the deploying default signer if unset
+    manager: Option<Address>,
+    /// ether of each dev account, anvil's 10000 if unset
+    default_balance: Option<u64>,
+    /// wei set on top of the genesis, see [`Self::fund`]
+    balances: Vec<(Address, U256)>,
+    /// RPC of the chain to fork, a pristine chain if unset
+    fork_url: Option<Url>,
+    /// block to fork at, the origin's latest if unset
+    fork_block: Option<u64>,
+    /// anvil binary, `anvil` on `PATH` if unset
+    anvil_path: Option<PathBuf>,
+    anvil_args: Vec<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TestChainBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_chain_id(mut self, chain_id: u64) -> Self {
+        self.chain_id = Some(chain_id);
+        self
+    }
+
+    pub fn with_block_time(mut self, secs: u64) -> Self {
+        self.block_time = Some(secs);
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn with_mnemonic(mut self, mnemonic: impl Into<String>) -> Self {
+        self.mnemonic = Some(mnemonic.into());
+        self
+    }
+
+    pub fn with_manager(mut self, manager: Address) -> Self {
+        self.manager = Some(manager);
+        self
+    }
+
+    /// Fund each of anvil's dev accounts with `ether` rather than 10000, e.g. a manager paying
+    /// for many committee registrations
+    pub fn with_default_balance(mut self, ether: u64) -> Self {
+        self.default_balance = Some(ether);
+        self
+    }
+
+    /// Give `address` a balance of `wei`, replacing its genesis balance (a dev account's too),
+    /// see [`Self::fund`]
+    pub fn with_balance(mut self, address: Address, wei: U256) -> Self {
+        self.balances.push((address, wei));
+        self
+    }
+
+    pub fn with_fork(mut self, fork_url: Url) -> Self {
+        self.fork_url = Some(fork_url);
+        self
+    }
+
+    /// only applies with [`TestChainBuilder::with_fork`]
+    pub fn with_fork_block(mut self, block: u64) -> Self {
+        self.fork_block = Some(block);
+        self
+    }
+
+    pub fn with_anvil_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.anvil_path = Some(path.into());
+        self
+    }
+
+    /// pass `arg` to anvil, e.g. `--code-size-limit`, see the reserved args above
+    pub fn with_anvil_arg(mut self, arg: impl Into<String>) -> Self {
+        self.anvil_args.push(arg.into());
+        self
+    }
+
+    pub fn with_anvil_args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.anvil_args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// The pre-funded accounts of the chain this builds, see [`test_accounts`]
+    pub fn accounts(&self) -> Result<Vec<PrivateKeySigner>> {
+        let mnemonic = self.mnemonic.as_deref().unwrap_or(ANVIL_MNEMONIC);
+        (0..ANVIL_ACCOUNT_COUNT)
+            .map(|idx| Ok(provider::build_signer(mnemonic.to_string(), idx)?))
+            .collect()
+    }
+
+    /// Spawn the chain, [`fund`](Self::fund) it and deploy the KeyManager from the default signer
+    pub async fn build(self) -> Result<(TestProviderWithWallet, Address)> {
+        let provider = self.spawn()?;
+        self.fund(&provider).await?;
+        let manager = self
+            .manager
+            .unwrap_or_else(|| provider.default_signer_address());
+        let km_addr = deployer::deploy_key_manager_contract(&provider, manager).await?;
+        Ok((provider, km_addr))
+    }
+
+    /// Set the balances of [`Self::with_balance`] with `anvil_setBalance`; anvil's genesis can
+    /// only be given the dev accounts' balance. [`Self::build`] does this right after spawning,
+    /// call it before anything else after [`Self::spawn`].
+    pub async fn fund(&self, provider: &TestProviderWithWallet) -> Result<()> {
+        for (address, wei) in &self.balances {
+            provider
+                .raw_request::<_, ()>("anvil_setBalance".into(), (address, wei))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Spawn the chain without deploying anything or setting [`Self::with_balance`]s
+    pub fn spawn(&self) -> Result<TestProviderWithWallet> {
+        // alloy panics if anvil fails to start, catch the likely misconfiguration up front
+        if let Some(path) = &self.anvil_path
+            && !path.is_file()
+        {
+            let err = anyhow::anyhow!("no anvil binary at {}", path.display());
+            return Err(TimeboostContractError::Config(err));
+        }
+        // this provider wraps both the test chain instance (exit on drop), and the wallet provider
+        let provider =
+            ProviderBuilder::new().connect_anvil_with_wallet_and_config(|mut anvil| {
+                if let Some(chain_id) = self.chain_id {
+                    anvil = anvil.chain_id(chain_id);
+                }
+                if let Some(secs) = self.block_time {
+                    anvil = anvil.block_time(secs);
+                }
+                if let Some(port) = self.port {
+                    anvil = anvil.port(port);
+                }
+                if let Some(mnemonic) = &self.mnemonic {
+                    anvil = anvil.mnemonic(mnemonic);
+                }
+                if let Some(ether) = self.default_balance {
+                    anvil = anvil.args(["--balance".to_string(), ether.to_string()]);
+                }
+                if let Some(fork_url) = &self.fork_url {
+                    anvil = anvil.fork(fork_url.as_str());
+                    if let Some(block) = self.fork_block {
+                        anvil = anvil.fork_block_number(block);
+                    }
+                }
+                if let Some(path) = &self.anvil_path {
+                    anvil = anvil.path(path);
+                }
+                anvil.args(&self.anvil_args)
+            })?;
+        Ok(provider)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        KeyManager, TestChainBuilder, init_forked_test_chain, seeded_test_chain, test_accounts,
+    };
+    use crate::{
+        CommitteeMemberSol,
+        error::{KeyManagerError, TimeboostContractError},
+    };
+    use alloy::{
+        eips::BlockId,
+        network::EthereumWallet,
+        node_bindings::Anvil,
+        primitives::{Address, U256},
+        providers::{Provider, ProviderBuilder, WalletProvider},
+    };
+
+    #[tokio::test]
+    async fn test_chain_builder() {
+        let mnemonic = "test test test test test test test test test test test junk";
+        let manager = Address::with_last_byte(7);
+        let (provider, addr) = TestChainBuilder::new()
+            .with_chain_id(4242)
+            .with_mnemonic(mnemonic)
+            .with_manager(manager)
+            .build()
+            .await
+            .unwrap();
+        assert_eq!(provider.get_chain_id().await.unwrap(), 4242);
+        // anvil's first default account, derived from its default mnemonic
+        assert_eq!(
+            provider.default_signer_address(),
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+                .parse::<Address>()
+                .unwrap()
+        );
+        let contract = KeyManager::new(addr, &provider);
+        assert_eq!(contract.manager().call().await.unwrap(), manager);
+        assert_ne!(manager, provider.default_signer_address());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_test_chain() {
+        let (provider, addr, committees) = seeded_test_chain(3, 4, 7).await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        assert_eq!(contract.nextCommitteeId().call().await.unwrap(), 3);
+        for (i, committee) in committees.iter().enumerate() {
+            assert_eq!(committee.id, i as u64);
+            assert_eq!(committee.effectiveTimestamp, 100 * (i as u64 + 1));
+            assert_eq!(committee.members.len(), 4);
+            assert!(committee.members.iter().all(|m| m.validate().is_ok()));
+            assert_eq!(
+                contract.getCommitteeById(i as u64).call().await.unwrap(),
+                *committee
+            );
+        }
+        assert_eq!(contract.currentCommitteeId().call().await.unwrap(), 2);
+
+        // the same seed registers the same members
+        let (_, _, again) = seeded_test_chain(3, 4, 7).await.unwrap();
+        let members =
+            |cs: &[crate::CommitteeSol]| cs.iter().map(|c| c.members.clone()).collect::<Vec<_>>();
+        assert_eq!(members(&again), members(&committees));
+        let (_, _, other) = seeded_test_chain(1, 4, 8).await.unwrap();
+        assert_ne!(other[0].members, committees[0].members);
+
+        let (_, _, none) = seeded_test_chain(0, 4, 7).await.unwrap();
+        assert!(none.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_chain_builder_balances() {
+        let funded = Address::with_last_byte(7);
+        let wei = U256::from(123_456_789);
+        let ether = U256::from(10).pow(U256::from(18));
+        let builder = TestChainBuilder::new()
+            .with_default_balance(5)
+            .with_balance(funded, wei);
+        let (provider, _) = builder.clone().build().await.unwrap();
+        assert_eq!(provider.get_balance(funded).await.unwrap(), wei);
+        let accounts = builder.accounts().unwrap();
+        assert_eq!(
+            provider.get_balance(accounts[1].address()).await.unwrap(),
+            U256::from(5) * ether
+        );
+        // the deployer paid for the KeyManager
+        assert!(provider.get_balance(accounts[0].address()).await.unwrap() < U256::from(5) * ether);
+
+        // a dev account's genesis balance is replaced
+        let builder = TestChainBuilder::new().with_balance(accounts[2].address(), wei);
+        let provider = builder.spawn().unwrap();
+        builder.fund(&provider).await.unwrap();
+        assert_eq!(
+            provider.get_balance(accounts[2].address()).await.unwrap(),
+            wei
+        );
+        assert_eq!(
+            provider.get_balance(accounts[3].address()).await.unwrap(),
+            U256::from(10_000) * ether
+        );
+    }
+
+    #[test]
+    fn test_chain_builder_anvil_path() {
+        let err = TestChainBuilder::new()
+            .with_anvil_path("/nonexistent/anvil")
+            .spawn()
+            .unwrap_err();
+        assert!(matches!(err, TimeboostContractError::Config(_)));
+        assert_eq!(err.to_string(), "no anvil binary at /nonexistent/anvil");
+    }
+
+    #[tokio::test]
+    async fn test_chain_builder_anvil_args() {
+        let (provider, _) = TestChainBuilder::new()
+            .with_anvil_args(["--gas-limit", "50000000"])
+            .with_anvil_arg("--code-size-limit")
+            .with_anvil_arg("49152")
+            .build()
+            .await
+            .unwrap();
+        let block = provider
+            .get_block(BlockId::latest())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(block.header.gas_limit, 50_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_accounts_act_as_non_manager() {
+        let accounts = test_accounts().unwrap();
+        assert_eq!(accounts.len(), 10);
+
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        assert_eq!(accounts[0].address(), provider.default_signer_address());
+        let chain_accounts = provider.get_accounts().await.unwrap();
+        assert_eq!(
+            accounts.iter().map(|a| a.address()).collect::<Vec<_>>(),
+            chain_accounts
+        );
+
+        let stranger = accounts[1].clone();
+        let stranger_addr = stranger.address();
+        let other = ProviderBuilder::new()
+            .wallet(EthereumWallet::from(stranger))
+            .connect_provider(provider.clone());
+        let err = KeyManager::new(addr, &other)
+            .setNextCommittee(100, vec![CommitteeMemberSol::random()])
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(
+            KeyManagerError::from_contract_error(&err),
+            Some(KeyManagerError::Unauthorized {
+                caller: stranger_addr
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forked_test_chain() {
+        // fork a local chain, so the test doesn't depend on a remote RPC
+        let origin = Anvil::new().spawn();
+        let origin_provider = ProviderBuilder::new()
+            .wallet(origin.wallet().unwrap())
+            .connect_http(origin.endpoint_url());
+        let manager = origin_provider.default_signer_address();
+        let existing = crate::deployer::deploy_key_manager_contract(&origin_provider, manager)
+            .await
+            .unwrap();
+        let fork_block = origin_provider.get_block_number().await.unwrap();
+        KeyManager::new(existing, &origin_provider)
+            .setNextCommittee(100, vec![CommitteeMemberSol::random()])
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+
+        let (provider, addr) = init_forked_test_chain(origin.endpoint_url(), Some(fork_block))
+            .await
+            .unwrap();
+        assert_ne!(addr, existing);
+        let fresh = KeyManager::new(addr, &provider);
+        assert_eq!(
+            fresh.manager().call().await.unwrap(),
+            provider.default_signer_address()
+        );
+
+        // the origin's KeyManager as of the fork block, before the registration
+        let forked = KeyManager::new(existing, &provider);
+        assert_eq!(forked.manager().call().await.unwrap(), manager);
+        assert_eq!(forked.nextCommitteeId().call().await.unwrap(), 0);
+        assert_eq!(
+            KeyManager::new(existing, &origin_provider)
+                .nextCommitteeId()
+                .call()
+                .await
+                .unwrap(),
+            1
+        );
+
+        // forking without deploying
+        let provider = TestChainBuilder::new()
+            .with_fork(origin.endpoint_url())
+            .spawn()
+            .unwrap();
+        assert_eq!(
+            KeyManager::new(existing, &provider)
+                .nextCommitteeId()
+                .call()
+                .await
+                .unwrap(),
+            1
+        );
+    }
 }
@@ -0,0 +1,187 @@
+//! In-memory KeyManager for unit tests that don't need a chain
+//!
+//! [`MockKeyManager`] implements [`KeyManagerRead`] like the real [`KeyManagerClient`], with the
+//! same errors for missing committees, so code generic over the trait can be tested without
+//! spawning anvil. The test sets up committees and the clock directly.
+//!
+//! [`KeyManagerClient`]: crate::client::KeyManagerClient
+
+use std::{
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use alloy::primitives::{Address, U256};
+
+use crate::{
+    CommitteeMemberSol, CommitteeSol,
+    KeyManager::{CommitteeIdDoesNotExist, KeyManagerErrors, NoCommitteeScheduled},
+    client::{ClientError, ClientResult, KeyManagerRead},
+};
+
+#[derive(Debug)]
+struct State {
+    manager: Address,
+    /// all committees ever added, indexed by id
+    committees: Vec<CommitteeSol>,
+    /// committees below this id have been pruned
+    oldest: u64,
+    /// timestamp of the "latest block"
+    now: u64,
+}
+
+/// Committees served from memory, see the [module docs](self)
+#[derive(Debug)]
+pub struct MockKeyManager {
+    state: Mutex<State>,
+}
+
+impl MockKeyManager {
+    /// An empty KeyManager with clock at the current system time
+    pub fn new(manager: Address) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self {
+            state: Mutex::new(State {
+                manager,
+                committees: Vec::new(),
+                oldest: 0,
+                now,
+            }),
+        }
+    }
+
+    pub fn set_manager(&self, manager: Address) {
+        self.state.lock().unwrap().manager = manager;
+    }
+
+    /// set the timestamp `current_committee_id` is evaluated at
+    pub fn set_timestamp(&self, now: u64) {
+        self.state.lock().unwrap().now = now;
+    }
+
+    /// Register the next committee, returning its id. Unlike the contract this doesn't check the
+    /// members or that timestamps increase, so tests can set up arbitrary histories.
+    pub fn add_committee(&self, effective_timestamp: u64, members: Vec<CommitteeMemberSol>) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.committees.len() as u64;
+        state.committees.push(CommitteeSol {
+            id,
+            effectiveTimestamp: effective_timestamp,
+            // one block per committee
+            registeredBlockNumber: U256::from(id + 1),
+            members,
+        });
+        id
+    }
+
+    /// Drop all committees up to and including `id`, like the contract's `pruneUntil`
+    pub fn prune_until(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.oldest = state.oldest.max(id + 1);
+    }
+
+    /// stored committees, newest first
+    fn newest_first(state: &State) -> impl Iterator<Item = &CommitteeSol> {
+        let oldest = state.oldest.min(state.committees.len() as u64) as usize;
+        state.committees[oldest..].iter().rev()
+    }
+}
+
+impl KeyManagerRead for MockKeyManager {
+    async fn manager(&self) -> ClientResult<Address> {
+        Ok(self.state.lock().unwrap().manager)
+    }
+
+    async fn current_committee_id(&self) -> ClientResult<u64> {
+        let state = self.state.lock().unwrap();
+        Self::newest_first(&state)
+            .find(|c| c.effectiveTimestamp <= state.now)
+            .map(|c| c.id)
+            .ok_or(ClientError::Reverted(
+                KeyManagerErrors::NoCommitteeScheduled(NoCommitteeScheduled),
+            ))
+    }
+
+    async fn next_committee_id(&self) -> ClientResult<u64> {
+        Ok(self.state.lock().unwrap().committees.len() as u64)
+    }
+
+    async fn committee_by_id(&self, id: u64) -> ClientResult<CommitteeSol> {
+        let state = self.state.lock().unwrap();
+        state
+            .committees
+            .get(id as usize)
+            .filter(|_| id >= state.oldest)
+            .cloned()
+            .ok_or(ClientError::Reverted(
+                KeyManagerErrors::CommitteeIdDoesNotExist(CommitteeIdDoesNotExist {
+                    committeeId: id,
+                }),
+            ))
+    }
+
+    async fn current_committee(&self, now: u64) -> ClientResult<Option<CommitteeSol>> {
+        let state = self.state.lock().unwrap();
+        Ok(Self::newest_first(&state)
+            .find(|c| c.effectiveTimestamp <= now)
+            .cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MockKeyManager;
+    use crate::{
+        CommitteeMemberSol,
+        KeyManager::KeyManagerErrors,
+        client::{ClientError, KeyManagerRead},
+    };
+    use alloy::primitives::Address;
+
+    /// stands in for downstream code written against the trait
+    async fn active_size(km: &impl KeyManagerRead) -> Option<usize> {
+        let id = km.current_committee_id().await.ok()?;
+        Some(km.committee_by_id(id).await.ok()?.members.len())
+    }
+
+    #[tokio::test]
+    async fn test_mock_key_manager() {
+        let km = MockKeyManager::new(Address::with_last_byte(1));
+        assert_eq!(km.manager().await.unwrap(), Address::with_last_byte(1));
+        assert_eq!(km.next_committee_id().await.unwrap(), 0);
+        assert!(matches!(
+            km.current_committee_id().await,
+            Err(ClientError::Reverted(
+                KeyManagerErrors::NoCommitteeScheduled(_)
+            ))
+        ));
+
+        let members = vec![CommitteeMemberSol::random(), CommitteeMemberSol::random()];
+        assert_eq!(km.add_committee(100, members.clone()), 0);
+        assert_eq!(km.add_committee(200, members[..1].to_vec()), 1);
+        assert_eq!(km.next_committee_id().await.unwrap(), 2);
+        assert_eq!(km.committee_count().await.unwrap(), 2);
+        assert_eq!(km.committee_by_id(0).await.unwrap().members, members);
+
+        km.set_timestamp(150);
+        assert_eq!(km.current_committee_id().await.unwrap(), 0);
+        assert_eq!(active_size(&km).await, Some(2));
+        km.set_timestamp(200);
+        assert_eq!(active_size(&km).await, Some(1));
+        assert!(km.current_committee(99).await.unwrap().is_none());
+        assert_eq!(km.current_committee(199).await.unwrap().unwrap().id, 0);
+
+        km.prune_until(0);
+        assert!(matches!(
+            km.committee_by_id(0).await,
+            Err(ClientError::Reverted(
+                KeyManagerErrors::CommitteeIdDoesNotExist(e)
+            )) if e.committeeId == 0
+        ));
+        assert!(km.committee_by_id(2).await.is_err());
+        assert!(km.current_committee(150).await.unwrap().is_none());
+        assert_eq!(km.next_committee_id().await.unwrap(), 2);
+    }
+}
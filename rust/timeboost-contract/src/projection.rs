@@ -0,0 +1,211 @@
+//! Committee reads that decode only selected member fields
+//!
+//! `getCommitteeById` always returns the full committee, so a projection saves no bandwidth or
+//! RPC cost. It only skips the allocation and copying of unwanted key material and strings,
+//! which is noticeable for large committees read often, e.g. when only the signing keys are needed.
+//! For anything else prefer the plain `getCommitteeById` call.
+
+use std::ops::BitOr;
+
+use alloy::{
+    primitives::{Address, Bytes, U256},
+    providers::Provider,
+    sol_types::Error as AbiError,
+};
+
+use crate::KeyManager::KeyManagerInstance;
+
+/// Set of committee member fields to decode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CommitteeFields(u8);
+
+impl CommitteeFields {
+    pub const NONE: Self = Self(0);
+    pub const SIG_KEY: Self = Self(1 << 0);
+    pub const DH_KEY: Self = Self(1 << 1);
+    pub const DKG_KEY: Self = Self(1 << 2);
+    pub const SIG_KEY_ADDRESS: Self = Self(1 << 3);
+    pub const NETWORK_ADDRESS: Self = Self(1 << 4);
+    pub const BATCH_POSTER_ADDRESS: Self = Self(1 << 5);
+    pub const ALL: Self = Self((1 << 6) - 1);
+
+    pub fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for CommitteeFields {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// A committee member with only the requested fields decoded, the rest is `None`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProjectedMember {
+    pub sig_key: Option<Bytes>,
+    pub dh_key: Option<Bytes>,
+    pub dkg_key: Option<Bytes>,
+    pub sig_key_address: Option<Address>,
+    pub network_address: Option<String>,
+    pub batch_poster_address: Option<String>,
+}
+
+/// A committee with its members projected onto a [`CommitteeFields`] selection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectedCommittee {
+    pub id: u64,
+    pub effective_timestamp: u64,
+    pub registered_block_number: U256,
+    pub members: Vec<ProjectedMember>,
+}
+
+/// Read committee `id`, decoding only the member `fields` requested
+pub async fn fetch_committee_projected<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    id: u64,
+    fields: CommitteeFields,
+) -> Result<ProjectedCommittee, alloy::contract::Error> {
+    let data = contract.getCommitteeById(id).call_raw().await?;
+    Ok(decode_projected(&data, fields)?)
+}
+
+/// Decode `getCommitteeById` return data, i.e. `abi.encode(committee)`, walking the ABI offsets
+/// and skipping unrequested dynamic fields.
+fn decode_projected(data: &[u8], fields: CommitteeFields) -> Result<ProjectedCommittee, AbiError> {
+    let buf = AbiBuf(data);
+    let committee = buf.offset(0, 0)?;
+    let members = buf.offset(committee, 3 * 32)?;
+    let len = buf.usize_at(members)?;
+    let heads = members + 32;
+
+    let mut projected = Vec::with_capacity(len.min(data.len() / 32));
+    for i in 0..len {
+        let m = buf.offset(heads, i * 32)?;
+        let pick = |field| fields.contains(field);
+        projected.push(ProjectedMember {
+            sig_key: pick(CommitteeFields::SIG_KEY)
+                .then(|| buf.bytes(buf.offset(m, 0)?))
+                .transpose()?,
+            dh_key: pick(CommitteeFields::DH_KEY)
+                .then(|| buf.bytes(buf.offset(m, 32)?))
+                .transpose()?,
+            dkg_key: pick(CommitteeFields::DKG_KEY)
+                .then(|| buf.bytes(buf.offset(m, 2 * 32)?))
+                .transpose()?,
+            sig_key_address: pick(CommitteeFields::SIG_KEY_ADDRESS)
+                .then(|| buf.word(m + 3 * 32).map(|w| Address::from_slice(&w[12..])))
+                .transpose()?,
+            network_address: pick(CommitteeFields::NETWORK_ADDRESS)
+                .then(|| buf.string(buf.offset(m, 4 * 32)?))
+                .transpose()?,
+            batch_poster_address: pick(CommitteeFields::BATCH_POSTER_ADDRESS)
+                .then(|| buf.string(buf.offset(m, 5 * 32)?))
+                .transpose()?,
+        });
+    }
+
+    Ok(ProjectedCommittee {
+        id: buf.u64_at(committee)?,
+        effective_timestamp: buf.u64_at(committee + 32)?,
+        registered_block_number: U256::from_be_slice(buf.word(committee + 2 * 32)?),
+        members: projected,
+    })
+}
+
+/// Bounds-checked access to ABI-encoded data
+struct AbiBuf<'a>(&'a [u8]);
+
+impl AbiBuf<'_> {
+    fn word(&self, at: usize) -> Result<&[u8], AbiError> {
+        let end = at.checked_add(32).ok_or(AbiError::Overrun)?;
+        self.0.get(at..end).ok_or(AbiError::Overrun)
+    }
+
+    fn usize_at(&self, at: usize) -> Result<usize, AbiError> {
+        U256::from_be_slice(self.word(at)?)
+            .try_into()
+            .map_err(|_| AbiError::Overrun)
+    }
+
+    fn u64_at(&self, at: usize) -> Result<u64, AbiError> {
+        U256::from_be_slice(self.word(at)?)
+            .try_into()
+            .map_err(|_| AbiError::custom("uint64 out of range"))
+    }
+
+    /// absolute position of the dynamic value whose offset (relative to `base`) is at `base + head`
+    fn offset(&self, base: usize, head: usize) -> Result<usize, AbiError> {
+        let head = base.checked_add(head).ok_or(AbiError::Overrun)?;
+        base.checked_add(self.usize_at(head)?)
+            .ok_or(AbiError::Overrun)
+    }
+
+    fn bytes(&self, at: usize) -> Result<Bytes, AbiError> {
+        let len = self.usize_at(at)?;
+        // `at + 32` can't overflow once the length word was read
+        let start = at + 32;
+        let end = start.checked_add(len).ok_or(AbiError::Overrun)?;
+        self.0
+            .get(start..end)
+            .map(Bytes::copy_from_slice)
+            .ok_or(AbiError::Overrun)
+    }
+
+    fn string(&self, at: usize) -> Result<String, AbiError> {
+        String::from_utf8(self.bytes(at)?.to_vec()).map_err(|_| AbiError::custom("invalid utf-8"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommitteeFields, decode_projected};
+    use crate::{CommitteeMemberSol, CommitteeSol};
+    use alloy::{
+        primitives::{Address, U256},
+        sol_types::SolValue,
+    };
+
+    #[test]
+    fn test_decode_projected() {
+        let members = (0..3)
+            .map(|i| CommitteeMemberSol {
+                sigKeyAddress: Address::with_last_byte(i),
+                ..CommitteeMemberSol::random()
+            })
+            .collect::<Vec<_>>();
+        let committee = CommitteeSol {
+            id: 4,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members: members.clone(),
+        };
+        let data = committee.abi_encode();
+
+        let fields = CommitteeFields::SIG_KEY | CommitteeFields::BATCH_POSTER_ADDRESS;
+        let projected = decode_projected(&data, fields).unwrap();
+        assert_eq!(projected.id, 4);
+        assert_eq!(projected.effective_timestamp, 100);
+        assert_eq!(projected.registered_block_number, U256::from(10));
+        for (p, m) in projected.members.iter().zip(&members) {
+            assert_eq!(p.sig_key.as_ref(), Some(&m.sigKey));
+            assert_eq!(p.batch_poster_address.as_ref(), Some(&m.batchPosterAddress));
+            assert!(p.dh_key.is_none() && p.network_address.is_none());
+        }
+
+        let all = decode_projected(&data, CommitteeFields::ALL).unwrap();
+        assert_eq!(
+            all.members[2].sig_key_address,
+            Some(Address::with_last_byte(2))
+        );
+        assert_eq!(all.members[1].dkg_key.as_ref(), Some(&members[1].dkgKey));
+        assert_eq!(
+            all.members[0].network_address.as_ref(),
+            Some(&members[0].networkAddress)
+        );
+
+        assert!(decode_projected(&data[..data.len() - 40], CommitteeFields::ALL).is_err());
+    }
+}
@@ -0,0 +1,2027 @@
+//! Helpers for reading committee state from a deployed KeyManager
+//!
+//! Reads take the provider and the address of the KeyManager proxy, and all of them fail with a
+//! [`TimeboostContractError`].
+
+use std::{collections::HashSet, fmt, net::SocketAddr, time::Duration};
+
+use alloy::{
+    contract::Error as ContractError,
+    eips::BlockId,
+    primitives::{Address, Bytes, TxHash, U256},
+    providers::{MULTICALL3_ADDRESS, MulticallItem, Provider},
+    rpc::types::{Filter, state::StateOverride},
+    sol_types::SolEvent,
+    transports::{RpcError, TransportErrorKind},
+};
+use futures::{Stream, future::try_join_all, stream};
+use serde::Serialize;
+
+use crate::{
+    Committee, CommitteeMemberSol, CommitteeSol, InvalidEndpoint,
+    KeyManager::{
+        CommitteeIdDoesNotExist, KeyManagerInstance, ManagerChanged, NoCommitteeScheduled,
+        getCommitteeByIdCall,
+    },
+    error::{KeyManagerError, Result, TimeboostContractError},
+    member_index,
+};
+
+type ContractResult<T> = Result<T, ContractError>;
+
+/// Read committee `id`, `None` if it was never registered or has been pruned
+pub(crate) async fn try_committee<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    id: u64,
+) -> ContractResult<Option<CommitteeSol>> {
+    try_committee_at(contract, id, BlockId::latest()).await
+}
+
+/// [`try_committee`] as of block `at`
+pub(crate) async fn try_committee_at<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    id: u64,
+    at: BlockId,
+) -> ContractResult<Option<CommitteeSol>> {
+    match contract.getCommitteeById(id).block(at).call().await {
+        Ok(committee) => Ok(Some(committee)),
+        Err(err) if err.as_decoded_error::<CommitteeIdDoesNotExist>().is_some() => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// All committees still stored in the contract (i.e. not pruned), in id order, fetched concurrently
+pub(crate) async fn stored_committees<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+) -> ContractResult<Vec<CommitteeSol>> {
+    let next_id = contract.nextCommitteeId().call().await?;
+    let committees = try_join_all((0..next_id).map(|id| try_committee(contract, id))).await?;
+    Ok(committees.into_iter().flatten().collect())
+}
+
+/// Committee `id` of the KeyManager at `proxy` as a [`Committee`], `None` if it was never
+/// registered or has been pruned. Fails with a [`CommitteeError`](crate::CommitteeError) if it
+/// doesn't convert.
+pub async fn committee_by_id<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    id: u64,
+) -> Result<Option<Committee>> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    match try_committee(&contract, id).await? {
+        Some(committee) => Ok(Some(committee.try_into()?)),
+        None => Ok(None),
+    }
+}
+
+/// The node can't read state as of the requested block, e.g. a full node that pruned it; reading
+/// the past needs an archive node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoricalStateUnavailable {
+    pub block: BlockId,
+    /// the node's error message
+    pub message: String,
+}
+
+impl fmt::Display for HistoricalStateUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no state at block {} on this node (is it an archive node?): {}",
+            self.block, self.message
+        )
+    }
+}
+
+impl std::error::Error for HistoricalStateUnavailable {}
+
+/// Committee `id` of the KeyManager at `proxy` as stored at `block`, like [`committee_by_id`]
+/// but reading past storage: `None` if it wasn't registered yet or had been pruned by then.
+///
+/// Fails with a [`HistoricalStateUnavailable`] if the node no longer has the state of `block`.
+pub async fn committee_by_id_at_block<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    id: u64,
+    block: BlockId,
+) -> Result<Option<Committee>> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let committee = try_committee_at(&contract, id, block)
+        .await
+        .map_err(|err| match missing_state_message(&err) {
+            Some(message) => {
+                TimeboostContractError::from(HistoricalStateUnavailable { block, message })
+            }
+            None => err.into(),
+        })?;
+    Ok(committee.map(Committee::try_from).transpose()?)
+}
+
+/// the error message if `err` means the node lacks the state of the requested block, going by the
+/// messages of common nodes (geth, erigon, nethermind, reth, ...)
+fn missing_state_message(err: &ContractError) -> Option<String> {
+    let ContractError::TransportError(RpcError::ErrorResp(payload)) = err else {
+        return None;
+    };
+    let message = payload.message.to_lowercase();
+    [
+        "missing trie node",
+        "historical state",
+        "state not available",
+        "state is not available",
+        "state unavailable",
+        "pruned",
+        "header not found",
+        "distance to target block exceeds maximum",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+    .then(|| payload.message.to_string())
+}
+
+/// The node rejected the state override set of an `eth_call`, i.e. it doesn't support overrides
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateOverrideUnsupported {
+    /// the node's error message
+    pub message: String,
+}
+
+impl fmt::Display for StateOverrideUnsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "this node doesn't support eth_call state overrides: {}",
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for StateOverrideUnsupported {}
+
+/// Committee `id` of the KeyManager at `proxy` as if account state (code, storage, ...) were
+/// replaced by `overrides` for this single read, like [`committee_by_id`] otherwise; nothing is
+/// written to the chain. This allows what-if reads against synthetic committee state, e.g. by
+/// overriding the proxy's storage slots.
+///
+/// Requires the node to support the state override set parameter of `eth_call` (geth, reth,
+/// erigon, anvil, ... do), fails with a [`StateOverrideUnsupported`] if it rejects it.
+pub async fn committee_by_id_with_overrides<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    id: u64,
+    overrides: StateOverride,
+) -> Result<Option<Committee>> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let committee = match contract.getCommitteeById(id).state(overrides).call().await {
+        Ok(committee) => Some(committee),
+        Err(err) if err.as_decoded_error::<CommitteeIdDoesNotExist>().is_some() => None,
+        Err(err) => {
+            return Err(match unsupported_override_message(&err) {
+                Some(message) => StateOverrideUnsupported { message }.into(),
+                None => err.into(),
+            });
+        }
+    };
+    Ok(committee.map(Committee::try_from).transpose()?)
+}
+
+/// the error message if `err` means the node rejected the state override parameter, going by the
+/// messages of common nodes and RPC providers; reverts never count
+fn unsupported_override_message(err: &ContractError) -> Option<String> {
+    let ContractError::TransportError(RpcError::ErrorResp(payload)) = err else {
+        return None;
+    };
+    if payload.as_revert_data().is_some() {
+        return None;
+    }
+    let message = payload.message.to_lowercase();
+    [
+        "too many arguments",
+        "too many params",
+        "state override",
+        "stateoverride",
+        "not supported",
+        "unsupported",
+    ]
+    .iter()
+    .any(|pattern| message.contains(pattern))
+    .then(|| payload.message.to_string())
+}
+
+/// Number of committees ever registered to the KeyManager at `proxy`, i.e. the next committee id.
+///
+/// Committee ids are `0..count`, those below the oldest stored one may have been pruned.
+pub async fn committee_count<P: Provider>(provider: &P, proxy: Address) -> Result<u64> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    Ok(contract.nextCommitteeId().call().await?)
+}
+
+/// Committees `ids` of the KeyManager at `proxy`, in the order of `ids`, `None` for those never
+/// registered or pruned.
+///
+/// All are read in a single `eth_call` to Multicall3 (at its canonical address). On chains
+/// where it isn't deployed, e.g. a fresh anvil, this falls back to reading them one at a time.
+pub async fn committees_batch<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    ids: &[u64],
+) -> Result<Vec<Option<CommitteeSol>>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let batch = provider
+        .multicall()
+        .dynamic::<getCommitteeByIdCall>()
+        .extend(ids.iter().map(|&id| contract.getCommitteeById(id)));
+    let results = match batch.aggregate3().await {
+        Ok(results) => results,
+        Err(err) => {
+            if !provider.get_code_at(MULTICALL3_ADDRESS).await?.is_empty() {
+                return Err(err.into());
+            }
+            tracing::debug!("no Multicall3 deployed, reading committees one at a time");
+            let mut committees = Vec::with_capacity(ids.len());
+            for &id in ids {
+                committees.push(try_committee(&contract, id).await?);
+            }
+            return Ok(committees);
+        }
+    };
+    results
+        .into_iter()
+        .map(|result| match result {
+            Ok(committee) => Ok(Some(committee)),
+            Err(failure) => match KeyManagerError::decode(&failure.return_data) {
+                KeyManagerError::CommitteeNotFound { .. } => Ok(None),
+                err => Err(err.into()),
+            },
+        })
+        .collect()
+}
+
+/// Every stored committee in id order, fetched one at a time. Ids pruned from the front are
+/// skipped, afterwards the first id that doesn't exist ends the stream, so committees
+/// registered while it is consumed are included.
+pub fn all_committees<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> impl Stream<Item = Result<CommitteeSol>> + '_ {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    stream::try_unfold(
+        (contract, 0, None),
+        |(contract, mut id, count)| async move {
+            let count = match count {
+                Some(count) => count,
+                None => contract.nextCommitteeId().call().await?,
+            };
+            loop {
+                match try_committee(&contract, id).await? {
+                    Some(committee) => {
+                        return Ok(Some((committee, (contract, id + 1, Some(count)))));
+                    }
+                    None if id < count => id += 1,
+                    None => return Ok(None),
+                }
+            }
+        },
+    )
+}
+
+/// The most recent `limit` committees, newest first.
+/// Returns fewer than `limit` if the (unpruned) history is shorter.
+pub async fn committees_newest_first<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    limit: u64,
+) -> Result<Vec<CommitteeSol>> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let next_id = contract.nextCommitteeId().call().await?;
+    let ids = (next_id.saturating_sub(limit)..next_id).rev();
+    let committees = try_join_all(ids.map(|id| try_committee(&contract, id))).await?;
+
+    // ids below the oldest stored committee have been pruned
+    Ok(committees.into_iter().map_while(|c| c).collect())
+}
+
+/// The committee of the KeyManager at `proxy` in effect at `now`, i.e. the one with the greatest
+/// `effectiveTimestamp <= now`, preferring the highest id among equal timestamps. `None` if no
+/// committee is effective yet, or the ones that were have all been pruned.
+///
+/// Walks back from the newest committee and stops at the first effective one, relying on the
+/// contract only accepting increasing timestamps.
+pub async fn current_committee<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    now: u64,
+) -> Result<Option<CommitteeSol>> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    Ok(committee_at_time(&contract, now).await?)
+}
+
+/// [`current_committee`] of `contract`
+pub(crate) async fn committee_at_time<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    now: u64,
+) -> ContractResult<Option<CommitteeSol>> {
+    let next_id = contract.nextCommitteeId().call().await?;
+    for id in (0..next_id).rev() {
+        match try_committee(contract, id).await? {
+            Some(c) if c.effectiveTimestamp <= now => return Ok(Some(c)),
+            Some(_) => {}
+            None => break,
+        }
+    }
+    Ok(None)
+}
+
+/// The member with `sigKeyAddress` `addr` of the committee in effect at `now` (see
+/// [`current_committee`]) of the KeyManager at `proxy`, with its index. `None` if `addr` isn't a
+/// member, or no committee is in effect.
+pub async fn find_member<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    addr: Address,
+    now: u64,
+) -> Result<Option<(usize, CommitteeMemberSol)>> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let Some(mut committee) = committee_at_time(&contract, now).await? else {
+        return Ok(None);
+    };
+    Ok(member_index(&committee, addr).map(|i| (i, committee.members.swap_remove(i))))
+}
+
+/// The `networkAddress`es of the committee in effect at `now` (see [`current_committee`]) of the
+/// KeyManager at `proxy`, e.g. to bootstrap the network layer: parsed and deduplicated, in member
+/// order. Members whose address doesn't parse as a socket address, a hostname included, are
+/// skipped with a warning. Empty if no committee is in effect.
+pub async fn current_peer_addresses<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    now: u64,
+) -> Result<Vec<SocketAddr>> {
+    member_addresses(
+        provider,
+        proxy,
+        now,
+        CommitteeMemberSol::network_socket_addr,
+    )
+    .await
+}
+
+/// [`current_peer_addresses`] for the `batchPosterAddress`es
+pub async fn batch_poster_addresses<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    now: u64,
+) -> Result<Vec<SocketAddr>> {
+    member_addresses(
+        provider,
+        proxy,
+        now,
+        CommitteeMemberSol::batch_poster_socket_addr,
+    )
+    .await
+}
+
+async fn member_addresses<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    now: u64,
+    parse: fn(&CommitteeMemberSol) -> Result<SocketAddr, InvalidEndpoint>,
+) -> Result<Vec<SocketAddr>> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let Some(committee) = committee_at_time(&contract, now).await? else {
+        return Ok(Vec::new());
+    };
+    let mut seen = HashSet::new();
+    let mut addrs = Vec::new();
+    for (index, member) in committee.members.iter().enumerate() {
+        match parse(member) {
+            Ok(addr) if seen.insert(addr) => addrs.push(addr),
+            Ok(_) => {}
+            Err(err) => tracing::warn!(committee = %committee.id, %index, %err, "skipping member"),
+        }
+    }
+    Ok(addrs)
+}
+
+/// The committee whose effective timestamp is exactly `ts`, with its id, from the KeyManager at
+/// `proxy`. `None` if there is none, or it has been pruned.
+///
+/// `CommitteeCreated` carries only the id, so matching against events would still read every
+/// committee. Instead, as the contract only accepts increasing effective timestamps, this binary
+/// searches the ids, reading `O(log n)` committees.
+pub async fn committee_by_timestamp<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    ts: u64,
+) -> Result<Option<(u64, CommitteeSol)>> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let (mut lo, mut hi) = (0, contract.nextCommitteeId().call().await?);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        match try_committee(&contract, mid).await? {
+            Some(c) if c.effectiveTimestamp == ts => return Ok(Some((mid, c))),
+            Some(c) if c.effectiveTimestamp > ts => hi = mid,
+            // pruned ids precede all stored ones
+            Some(_) | None => lo = mid + 1,
+        }
+    }
+    Ok(None)
+}
+
+/// One committee member, flattened together with its committee for tabular analysis
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemberRow {
+    pub committee_id: u64,
+    pub effective_timestamp: u64,
+    /// position of the member within its committee
+    pub member_index: usize,
+    pub sig_key: Bytes,
+    pub dh_key: Bytes,
+    pub dkg_key: Bytes,
+    #[serde(with = "crate::address_hex")]
+    pub sig_key_address: Address,
+    pub network_address: String,
+    pub batch_poster_address: String,
+}
+
+/// All members of all stored committees as flat rows, ordered by committee id then member index,
+/// suitable for loading into a dataframe or database.
+pub async fn committee_members_table<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> Result<Vec<MemberRow>> {
+    let contract = &KeyManagerInstance::new(proxy, provider);
+    let committees = stored_committees(contract).await?;
+    let rows = committees
+        .into_iter()
+        .flat_map(|c| {
+            c.members
+                .into_iter()
+                .enumerate()
+                .map(move |(member_index, m)| MemberRow {
+                    committee_id: c.id,
+                    effective_timestamp: c.effectiveTimestamp,
+                    member_index,
+                    sig_key: m.sigKey,
+                    dh_key: m.dhKey,
+                    dkg_key: m.dkgKey,
+                    sig_key_address: m.sigKeyAddress,
+                    network_address: m.networkAddress,
+                    batch_poster_address: m.batchPosterAddress,
+                })
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// The number of registered committees together with the latest one, both read at the same
+/// block so that a committee update landing in between can't make them disagree.
+/// The latest committee is `None` if no committee was registered yet.
+pub async fn committee_snapshot<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> Result<(u64, Option<CommitteeSol>)> {
+    let contract = &KeyManagerInstance::new(proxy, provider);
+    let at = BlockId::number(contract.provider().get_block_number().await?);
+    let count = contract.nextCommitteeId().block(at).call().await?;
+    let latest = match count.checked_sub(1) {
+        Some(id) => try_committee_at(contract, id, at).await?,
+        None => None,
+    };
+    Ok((count, latest))
+}
+
+/// Spread of the intervals between consecutive committees' effective timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotationStats {
+    /// number of intervals, one less than the number of committees
+    pub rotations: usize,
+    pub mean: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl RotationStats {
+    /// stats over consecutive `effective_timestamps`, `None` for fewer than two
+    fn from_timestamps(effective_timestamps: &[u64]) -> Option<Self> {
+        let intervals = effective_timestamps
+            .windows(2)
+            .map(|w| w[1].saturating_sub(w[0]))
+            .collect::<Vec<_>>();
+        let rotations = intervals.len();
+        let total = intervals.iter().fold(0u64, |acc, &i| acc.saturating_add(i));
+        Some(Self {
+            rotations,
+            mean: Duration::from_secs(total) / u32::try_from(rotations).ok()?.max(1),
+            min: Duration::from_secs(*intervals.iter().min()?),
+            max: Duration::from_secs(*intervals.iter().max()?),
+        })
+    }
+}
+
+/// Mean, min and max time between consecutive committees over the stored history,
+/// `None` if fewer than two committees exist.
+pub async fn rotation_interval_stats<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> Result<Option<RotationStats>> {
+    let contract = &KeyManagerInstance::new(proxy, provider);
+    let timestamps = stored_committees(contract)
+        .await?
+        .iter()
+        .map(|c| c.effectiveTimestamp)
+        .collect::<Vec<_>>();
+    Ok(RotationStats::from_timestamps(&timestamps))
+}
+
+/// Mean time between consecutive committees' effective timestamps,
+/// `None` if fewer than two committees exist. See [`rotation_interval_stats`] for min and max.
+pub async fn average_rotation_interval<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> Result<Option<Duration>> {
+    let contract = &KeyManagerInstance::new(proxy, provider);
+    let stats = rotation_interval_stats(contract.provider(), *contract.address()).await?;
+    Ok(stats.map(|s| s.mean))
+}
+
+/// The latest committee registered in a finalized block, `None` if there is none yet.
+///
+/// Unlike reads at `latest`, the result can't be reorged away. On chains whose RPC doesn't
+/// know the `finalized` block tag, the call fails with the node's error.
+pub async fn latest_finalized_committee<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> Result<Option<CommitteeSol>> {
+    let contract = &KeyManagerInstance::new(proxy, provider);
+    let at = BlockId::finalized();
+    let count = contract.nextCommitteeId().block(at).call().await?;
+    match count.checked_sub(1) {
+        Some(id) => Ok(try_committee_at(contract, id, at).await?),
+        None => Ok(None),
+    }
+}
+
+/// The first committee (id 0) and whether it was part of the deployment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenesisCommittee {
+    pub committee: CommitteeSol,
+    /// registered in the block the contract was created in
+    pub bootstrap: bool,
+}
+
+/// Read committee 0, `None` if no committee was registered yet or it has been pruned.
+///
+/// Heuristic: committee 0 counts as the bootstrap committee if its `registeredBlockNumber` equals
+/// the block the KeyManager at the contract address (the proxy) was created in, as found by
+/// [`crate::provider::contract_creation_block`], i.e. it was seeded as part of the deployment
+/// rather than by a later `setNextCommittee`. `initialize` itself only sets the manager, so this
+/// recognizes deployments that register the first committee in the same block as the proxy
+/// creation; one registered a block later is not considered bootstrap. Needs a node serving
+/// historical state.
+pub async fn genesis_committee<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> Result<Option<GenesisCommittee>> {
+    let contract = &KeyManagerInstance::new(proxy, provider);
+    let Some(committee) = try_committee(contract, 0).await? else {
+        return Ok(None);
+    };
+    let created =
+        crate::provider::contract_creation_block(contract.provider(), *contract.address()).await?;
+    let bootstrap = committee.registeredBlockNumber == U256::from(created);
+    Ok(Some(GenesisCommittee {
+        committee,
+        bootstrap,
+    }))
+}
+
+/// An account that was manager of a KeyManager, i.e. could register committees
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ManagerTenure {
+    #[serde(with = "crate::address_hex")]
+    pub manager: Address,
+    /// block from which on it was manager: the contract's creation block for the initial
+    /// manager, that of its `ManagerChanged` event otherwise
+    pub since_block: u64,
+    /// the `setManager` transaction, `None` for the initial manager
+    pub tx_hash: Option<TxHash>,
+}
+
+/// Everyone who has been manager of the KeyManager at the contract address, oldest first, the
+/// last being the current manager.
+///
+/// Reconstructed from the `ManagerChanged` events since the contract's creation block (see
+/// [`crate::provider::contract_creation_block`], which needs a node serving historical state).
+/// `initialize` sets the first manager without an event, it is the `oldManager` of the first
+/// change, or the current `manager()` if there was none.
+pub async fn manager_history<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> Result<Vec<ManagerTenure>> {
+    let contract = &KeyManagerInstance::new(proxy, provider);
+    let provider = contract.provider();
+    let created = crate::provider::contract_creation_block(provider, *contract.address()).await?;
+    let head = provider.get_block_number().await?;
+    let filter = Filter::new()
+        .address(*contract.address())
+        .event(ManagerChanged::SIGNATURE);
+    let logs = crate::provider::query_logs_chunked(
+        provider,
+        &filter,
+        created,
+        head,
+        crate::provider::DEFAULT_LOG_QUERY_CHUNK,
+    )
+    .await?;
+    let changes = logs
+        .into_iter()
+        .map(|log| log.log_decode::<ManagerChanged>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let initial = match changes.first() {
+        Some(first) => first.data().oldManager,
+        None => contract.manager().call().await?,
+    };
+    let mut history = vec![ManagerTenure {
+        manager: initial,
+        since_block: created,
+        tx_hash: None,
+    }];
+    history.extend(changes.into_iter().map(|log| ManagerTenure {
+        manager: log.data().newManager,
+        since_block: log.block_number.unwrap_or(head),
+        tx_hash: log.transaction_hash,
+    }));
+    Ok(history)
+}
+
+/// A way in which two KeyManagers differ, see [`compare_deployments`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DeploymentDifference {
+    Manager {
+        #[serde(with = "crate::address_hex")]
+        a: Address,
+        #[serde(with = "crate::address_hex")]
+        b: Address,
+    },
+    /// different numbers of committees registered, those with ids registered in both are still
+    /// compared
+    CommitteeCount {
+        a: u64,
+        b: u64,
+    },
+    /// committee `id` is pruned in one of them only
+    Pruned {
+        id: u64,
+        in_a: bool,
+    },
+    EffectiveTimestamp {
+        id: u64,
+        a: u64,
+        b: u64,
+    },
+    /// the members of committee `id` differ from `index` on, the first member (or the end of the
+    /// shorter list) that isn't the same in both
+    MemberMismatch {
+        id: u64,
+        index: usize,
+    },
+}
+
+impl fmt::Display for DeploymentDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Manager { a, b } => write!(f, "different manager: {a} vs {b}"),
+            Self::CommitteeCount { a, b } => write!(f, "different committee count: {a} vs {b}"),
+            Self::Pruned { id, in_a } => {
+                let side = if *in_a { "first" } else { "second" };
+                write!(f, "committee {id} is pruned in the {side} deployment only")
+            }
+            Self::EffectiveTimestamp { id, a, b } => {
+                write!(f, "effective timestamp mismatch at id {id}: {a} vs {b}")
+            }
+            Self::MemberMismatch { id, index } => {
+                write!(f, "member mismatch at id {id}, member {index}")
+            }
+        }
+    }
+}
+
+/// How two KeyManagers differ, empty if they are equivalent
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DeploymentComparison {
+    pub differences: Vec<DeploymentDifference>,
+}
+
+impl DeploymentComparison {
+    pub fn is_equivalent(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+/// Manager, committee count and stored committees of a KeyManager
+struct DeploymentState {
+    manager: Address,
+    count: u64,
+    committees: Vec<CommitteeSol>,
+}
+
+impl DeploymentState {
+    async fn read<P: Provider>(contract: &KeyManagerInstance<P>) -> ContractResult<Self> {
+        Ok(Self {
+            manager: contract.manager().call().await?,
+            count: contract.nextCommitteeId().call().await?,
+            committees: stored_committees(contract).await?,
+        })
+    }
+
+    fn committee(&self, id: u64) -> Option<&CommitteeSol> {
+        self.committees.iter().find(|c| c.id == id)
+    }
+
+    fn compare(&self, other: &Self) -> DeploymentComparison {
+        let mut differences = Vec::new();
+        if self.manager != other.manager {
+            differences.push(DeploymentDifference::Manager {
+                a: self.manager,
+                b: other.manager,
+            });
+        }
+        if self.count != other.count {
+            differences.push(DeploymentDifference::CommitteeCount {
+                a: self.count,
+                b: other.count,
+            });
+        }
+        for id in 0..self.count.min(other.count) {
+            let (a, b) = match (self.committee(id), other.committee(id)) {
+                (Some(a), Some(b)) => (a, b),
+                (None, None) => continue,
+                (a, _) => {
+                    differences.push(DeploymentDifference::Pruned {
+                        id,
+                        in_a: a.is_none(),
+                    });
+                    continue;
+                }
+            };
+            if a.effectiveTimestamp != b.effectiveTimestamp {
+                differences.push(DeploymentDifference::EffectiveTimestamp {
+                    id,
+                    a: a.effectiveTimestamp,
+                    b: b.effectiveTimestamp,
+                });
+            }
+            if a.members != b.members {
+                let index = a
+                    .members
+                    .iter()
+                    .zip(&b.members)
+                    .position(|(a, b)| a != b)
+                    .unwrap_or(a.members.len().min(b.members.len()));
+                differences.push(DeploymentDifference::MemberMismatch { id, index });
+            }
+        }
+        DeploymentComparison { differences }
+    }
+}
+
+/// Compare the KeyManager at `addr_a` with the one at `addr_b`, possibly on another chain, e.g.
+/// to check that a mainnet deployment is configured like the testnet one it was promoted from.
+///
+/// Compares the managers, the committee counts and, for every id registered in both, the
+/// effective timestamp and members of the committee. Where and when committees were registered
+/// (`registeredBlockNumber`) is chain specific and ignored. Effective timestamps are compared,
+/// drop [`DeploymentDifference::EffectiveTimestamp`] if the schedules are expected to differ.
+pub async fn compare_deployments<A: Provider, B: Provider>(
+    provider_a: &A,
+    addr_a: Address,
+    provider_b: &B,
+    addr_b: Address,
+) -> Result<DeploymentComparison> {
+    let a = KeyManagerInstance::new(addr_a, provider_a);
+    let b = KeyManagerInstance::new(addr_b, provider_b);
+    let (a, b) =
+        futures::future::try_join(DeploymentState::read(&a), DeploymentState::read(&b)).await?;
+    Ok(a.compare(&b))
+}
+
+/// How long the current committee has been active: the latest block timestamp minus the
+/// committee's effective timestamp, both read at the same block.
+/// A large value with no newer committee registered suggests that rotation stalled.
+///
+/// Errors with `NoCommitteeScheduled` if no committee is effective yet.
+pub async fn committee_staleness<P: Provider>(provider: &P, proxy: Address) -> Result<Duration> {
+    let contract = &KeyManagerInstance::new(proxy, provider);
+    let block = contract
+        .provider()
+        .get_block(BlockId::latest())
+        .await?
+        .ok_or_else(|| RpcError::<TransportErrorKind>::NullResp)?;
+    let at = BlockId::number(block.header.number);
+
+    let id = contract.currentCommitteeId().block(at).call().await?;
+    let committee = contract.getCommitteeById(id).block(at).call().await?;
+    let active_for = block
+        .header
+        .timestamp
+        .saturating_sub(committee.effectiveTimestamp);
+    Ok(Duration::from_secs(active_for))
+}
+
+/// The committee in effect at a block, together with that block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitteeAtBlock {
+    /// `None` if no committee is effective at `timestamp` yet
+    pub committee: Option<CommitteeSol>,
+    pub block_number: u64,
+    pub timestamp: u64,
+}
+
+/// The committee in effect at the latest block of the KeyManager at `proxy`, along with that
+/// block's number and timestamp.
+///
+/// Everything is read at the same block, so the committee is the one effective at exactly the
+/// returned timestamp, even if a block with a new committee's effective timestamp lands in
+/// between. The current committee id and block are read in a single `eth_call` to Multicall3,
+/// then the committee at that block; where Multicall3 isn't deployed the block header is
+/// fetched first instead, like [`committee_staleness`] does.
+pub async fn current_committee_and_time<P: Provider>(
+    provider: &P,
+    proxy: Address,
+) -> Result<CommitteeAtBlock> {
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let batch = provider
+        .multicall()
+        .get_block_number()
+        .get_current_block_timestamp()
+        .add_call(contract.currentCommitteeId().into_call(true));
+    let (block_number, timestamp, id) = match batch.aggregate3().await {
+        Ok((number, timestamp, id)) => {
+            let id = match id {
+                Ok(id) => Some(id),
+                Err(failure) => match KeyManagerError::decode(&failure.return_data) {
+                    KeyManagerError::NoCommitteeScheduled => None,
+                    err => return Err(err.into()),
+                },
+            };
+            let number = number.map_err(anyhow::Error::from)?;
+            let timestamp = timestamp.map_err(anyhow::Error::from)?;
+            (number.saturating_to(), timestamp.saturating_to(), id)
+        }
+        Err(err) => {
+            if !provider.get_code_at(MULTICALL3_ADDRESS).await?.is_empty() {
+                return Err(err.into());
+            }
+            tracing::debug!("no Multicall3 deployed, reading the latest block first");
+            let block = provider
+                .get_block(BlockId::latest())
+                .await?
+                .ok_or_else(|| RpcError::<TransportErrorKind>::NullResp)?;
+            let at = BlockId::number(block.header.number);
+            let id = match contract.currentCommitteeId().block(at).call().await {
+                Ok(id) => Some(id),
+                Err(err) if err.as_decoded_error::<NoCommitteeScheduled>().is_some() => None,
+                Err(err) => return Err(err.into()),
+            };
+            (block.header.number, block.header.timestamp, id)
+        }
+    };
+    let committee = match id {
+        Some(id) => try_committee_at(&contract, id, BlockId::number(block_number)).await?,
+        None => None,
+    };
+    Ok(CommitteeAtBlock {
+        committee,
+        block_number,
+        timestamp,
+    })
+}
+
+/// A committee wasn't effective in time, i.e. the latest block's timestamp stayed before its
+/// effective timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitteeWaitTimeout {
+    pub id: u64,
+    pub effective_timestamp: u64,
+    /// timestamp of the latest block at the deadline
+    pub chain_timestamp: u64,
+}
+
+impl fmt::Display for CommitteeWaitTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "timed out waiting for committee {} to be effective at {}, chain is at {}",
+            self.id, self.effective_timestamp, self.chain_timestamp
+        )
+    }
+}
+
+impl std::error::Error for CommitteeWaitTimeout {}
+
+/// Poll until the latest block's timestamp reaches the effective timestamp of committee `id` of
+/// the KeyManager at `proxy`, at the provider's poll interval, returning the committee.
+/// On timeout the error is a [`CommitteeWaitTimeout`].
+///
+/// With `fast_forward` on an Anvil node (as told by its client version), a block with the
+/// effective timestamp is mined right away rather than waiting for the chain to get there; on
+/// other nodes `fast_forward` has no effect. Note that chains only mining on demand don't advance
+/// their timestamp by themselves.
+pub async fn wait_for_committee_effective<P: Provider>(
+    provider: &P,
+    proxy: Address,
+    id: u64,
+    timeout: Duration,
+    fast_forward: bool,
+) -> Result<CommitteeSol> {
+    let deadline = crate::time::Instant::now() + timeout;
+    let committee = KeyManagerInstance::new(proxy, provider)
+        .getCommitteeById(id)
+        .call()
+        .await?;
+    let target = committee.effectiveTimestamp;
+    if fast_forward
+        && provider.get_client_version().await?.starts_with("anvil")
+        && latest_timestamp(provider).await? < target
+    {
+        provider
+            .raw_request::<_, ()>("evm_setNextBlockTimestamp".into(), (target,))
+            .await?;
+        provider
+            .raw_request::<_, String>("evm_mine".into(), ())
+            .await?;
+    }
+    loop {
+        let current = latest_timestamp(provider).await?;
+        if current >= target {
+            return Ok(committee);
+        }
+        if crate::time::Instant::now() >= deadline {
+            return Err(CommitteeWaitTimeout {
+                id,
+                effective_timestamp: target,
+                chain_timestamp: current,
+            }
+            .into());
+        }
+        crate::time::sleep(provider.client().poll_interval()).await;
+    }
+}
+
+/// Why the mined transaction `tx_hash` reverted, e.g. a failed committee operation seen in logs.
+///
+/// The transaction is replayed with `eth_call` from its sender, with its gas limit but without
+/// fees, on the state before its block, and the revert data decoded like
+/// [`KeyManagerError::decode`], as [`KeyManagerError::Raw`] if unknown. Transactions earlier in the
+/// same block aren't replayed: a transaction that only failed because of one of them doesn't
+/// revert when replayed, which is an error, as are a transaction that didn't revert and one
+/// failing without revert data, e.g. out of gas. Needs a node serving the state of the block
+/// before.
+pub async fn decode_revert<P: Provider>(provider: &P, tx_hash: TxHash) -> Result<KeyManagerError> {
+    let receipt = provider
+        .get_transaction_receipt(tx_hash)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("transaction {tx_hash} not found or not mined"))?;
+    if receipt.status() {
+        return Err(anyhow::anyhow!("transaction {tx_hash} did not revert").into());
+    }
+    let block = receipt
+        .block_number
+        .ok_or_else(|| anyhow::anyhow!("transaction {tx_hash} is not mined"))?;
+    let tx = provider
+        .get_transaction_by_hash(tx_hash)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("transaction {tx_hash} not found"))?;
+
+    let mut request = tx.into_request();
+    // the base fee before the block may be above what the transaction offered
+    request.nonce = None;
+    request.gas_price = None;
+    request.max_fee_per_gas = None;
+    request.max_priority_fee_per_gas = None;
+    let replayed = provider
+        .call(request)
+        .block(BlockId::number(block.saturating_sub(1)))
+        .await;
+    match replayed {
+        Ok(_) => Err(anyhow::anyhow!(
+            "replaying {tx_hash} didn't revert, it may have failed due to a transaction before it \
+             in block {block}"
+        )
+        .into()),
+        Err(err) => match err.as_error_resp().and_then(|resp| resp.as_revert_data()) {
+            Some(data) => Ok(KeyManagerError::decode(&data)),
+            None => Err(anyhow::Error::new(err)
+                .context(format!("replaying {tx_hash} failed"))
+                .into()),
+        },
+    }
+}
+
+/// timestamp of the latest block
+async fn latest_timestamp<P: Provider>(provider: &P) -> ContractResult<u64> {
+    let block = provider
+        .get_block(BlockId::latest())
+        .await?
+        .ok_or_else(|| RpcError::<TransportErrorKind>::NullResp)?;
+    Ok(block.header.timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DeploymentDifference, DeploymentState, HistoricalStateUnavailable, RotationStats,
+        StateOverrideUnsupported, all_committees, batch_poster_addresses, committee_by_id,
+        committee_by_id_at_block, committee_by_id_with_overrides, committee_by_timestamp,
+        committee_count, committee_members_table, committee_snapshot, committee_staleness,
+        committees_batch, committees_newest_first, compare_deployments, current_committee,
+        current_committee_and_time, current_peer_addresses, decode_revert, find_member,
+        genesis_committee, latest_finalized_committee, manager_history, missing_state_message,
+        unsupported_override_message, wait_for_committee_effective,
+    };
+    use crate::{
+        CommitteeMemberSol, CommitteeSol, KeyManager,
+        error::{KeyManagerError, TimeboostContractError},
+    };
+    use alloy::{
+        contract::Error as ContractError,
+        eips::BlockId,
+        network::EthereumWallet,
+        primitives::{Address, B256, TxHash, U256, keccak256},
+        providers::{Provider, ProviderBuilder, WalletProvider},
+        rpc::{
+            json_rpc::ErrorPayload,
+            types::state::{AccountOverride, StateOverride},
+        },
+        transports::RpcError,
+    };
+    use futures::{StreamExt, TryStreamExt};
+    use std::{pin::pin, time::Duration};
+
+    #[test]
+    fn test_rotation_stats() {
+        assert_eq!(RotationStats::from_timestamps(&[]), None);
+        assert_eq!(RotationStats::from_timestamps(&[100]), None);
+
+        let stats = RotationStats::from_timestamps(&[100, 160, 200, 300]).unwrap();
+        assert_eq!(stats.rotations, 3);
+        assert_eq!(stats.mean, Duration::from_secs(200) / 3);
+        assert_eq!(stats.min, Duration::from_secs(40));
+        assert_eq!(stats.max, Duration::from_secs(100));
+    }
+
+    #[test]
+    fn test_deployment_state_compare() {
+        let committee = |id, ts, members: Vec<CommitteeMemberSol>| CommitteeSol {
+            id,
+            effectiveTimestamp: ts,
+            registeredBlockNumber: U256::from(id + 1),
+            members,
+        };
+        let members: Vec<_> = (0..3).map(|_| CommitteeMemberSol::random()).collect();
+        let a = DeploymentState {
+            manager: Address::with_last_byte(1),
+            count: 4,
+            committees: vec![
+                committee(1, 100, members.clone()),
+                committee(2, 200, members.clone()),
+                committee(3, 300, members.clone()),
+            ],
+        };
+        let mut same = a.committees.clone();
+        // registered elsewhere
+        same[0].registeredBlockNumber = U256::from(77);
+        let b = DeploymentState {
+            committees: same,
+            ..a
+        };
+        assert!(a.compare(&b).is_equivalent());
+
+        let mut swapped = members.clone();
+        swapped.swap(1, 2);
+        let b = DeploymentState {
+            manager: Address::with_last_byte(2),
+            count: 5,
+            committees: vec![
+                committee(2, 250, members[..2].to_vec()),
+                committee(3, 300, swapped),
+                committee(4, 400, members.clone()),
+            ],
+        };
+        let differences = a.compare(&b).differences;
+        assert_eq!(
+            differences,
+            [
+                DeploymentDifference::Manager {
+                    a: Address::with_last_byte(1),
+                    b: Address::with_last_byte(2),
+                },
+                DeploymentDifference::CommitteeCount { a: 4, b: 5 },
+                DeploymentDifference::Pruned { id: 1, in_a: false },
+                DeploymentDifference::EffectiveTimestamp {
+                    id: 2,
+                    a: 200,
+                    b: 250
+                },
+                DeploymentDifference::MemberMismatch { id: 2, index: 2 },
+                DeploymentDifference::MemberMismatch { id: 3, index: 1 },
+            ]
+        );
+        assert_eq!(
+            differences[1].to_string(),
+            "different committee count: 4 vs 5"
+        );
+        assert_eq!(
+            differences[2].to_string(),
+            "committee 1 is pruned in the second deployment only"
+        );
+        assert_eq!(
+            differences[5].to_string(),
+            "member mismatch at id 3, member 1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compare_deployments() {
+        let (provider_a, addr_a) = crate::init_test_chain().await.unwrap();
+        let (provider_b, addr_b) = crate::init_test_chain().await.unwrap();
+        let members: Vec<_> = (0..2).map(|_| CommitteeMemberSol::random()).collect();
+        let register = async |provider, addr, members| {
+            KeyManager::new(addr, provider)
+                .setNextCommittee(100, members)
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        };
+        let compare = async || {
+            compare_deployments(&provider_a, addr_a, &provider_b, addr_b)
+                .await
+                .unwrap()
+        };
+        assert!(compare().await.is_equivalent());
+
+        register(&provider_a, addr_a, members.clone()).await;
+        assert_eq!(
+            compare().await.differences,
+            [DeploymentDifference::CommitteeCount { a: 1, b: 0 }]
+        );
+        // mined at another block on the other chain
+        provider_b
+            .raw_request::<_, ()>("anvil_mine".into(), (3,))
+            .await
+            .unwrap();
+        register(&provider_b, addr_b, members.clone()).await;
+        assert!(compare().await.is_equivalent());
+
+        register(&provider_a, addr_a, members.clone()).await;
+        register(&provider_b, addr_b, members[..1].to_vec()).await;
+        assert_eq!(
+            compare().await.differences,
+            [DeploymentDifference::MemberMismatch { id: 1, index: 1 }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_revert() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let stranger = crate::test_accounts().unwrap()[1].clone();
+        let caller = stranger.address();
+        let other = ProviderBuilder::new()
+            .wallet(EthereumWallet::from(stranger))
+            .connect_provider(provider.clone());
+        // with a gas limit, so that the failing transaction is sent rather than estimated
+        let failed = KeyManager::new(addr, &other)
+            .setNextCommittee(100, vec![CommitteeMemberSol::random()])
+            .gas(1_000_000)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        assert!(!failed.status());
+        assert_eq!(
+            decode_revert(&provider, failed.transaction_hash)
+                .await
+                .unwrap(),
+            KeyManagerError::Unauthorized { caller }
+        );
+
+        let contract = KeyManager::new(addr, &provider);
+        let ok = contract
+            .setNextCommittee(100, vec![CommitteeMemberSol::random()])
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let err = decode_revert(&provider, ok.transaction_hash)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("transaction {} did not revert", ok.transaction_hash)
+        );
+        assert!(decode_revert(&provider, TxHash::ZERO).await.is_err());
+
+        // the same timestamp again
+        let failed = contract
+            .setNextCommittee(100, vec![CommitteeMemberSol::random()])
+            .gas(1_000_000)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        assert_eq!(
+            decode_revert(&provider, failed.transaction_hash)
+                .await
+                .unwrap(),
+            KeyManagerError::InvalidTimestamp {
+                timestamp: 100,
+                last: 100
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_committee_by_id() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        assert_eq!(committee_by_id(&provider, addr, 0).await.unwrap(), None);
+
+        let member = CommitteeMemberSol::random();
+        let contract = KeyManager::new(addr, &provider);
+        contract
+            .setNextCommittee(100, vec![member.clone()])
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let committee = committee_by_id(&provider, addr, 0).await.unwrap().unwrap();
+        assert_eq!(committee.effective_timestamp, 100);
+        assert_eq!(committee.members[0].dkg_key[..], member.dkgKey[..]);
+
+        let mut invalid = CommitteeMemberSol::random();
+        invalid.networkAddress = "localhost".into();
+        contract
+            .setNextCommittee(200, vec![invalid])
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let err = committee_by_id(&provider, addr, 1).await.unwrap_err();
+        assert!(matches!(err, TimeboostContractError::Committee(_)));
+    }
+
+    #[tokio::test]
+    async fn test_committee_by_id_at_block() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let mut blocks = Vec::new();
+        for ts in [100, 200] {
+            let receipt = contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+            blocks.push(receipt.block_number.unwrap());
+        }
+        contract
+            .pruneUntil(0)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        assert_eq!(committee_by_id(&provider, addr, 0).await.unwrap(), None);
+
+        // committee 0 as of before it was pruned
+        let at = |block| committee_by_id_at_block(&provider, addr, 0, BlockId::number(block));
+        assert_eq!(
+            at(blocks[0]).await.unwrap().unwrap().effective_timestamp,
+            100
+        );
+        assert_eq!(at(blocks[0] - 1).await.unwrap(), None);
+        let second = committee_by_id_at_block(&provider, addr, 1, BlockId::number(blocks[1]));
+        assert_eq!(second.await.unwrap().unwrap().effective_timestamp, 200);
+        let before = committee_by_id_at_block(&provider, addr, 1, BlockId::number(blocks[0]));
+        assert_eq!(before.await.unwrap(), None);
+    }
+
+    #[test]
+    fn test_missing_state_message() {
+        let rpc_error = |message: &str| {
+            let payload: ErrorPayload =
+                serde_json::from_value(serde_json::json!({ "code": -32000, "message": message }))
+                    .unwrap();
+            ContractError::TransportError(RpcError::ErrorResp(payload))
+        };
+        let err = rpc_error("missing trie node 1a2b (path ) state 0x1a2b is not available");
+        assert_eq!(
+            missing_state_message(&err).as_deref(),
+            Some("missing trie node 1a2b (path ) state 0x1a2b is not available")
+        );
+        assert!(missing_state_message(&rpc_error("historical state unavailable")).is_some());
+        assert!(missing_state_message(&rpc_error("execution reverted")).is_none());
+
+        let err = HistoricalStateUnavailable {
+            block: BlockId::number(7),
+            message: "header not found".into(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "no state at block 0x7 on this node (is it an archive node?): header not found"
+        );
+    }
+
+    #[test]
+    fn test_unsupported_override_message() {
+        let rpc_error = |message: &str, data: Option<&str>| {
+            let payload: ErrorPayload = serde_json::from_value(
+                serde_json::json!({ "code": -32602, "message": message, "data": data }),
+            )
+            .unwrap();
+            ContractError::TransportError(RpcError::ErrorResp(payload))
+        };
+        let err = rpc_error("too many arguments, want at most 2", None);
+        assert_eq!(
+            unsupported_override_message(&err).as_deref(),
+            Some("too many arguments, want at most 2")
+        );
+        assert!(
+            unsupported_override_message(&rpc_error("state override is not supported", None))
+                .is_some()
+        );
+        assert!(unsupported_override_message(&rpc_error("header not found", None)).is_none());
+        // a revert whose reason happens to match is still a revert
+        assert!(
+            unsupported_override_message(&rpc_error(
+                "execution reverted: unsupported",
+                Some("0x08c379a0")
+            ))
+            .is_none()
+        );
+
+        let err = StateOverrideUnsupported {
+            message: "too many arguments, want at most 2".into(),
+        };
+        assert_eq!(
+            err.to_string(),
+            "this node doesn't support eth_call state overrides: too many arguments, want at most 2"
+        );
+    }
+
+    /// storage slots of `KeyManager.committees` and of the packed `nextCommitteeId`, after the OZ
+    /// upgradeable base contracts' slots
+    const COMMITTEES_SLOT: u64 = 202;
+    const NEXT_ID_SLOT: u64 = 204;
+
+    #[tokio::test]
+    async fn test_committee_by_id_with_overrides() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        contract
+            .setNextCommittee(100, vec![CommitteeMemberSol::random()])
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let read = async |diff: Vec<(B256, B256)>| {
+            let overrides = StateOverride::from_iter([(
+                addr,
+                AccountOverride::default().with_state_diff(diff),
+            )]);
+            committee_by_id_with_overrides(&provider, addr, 0, overrides)
+                .await
+                .unwrap()
+        };
+        let real = read(vec![]).await.unwrap();
+        assert_eq!(real.effective_timestamp, 100);
+
+        // the first slot of `committees[0]` packs `id` and `effectiveTimestamp`
+        let mut key = [0u8; 64];
+        key[32..].copy_from_slice(&U256::from(COMMITTEES_SLOT).to_be_bytes::<32>());
+        let first = keccak256(key);
+        let what_if = read(vec![(first, B256::from(U256::from(999u64) << 64))])
+            .await
+            .unwrap();
+        assert_eq!(what_if.effective_timestamp, 999);
+        assert_eq!(what_if.members, real.members);
+
+        // as if nothing was registered yet
+        let next_id = B256::from(U256::from(NEXT_ID_SLOT));
+        assert!(read(vec![(next_id, B256::ZERO)]).await.is_none());
+
+        // the chain is untouched
+        assert_eq!(
+            contract
+                .getCommitteeById(0)
+                .call()
+                .await
+                .unwrap()
+                .effectiveTimestamp,
+            100
+        );
+    }
+
+    #[tokio::test]
+    async fn test_committees_batch() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        assert!(
+            committees_batch(&provider, addr, &[])
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let contract = KeyManager::new(addr, &provider);
+        for ts in [100, 200, 300] {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+        // anvil has no Multicall3, so this takes the fallback
+        let committees = committees_batch(&provider, addr, &[2, 0, 5, 2])
+            .await
+            .unwrap();
+        let timestamps = committees
+            .iter()
+            .map(|c| c.as_ref().map(|c| c.effectiveTimestamp))
+            .collect::<Vec<_>>();
+        assert_eq!(timestamps, [Some(300), Some(100), None, Some(300)]);
+    }
+
+    #[tokio::test]
+    async fn test_committee_count() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        assert_eq!(committee_count(&provider, addr).await.unwrap(), 0);
+
+        let contract = KeyManager::new(addr, &provider);
+        for ts in [100, 200] {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+        assert_eq!(committee_count(&provider, addr).await.unwrap(), 2);
+
+        // pruned committees still count
+        contract
+            .pruneUntil(0)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        assert_eq!(committee_count(&provider, addr).await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_committee_effective() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let now = provider
+            .get_block(BlockId::latest())
+            .await
+            .unwrap()
+            .unwrap()
+            .header
+            .timestamp;
+        for ts in [now, now + 3600] {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+
+        let timeout = Duration::from_millis(200);
+        let effective = wait_for_committee_effective(&provider, addr, 0, timeout, false).await;
+        assert_eq!(effective.unwrap().id, 0);
+
+        let err = wait_for_committee_effective(&provider, addr, 1, timeout, false)
+            .await
+            .unwrap_err();
+        let TimeboostContractError::CommitteeWaitTimeout(err) = err else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!((err.id, err.effective_timestamp), (1, now + 3600));
+        assert!(err.chain_timestamp < now + 3600);
+
+        let committee = wait_for_committee_effective(&provider, addr, 1, timeout, true)
+            .await
+            .unwrap();
+        assert_eq!(committee.id, 1);
+        assert_eq!(contract.currentCommitteeId().call().await.unwrap(), 1);
+
+        // unknown committees fail right away
+        assert!(
+            wait_for_committee_effective(&provider, addr, 2, timeout, true)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_committee_by_timestamp() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        assert!(
+            committee_by_timestamp(&provider, addr, 100)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let contract = KeyManager::new(addr, &provider);
+        for ts in [100, 200, 300, 400, 500] {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+        for (id, ts) in [(0, 100), (1, 200), (3, 400), (4, 500)] {
+            let (found, committee) = committee_by_timestamp(&provider, addr, ts)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                (found, committee.id, committee.effectiveTimestamp),
+                (id, id, ts)
+            );
+        }
+        for ts in [0, 150, 501] {
+            let found = committee_by_timestamp(&provider, addr, ts).await.unwrap();
+            assert!(found.is_none(), "{ts}");
+        }
+
+        contract
+            .pruneUntil(1)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let pruned = committee_by_timestamp(&provider, addr, 200).await.unwrap();
+        assert!(pruned.is_none());
+        let found = committee_by_timestamp(&provider, addr, 300).await.unwrap();
+        assert_eq!(found.unwrap().0, 2);
+    }
+
+    #[tokio::test]
+    async fn test_current_peer_addresses() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        assert!(
+            current_peer_addresses(&provider, addr, 1000)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let member = |network: &str, batch_poster: &str| CommitteeMemberSol {
+            networkAddress: network.into(),
+            batchPosterAddress: batch_poster.into(),
+            ..CommitteeMemberSol::random()
+        };
+        let members = vec![
+            member("10.0.0.1:9000", "10.0.0.1:9100"),
+            member("not an address", "10.0.0.2:9100"),
+            member("[::1]:9000", "10.0.0.2:9100"),
+            member("10.0.0.1:9000", "10.0.0.4"),
+            member("node.example.com:9000", "poster.example.com:9100"),
+        ];
+        KeyManager::new(addr, &provider)
+            .setNextCommittee(100, members)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+
+        let peers = current_peer_addresses(&provider, addr, 100).await.unwrap();
+        // the hostnames are skipped too
+        let expected: Vec<std::net::SocketAddr> = vec![
+            "10.0.0.1:9000".parse().unwrap(),
+            "[::1]:9000".parse().unwrap(),
+        ];
+        assert_eq!(peers, expected);
+        let posters = batch_poster_addresses(&provider, addr, 100).await.unwrap();
+        let expected: Vec<std::net::SocketAddr> = vec![
+            "10.0.0.1:9100".parse().unwrap(),
+            "10.0.0.2:9100".parse().unwrap(),
+        ];
+        assert_eq!(posters, expected);
+        // not in effect yet
+        assert!(
+            batch_poster_addresses(&provider, addr, 99)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_member() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let me = Address::with_last_byte(7);
+        assert!(
+            find_member(&provider, addr, me, 1000)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let members = |addrs: &[u8]| -> Vec<_> {
+            addrs
+                .iter()
+                .map(|&a| CommitteeMemberSol {
+                    sigKeyAddress: Address::with_last_byte(a),
+                    ..CommitteeMemberSol::random()
+                })
+                .collect()
+        };
+        for (ts, addrs) in [(100, &[1, 7][..]), (200, &[1, 2, 3])] {
+            contract
+                .setNextCommittee(ts, members(addrs))
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+
+        let (index, member) = find_member(&provider, addr, me, 150)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(member.sigKeyAddress, me);
+        // rotated out of the next committee
+        assert!(
+            find_member(&provider, addr, me, 200)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let (index, _) = find_member(&provider, addr, Address::with_last_byte(3), 200)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(index, 2);
+        assert!(
+            find_member(&provider, addr, me, 99)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_all_committees() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let none = all_committees(&provider, addr)
+            .try_collect::<Vec<_>>()
+            .await;
+        assert!(none.unwrap().is_empty());
+
+        let register = async |ts| {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        };
+        for ts in [100, 200, 300] {
+            register(ts).await;
+        }
+
+        let mut committees = pin!(all_committees(&provider, addr));
+        assert_eq!(committees.next().await.unwrap().unwrap().id, 0);
+        // registered after the stream started
+        register(400).await;
+        let rest = committees.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(rest.iter().map(|c| c.id).collect::<Vec<_>>(), [1, 2, 3]);
+        assert_eq!(rest[2].effectiveTimestamp, 400);
+    }
+
+    #[tokio::test]
+    async fn test_current_committee() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        assert!(
+            current_committee(&provider, addr, u64::MAX)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        for ts in [100, 200, 300] {
+            let members = vec![CommitteeMemberSol::random()];
+            contract
+                .setNextCommittee(ts, members)
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+
+        let id_at = async |now| {
+            current_committee(&provider, addr, now)
+                .await
+                .unwrap()
+                .map(|c| c.id)
+        };
+        assert_eq!(id_at(0).await, None);
+        assert_eq!(id_at(99).await, None);
+        assert_eq!(id_at(100).await, Some(0));
+        assert_eq!(id_at(199).await, Some(0));
+        assert_eq!(id_at(200).await, Some(1));
+        assert_eq!(id_at(299).await, Some(1));
+        assert_eq!(id_at(300).await, Some(2));
+        assert_eq!(id_at(u64::MAX).await, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_committees_newest_first() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        assert!(
+            committees_newest_first(&provider, addr, 3)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        for i in 0..5 {
+            let members = vec![CommitteeMemberSol::random()];
+            contract
+                .setNextCommittee(100 + i, members)
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+
+        let recent = committees_newest_first(&provider, addr, 3).await.unwrap();
+        assert_eq!(recent.iter().map(|c| c.id).collect::<Vec<_>>(), [4, 3, 2]);
+
+        let all = committees_newest_first(&provider, addr, 10).await.unwrap();
+        assert_eq!(all.len(), 5);
+        assert_eq!(all.last().unwrap().id, 0);
+    }
+
+    #[tokio::test]
+    async fn test_committee_members_table() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+
+        let mut committees = Vec::new();
+        for (i, size) in [2, 3].into_iter().enumerate() {
+            let members = (0..size)
+                .map(|_| CommitteeMemberSol::random())
+                .collect::<Vec<_>>();
+            contract
+                .setNextCommittee(100 + i as u64, members.clone())
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+            committees.push(members);
+        }
+
+        let rows = committee_members_table(&provider, addr).await.unwrap();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows.iter()
+                .map(|r| (r.committee_id, r.member_index))
+                .collect::<Vec<_>>(),
+            [(0, 0), (0, 1), (1, 0), (1, 1), (1, 2)]
+        );
+        assert_eq!(rows[3].effective_timestamp, 101);
+        assert_eq!(rows[3].sig_key, committees[1][1].sigKey);
+        assert_eq!(rows[3].network_address, committees[1][1].networkAddress);
+    }
+
+    #[tokio::test]
+    async fn test_committee_snapshot() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        assert_eq!(
+            committee_snapshot(&provider, addr).await.unwrap(),
+            (0, None)
+        );
+
+        for i in 0..2 {
+            contract
+                .setNextCommittee(100 + i, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+        let (count, latest) = committee_snapshot(&provider, addr).await.unwrap();
+        assert_eq!(count, 2);
+        let latest = latest.unwrap();
+        assert_eq!(latest.id, 1);
+        assert_eq!(latest.effectiveTimestamp, 101);
+    }
+
+    #[tokio::test]
+    async fn test_latest_finalized_committee() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        assert!(
+            latest_finalized_committee(&provider, addr)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        contract
+            .setNextCommittee(100, vec![CommitteeMemberSol::random()])
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        // anvil finalizes blocks instantly
+        let committee = latest_finalized_committee(&provider, addr)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(committee.id, 0);
+    }
+
+    #[tokio::test]
+    async fn test_genesis_committee() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        assert!(genesis_committee(&provider, addr).await.unwrap().is_none());
+
+        // registered after the deployment
+        for ts in [100, 200] {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+        let genesis = genesis_committee(&provider, addr).await.unwrap().unwrap();
+        assert_eq!(genesis.committee.id, 0);
+        assert!(!genesis.bootstrap);
+    }
+
+    #[tokio::test]
+    async fn test_manager_history() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let initial = provider.default_signer_address();
+        let history = manager_history(&provider, addr).await.unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].manager, history[0].tx_hash), (initial, None));
+
+        let mut receipts = Vec::new();
+        for manager in [Address::with_last_byte(1), Address::with_last_byte(2)] {
+            let receipt = contract
+                .setManager(manager)
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+            receipts.push(receipt);
+        }
+        let history = manager_history(&provider, addr).await.unwrap();
+        let managers = history.iter().map(|t| t.manager).collect::<Vec<_>>();
+        assert_eq!(
+            managers,
+            [
+                initial,
+                Address::with_last_byte(1),
+                Address::with_last_byte(2)
+            ]
+        );
+        assert!(history[0].since_block <= history[1].since_block);
+        assert_eq!(history[2].since_block, receipts[1].block_number.unwrap());
+        assert_eq!(history[1].tx_hash, Some(receipts[0].transaction_hash));
+    }
+
+    #[tokio::test]
+    async fn test_committee_staleness() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        assert!(committee_staleness(&provider, addr).await.is_err());
+
+        let now = provider
+            .get_block(BlockId::latest())
+            .await
+            .unwrap()
+            .unwrap()
+            .header
+            .timestamp;
+        contract
+            .setNextCommittee(now - 600, vec![CommitteeMemberSol::random()])
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+
+        let staleness = committee_staleness(&provider, addr).await.unwrap();
+        assert!(staleness.as_secs() >= 600);
+    }
+
+    #[tokio::test]
+    async fn test_current_committee_and_time() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let head = provider
+            .get_block(BlockId::latest())
+            .await
+            .unwrap()
+            .unwrap();
+        let read = current_committee_and_time(&provider, addr).await.unwrap();
+        assert!(read.committee.is_none());
+        assert_eq!(read.block_number, head.header.number);
+        assert_eq!(read.timestamp, head.header.timestamp);
+
+        let now = head.header.timestamp;
+        for ts in [now, now + 1000] {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+        let read = current_committee_and_time(&provider, addr).await.unwrap();
+        let committee = read.committee.unwrap();
+        assert_eq!(committee.id, 0);
+        assert!(committee.effectiveTimestamp <= read.timestamp);
+        assert_eq!(
+            read.block_number,
+            provider.get_block_number().await.unwrap()
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Committee updates proposed to a Safe multisig
+//!
+//! The Safe must be the KeyManager's manager. A proposal is a `setNextCommittee` call wrapped in
+//! a Safe transaction, signed by one owner (or delegate) and submitted to the Safe transaction
+//! service, where the remaining owners confirm it until the Safe's threshold is reached and
+//! someone executes it.
+//!
+//! Assumptions about the Safe:
+//! - version 1.3 or later, whose EIP-712 domain is `(chainId, verifyingContract)`; the locally
+//!   computed hash is checked against the Safe's `getTransactionHash` before submitting
+//! - the proposal uses the Safe's current on-chain nonce, so it replaces rather than follows any
+//!   transaction still queued in the service
+//!
+//! Assumptions about the transaction service (`safe-transaction-service`, API v1):
+//! - proposals are `POST {service}/api/v1/safes/{checksummed safe}/multisig-transactions/` with a
+//!   JSON [`SafeProposal`] body; uint256 fields are decimal strings
+//! - the `signature` is the proposer's 65 byte ECDSA signature of the Safe transaction hash
+//!   (`v` of 27 or 28), and the `sender` has to be an owner or delegate of the Safe
+//! - any 2xx response means the proposal was accepted
+
+use alloy::{
+    primitives::{Address, B256, Bytes, U256},
+    providers::Provider,
+    signers::Signer,
+    sol,
+    sol_types::{SolCall, SolStruct, eip712_domain},
+    transports::http::reqwest::{self, Url, header::CONTENT_TYPE},
+};
+use anyhow::{Result, bail, ensure};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{CommitteeMemberSol, KeyManager};
+
+sol! {
+    /// The subset of the Safe contract used for proposals
+    #[sol(rpc)]
+    #[allow(clippy::too_many_arguments)]
+    interface ISafe {
+        function nonce() external view returns (uint256);
+        function getTransactionHash(address to, uint256 value, bytes calldata data, uint8 operation, uint256 safeTxGas, uint256 baseGas, uint256 gasPrice, address gasToken, address refundReceiver, uint256 _nonce) external view returns (bytes32);
+    }
+
+    /// A Safe transaction, as hashed per EIP-712 by the Safe
+    #[derive(Debug, PartialEq, Eq)]
+    struct SafeTx {
+        address to;
+        uint256 value;
+        bytes data;
+        uint8 operation;
+        uint256 safeTxGas;
+        uint256 baseGas;
+        uint256 gasPrice;
+        address gasToken;
+        address refundReceiver;
+        uint256 nonce;
+    }
+}
+
+impl SafeTx {
+    /// A plain call of `setNextCommittee(timestamp, members)` on `key_manager`, no refunds
+    pub fn committee_update(
+        key_manager: Address,
+        timestamp: u64,
+        members: Vec<CommitteeMemberSol>,
+        nonce: U256,
+    ) -> Self {
+        let call = KeyManager::setNextCommitteeCall {
+            effectiveTimestamp: timestamp,
+            members,
+        };
+        Self {
+            to: key_manager,
+            value: U256::ZERO,
+            data: call.abi_encode().into(),
+            operation: 0,
+            safeTxGas: U256::ZERO,
+            baseGas: U256::ZERO,
+            gasPrice: U256::ZERO,
+            gasToken: Address::ZERO,
+            refundReceiver: Address::ZERO,
+            nonce,
+        }
+    }
+
+    /// the Safe transaction hash owners sign, for the Safe at `safe` on chain `chain_id`
+    pub fn safe_tx_hash(&self, chain_id: u64, safe: Address) -> B256 {
+        self.eip712_signing_hash(&eip712_domain! {
+            chain_id: chain_id,
+            verifying_contract: safe,
+        })
+    }
+}
+
+/// Body of a proposal to the Safe transaction service
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeProposal {
+    #[serde(with = "crate::address_hex")]
+    pub to: Address,
+    pub value: String,
+    pub data: Bytes,
+    pub operation: u8,
+    pub safe_tx_gas: String,
+    pub base_gas: String,
+    pub gas_price: String,
+    #[serde(with = "crate::address_hex")]
+    pub gas_token: Address,
+    #[serde(with = "crate::address_hex")]
+    pub refund_receiver: Address,
+    pub nonce: String,
+    pub contract_transaction_hash: B256,
+    #[serde(with = "crate::address_hex")]
+    pub sender: Address,
+    pub signature: Bytes,
+    pub origin: Option<String>,
+}
+
+/// Sign `tx` as `signer` and submit it to the transaction `service` for the Safe at `safe` on
+/// chain `chain_id`. Returns the Safe transaction hash the other owners confirm.
+pub async fn propose_safe_transaction<S: Signer + Sync>(
+    service: &Url,
+    safe: Address,
+    chain_id: u64,
+    tx: &SafeTx,
+    signer: &S,
+) -> Result<B256> {
+    let hash = tx.safe_tx_hash(chain_id, safe);
+    let signature = signer.sign_hash(&hash).await?;
+    let proposal = SafeProposal {
+        to: tx.to,
+        value: tx.value.to_string(),
+        data: tx.data.clone(),
+        operation: tx.operation,
+        safe_tx_gas: tx.safeTxGas.to_string(),
+        base_gas: tx.baseGas.to_string(),
+        gas_price: tx.gasPrice.to_string(),
+        gas_token: tx.gasToken,
+        refund_receiver: tx.refundReceiver,
+        nonce: tx.nonce.to_string(),
+        contract_transaction_hash: hash,
+        sender: signer.address(),
+        signature: signature.as_bytes().into(),
+        origin: Some("timeboost-contract".to_string()),
+    };
+
+    let url = format!(
+        "{}/api/v1/safes/{safe}/multisig-transactions/",
+        service.as_str().trim_end_matches('/')
+    );
+    let resp = reqwest::Client::new()
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&proposal)?)
+        .send()
+        .await?;
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        bail!("safe transaction service rejected the proposal ({status}): {body}");
+    }
+    info!(%safe, %hash, nonce = %tx.nonce, "proposed safe transaction");
+    Ok(hash)
+}
+
+/// Propose `setNextCommittee(timestamp, members)` on `key_manager` to the Safe at `safe`, signed
+/// by `signer`, at the Safe's current nonce. Returns the Safe transaction hash.
+pub async fn propose_committee_update<P: Provider, S: Signer + Sync>(
+    provider: &P,
+    service: &Url,
+    safe: Address,
+    key_manager: Address,
+    timestamp: u64,
+    members: Vec<CommitteeMemberSol>,
+    signer: &S,
+) -> Result<B256> {
+    let chain_id = provider.get_chain_id().await?;
+    let contract = ISafe::new(safe, provider);
+    let nonce = contract.nonce().call().await?;
+    let tx = SafeTx::committee_update(key_manager, timestamp, members, nonce);
+
+    let expected = contract
+        .getTransactionHash(
+            tx.to,
+            tx.value,
+            tx.data.clone(),
+            tx.operation,
+            tx.safeTxGas,
+            tx.baseGas,
+            tx.gasPrice,
+            tx.gasToken,
+            tx.refundReceiver,
+            tx.nonce,
+        )
+        .call()
+        .await?;
+    ensure!(
+        expected == tx.safe_tx_hash(chain_id, safe),
+        "safe transaction hash mismatch, unsupported Safe version at {safe}"
+    );
+
+    propose_safe_transaction(service, safe, chain_id, &tx, signer).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SafeProposal, SafeTx, propose_safe_transaction};
+    use crate::{CommitteeMemberSol, KeyManager, test_util::http};
+    use alloy::{
+        primitives::{Address, Signature, U256},
+        signers::local::PrivateKeySigner,
+        sol_types::SolCall,
+    };
+
+    #[test]
+    fn test_committee_update_tx() {
+        let members = vec![CommitteeMemberSol::random()];
+        let km = Address::with_last_byte(1);
+        let tx = SafeTx::committee_update(km, 100, members.clone(), U256::from(3));
+        let call = KeyManager::setNextCommitteeCall::abi_decode(&tx.data).unwrap();
+        assert_eq!((call.effectiveTimestamp, call.members), (100, members));
+
+        // the hash is bound to the nonce, chain and safe
+        let safe = Address::with_last_byte(2);
+        let hash = tx.safe_tx_hash(1, safe);
+        let mut next = tx.clone();
+        next.nonce = U256::from(4);
+        assert_ne!(hash, next.safe_tx_hash(1, safe));
+        assert_ne!(hash, tx.safe_tx_hash(2, safe));
+        assert_ne!(hash, tx.safe_tx_hash(1, km));
+    }
+
+    #[tokio::test]
+    async fn test_propose_safe_transaction() {
+        let (listener, service) = http::listen("/").await;
+        let server = tokio::spawn(http::serve(listener, 1, |_| (201, String::new())));
+
+        let signer = PrivateKeySigner::random();
+        let safe = Address::with_last_byte(2);
+        let tx = SafeTx::committee_update(
+            Address::with_last_byte(1),
+            100,
+            vec![CommitteeMemberSol::random()],
+            U256::from(3),
+        );
+        let hash = propose_safe_transaction(&service, safe, 1, &tx, &signer)
+            .await
+            .unwrap();
+        assert_eq!(hash, tx.safe_tx_hash(1, safe));
+
+        let request = server.await.unwrap().remove(0);
+        assert!(request.head.starts_with(&format!(
+            "POST /api/v1/safes/{safe}/multisig-transactions/ "
+        )));
+        let proposal = serde_json::from_str::<SafeProposal>(&request.body).unwrap();
+        assert_eq!(proposal.contract_transaction_hash, hash);
+        assert_eq!(proposal.nonce, "3");
+        assert_eq!(proposal.sender, signer.address());
+        let signature = Signature::from_raw(&proposal.signature).unwrap();
+        assert_eq!(
+            signature.recover_address_from_prehash(&hash).unwrap(),
+            signer.address()
+        );
+    }
+}
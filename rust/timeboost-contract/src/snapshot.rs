@@ -0,0 +1,124 @@
+//! Versioned binary snapshots of committee history
+//!
+//! A snapshot is one version byte followed by the committees encoded under that version, so that
+//! caches written today stay readable once the encoding changes.
+//!
+//! | version | payload |
+//! |---------|---------|
+//! | 1 | `abi.encode(Committee[])`, uncompressed |
+//!
+//! Unknown versions are rejected with [`SnapshotError::UnknownVersion`] rather than misread.
+//!
+//! Snapshots are stored uncompressed, compress the bytes of [`serialize_committees`] where size
+//! matters.
+
+use std::fmt;
+
+use alloy::sol_types::SolValue;
+
+use crate::CommitteeSol;
+
+/// Version [`serialize_committees`] writes
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// Why a snapshot couldn't be read
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// no version byte
+    Empty,
+    /// written by a newer (or foreign) encoder
+    UnknownVersion(u8),
+    /// the payload isn't valid for its version
+    Decode(alloy::sol_types::Error),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "empty committee snapshot"),
+            Self::UnknownVersion(v) => write!(
+                f,
+                "unknown committee snapshot version {v}, expected {SNAPSHOT_VERSION}"
+            ),
+            Self::Decode(err) => write!(f, "malformed committee snapshot: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// `committees` as a snapshot of the current [`SNAPSHOT_VERSION`]
+pub fn serialize_committees(committees: &[CommitteeSol]) -> Vec<u8> {
+    let payload = committees.abi_encode();
+    let mut bytes = Vec::with_capacity(1 + payload.len());
+    bytes.push(SNAPSHOT_VERSION);
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+/// The committees of a snapshot written by [`serialize_committees`], of any known version
+pub fn deserialize_committees(bytes: &[u8]) -> Result<Vec<CommitteeSol>, SnapshotError> {
+    let (&version, payload) = bytes.split_first().ok_or(SnapshotError::Empty)?;
+    match version {
+        1 => decode_v1(payload).map_err(SnapshotError::Decode),
+        v => Err(SnapshotError::UnknownVersion(v)),
+    }
+}
+
+fn decode_v1(payload: &[u8]) -> Result<Vec<CommitteeSol>, alloy::sol_types::Error> {
+    let committees = Vec::<CommitteeSol>::abi_decode_validate(payload)?;
+    // like `CommitteeSol::from_abi_bytes`, catch truncated padding and trailing data
+    if committees.abi_encode() != payload {
+        return Err(alloy::sol_types::Error::ReserMismatch);
+    }
+    Ok(committees)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SNAPSHOT_VERSION, SnapshotError, deserialize_committees, serialize_committees};
+    use crate::{CommitteeMemberSol, CommitteeSol};
+    use alloy::primitives::U256;
+
+    #[test]
+    fn test_snapshot() {
+        let committees: Vec<_> = (0..3)
+            .map(|id| CommitteeSol {
+                id,
+                effectiveTimestamp: 100 * (id + 1),
+                registeredBlockNumber: U256::from(id + 1),
+                members: (0..=id).map(|_| CommitteeMemberSol::random()).collect(),
+            })
+            .collect();
+
+        let bytes = serialize_committees(&committees);
+        assert_eq!(bytes[0], SNAPSHOT_VERSION);
+        assert_eq!(deserialize_committees(&bytes).unwrap(), committees);
+        let empty = serialize_committees(&[]);
+        assert!(deserialize_committees(&empty).unwrap().is_empty());
+
+        assert!(matches!(
+            deserialize_committees(&[]),
+            Err(SnapshotError::Empty)
+        ));
+        let mut future = bytes.clone();
+        future[0] = 2;
+        let err = deserialize_committees(&future).unwrap_err();
+        assert!(matches!(err, SnapshotError::UnknownVersion(2)));
+        assert_eq!(
+            err.to_string(),
+            "unknown committee snapshot version 2, expected 1"
+        );
+        assert!(matches!(
+            deserialize_committees(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::Decode(_))
+        ));
+    }
+}
@@ -1,25 +1,1476 @@
 //! Solidity types for contract interaction
 
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+
+use alloy::{
+    primitives::{Address, B256, Bytes, U256},
+    sol_types::SolValue,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::commitment::CommitmentScheme;
+
 pub use crate::bindings::{
     erc1967_proxy::ERC1967Proxy,
     key_manager::KeyManager,
-    key_manager::KeyManager::{Committee as CommitteeSol, CommitteeMember as CommitteeMemberSol},
+    key_manager::KeyManager::{
+        Committee as CommitteeSol, CommitteeMember as CommitteeMemberSol,
+        KeyManagerEvents as KeyManagerEvent,
+    },
 };
 
+impl CommitteeSol {
+    /// Hash identifying a committee by its membership and effective timestamp, ignoring the
+    /// contract-assigned `id` and `registeredBlockNumber`, so that re-registrations of the same
+    /// membership collide.
+    ///
+    /// Scheme: members are sorted by the bytes of their individual `abi.encode(member)`, then
+    /// `keccak256(abi.encode(effectiveTimestamp, sortedMembers))`.
+    pub fn identity_hash(&self) -> B256 {
+        self.identity_hash_with(CommitmentScheme::Keccak256)
+    }
+
+    /// Decode a committee from its Solidity `abi.encode(committee)` encoding, e.g. as found in
+    /// calldata or another contract's return data. Rejects non-canonical or trailing bytes.
+    pub fn from_abi_bytes(bytes: &[u8]) -> Result<Self, alloy::sol_types::Error> {
+        let committee = Self::abi_decode_validate(bytes)?;
+        // the decoder tolerates truncated padding and trailing data, re-encoding does not
+        if committee.abi_encode() != bytes {
+            return Err(alloy::sol_types::Error::ReserMismatch);
+        }
+        Ok(committee)
+    }
+
+    /// [`Self::identity_hash`] with the final hash computed under `scheme`
+    pub fn identity_hash_with(&self, scheme: CommitmentScheme) -> B256 {
+        let mut members = self.members.clone();
+        members.sort_by_cached_key(|m| m.abi_encode());
+        scheme.hash_bytes(&(self.effectiveTimestamp, members).abi_encode_params())
+    }
+}
+
+/// Membership change between two committees, see [`CommitteeSol::diff`] and [`diff_committees`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CommitteeDiff {
+    pub added: Vec<CommitteeMemberSol>,
+    pub removed: Vec<CommitteeMemberSol>,
+    /// `(previous, current)` of members whose other fields changed
+    pub changed: Vec<(CommitteeMemberSol, CommitteeMemberSol)>,
+}
+
+impl CommitteeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// members of `current` and `previous` are the same if their `identity` is
+    fn between<K: PartialEq>(
+        previous: &CommitteeSol,
+        current: &CommitteeSol,
+        identity: impl Fn(&CommitteeMemberSol) -> K,
+    ) -> Self {
+        let find = |members: &[CommitteeMemberSol], m: &CommitteeMemberSol| {
+            let id = identity(m);
+            members.iter().find(|o| identity(o) == id).cloned()
+        };
+        let mut diff = Self::default();
+        for member in &current.members {
+            match find(&previous.members, member) {
+                None => diff.added.push(member.clone()),
+                Some(prev) if prev != *member => diff.changed.push((prev, member.clone())),
+                Some(_) => {}
+            }
+        }
+        diff.removed = previous
+            .members
+            .iter()
+            .filter(|m| find(&current.members, m).is_none())
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+impl CommitteeSol {
+    /// How membership changed from `previous` to `self`, in member order, members matched by
+    /// `sigKey`
+    pub fn diff(&self, previous: &CommitteeSol) -> CommitteeDiff {
+        CommitteeDiff::between(previous, self, |m| m.sigKey.clone())
+    }
+}
+
+/// How membership changed from `old` to `new`, in member order, members matched by
+/// `sigKeyAddress`.
+///
+/// Unlike [`CommitteeSol::diff`] a member keeps its identity across key rotations, which show up
+/// in `changed` rather than as a removal and an addition. `Address::ZERO`, the address of members
+/// registered without one, identifies nobody (like in [`member_index`]): those members are
+/// matched by `sigKey` instead, so their key rotations are a removal and an addition.
+pub fn diff_committees(old: &CommitteeSol, new: &CommitteeSol) -> CommitteeDiff {
+    CommitteeDiff::between(old, new, |m| {
+        if m.sigKeyAddress.is_zero() {
+            MemberId::SigKey(m.sigKey.clone())
+        } else {
+            MemberId::Address(m.sigKeyAddress)
+        }
+    })
+}
+
+/// identity of a member in [`diff_committees`]
+#[derive(PartialEq)]
+enum MemberId {
+    Address(Address),
+    SigKey(Bytes),
+}
+
+/// Index of the member of `committee` whose `sigKeyAddress` is `addr`.
+/// `Address::ZERO`, the address of members registered without one, matches nobody.
+pub fn member_index(committee: &CommitteeSol, addr: Address) -> Option<usize> {
+    if addr.is_zero() {
+        return None;
+    }
+    committee
+        .members
+        .iter()
+        .position(|m| m.sigKeyAddress == addr)
+}
+
+/// Id of the committee in effect at `now` among `committees`, in any order: the one with the
+/// greatest `effectiveTimestamp <= now`, preferring the highest id among equal timestamps, as
+/// [`current_committee`](crate::reader::current_committee) does on chain. `None` if none is
+/// effective yet.
+///
+/// `CommitteeCreated` only carries the id, so a client caching the event history has to keep the
+/// committees the events announce (read once per event), which this selects from without another
+/// round trip.
+pub fn committee_id_for_timestamp(committees: &[CommitteeSol], now: u64) -> Option<u64> {
+    committees
+        .iter()
+        .filter(|c| c.effectiveTimestamp <= now)
+        .max_by_key(|c| (c.effectiveTimestamp, c.id))
+        .map(|c| c.id)
+}
+
+/// Compact one-line summary for logs: keys as truncated hex, the address checksummed
+impl fmt::Display for CommitteeMemberSol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} sig={} dh={} dkg={} net={} batch={}",
+            self.sigKeyAddress.to_checksum(None),
+            ShortHex(&self.sigKey),
+            ShortHex(&self.dhKey),
+            ShortHex(&self.dkgKey),
+            self.networkAddress,
+            self.batchPosterAddress
+        )
+    }
+}
+
+/// Compact one-line summary for logs, with the members as by their `Display`
+impl fmt::Display for CommitteeSol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "committee {} effective at {} (block {}), {} members: [",
+            self.id,
+            self.effectiveTimestamp,
+            self.registeredBlockNumber,
+            self.members.len()
+        )?;
+        for (i, member) in self.members.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{member}")?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// hex of the first 4 and last 2 bytes, in full if that's not shorter
+struct ShortHex<'a>(&'a [u8]);
+
+impl fmt::Display for ShortHex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.0;
+        if bytes.len() <= 6 {
+            return write!(f, "0x{}", alloy::hex::encode(bytes));
+        }
+        let (head, tail) = (&bytes[..4], &bytes[bytes.len() - 2..]);
+        write!(
+            f,
+            "0x{}…{}",
+            alloy::hex::encode(head),
+            alloy::hex::encode(tail)
+        )
+    }
+}
+
+/// A member's network or batch poster address: `ip:port`, `[ipv6]:port` or `hostname:port`,
+/// with a non-zero port and a specified IP. Hostnames are kept as is, to be looked up when
+/// connecting, e.g. with [`Self::resolve`]. (De)serialized as its string form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    Socket(SocketAddr),
+    Host { host: String, port: u16 },
+}
+
+impl Endpoint {
+    pub fn port(&self) -> u16 {
+        match self {
+            Self::Socket(addr) => addr.port(),
+            Self::Host { port, .. } => *port,
+        }
+    }
+
+    /// the socket address, `None` for a hostname
+    pub fn socket_addr(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Socket(addr) => Some(*addr),
+            Self::Host { .. } => None,
+        }
+    }
+
+    /// The socket addresses to connect to, looking up a hostname
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn resolve(&self) -> std::io::Result<Vec<SocketAddr>> {
+        match self {
+            Self::Socket(addr) => Ok(vec![*addr]),
+            Self::Host { host, port } => Ok(tokio::net::lookup_host((host.as_str(), *port))
+                .await?
+                .collect()),
+        }
+    }
+}
+
+impl From<SocketAddr> for Endpoint {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Socket(addr)
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Socket(addr) => addr.fmt(f),
+            Self::Host { host, port } => write!(f, "{host}:{port}"),
+        }
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = EndpointError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(addr) = s.parse::<SocketAddr>() {
+            return if addr.port() == 0 {
+                Err(EndpointError::InvalidPort)
+            } else if addr.ip().is_unspecified() {
+                Err(EndpointError::UnspecifiedIp)
+            } else {
+                Ok(Self::Socket(addr))
+            };
+        }
+        if s.parse::<IpAddr>().is_ok() {
+            return Err(EndpointError::MissingPort);
+        }
+        let (host, port) = s.rsplit_once(':').ok_or(EndpointError::MissingPort)?;
+        let port = match port.parse::<u16>() {
+            Ok(0) | Err(_) => return Err(EndpointError::InvalidPort),
+            Ok(port) => port,
+        };
+        let valid_label = |l: &str| {
+            !l.is_empty()
+                && l.len() <= 63
+                && !l.starts_with('-')
+                && !l.ends_with('-')
+                && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+        // an all-numeric last label would be a malformed IP, e.g. `10.0.0.256`
+        let numeric = host
+            .rsplit('.')
+            .next()
+            .is_some_and(|l| l.chars().all(|c| c.is_ascii_digit()));
+        if host.len() > 253 || numeric || !host.split('.').all(valid_label) {
+            return Err(EndpointError::InvalidHost);
+        }
+        Ok(Self::Host {
+            host: host.to_string(),
+            port,
+        })
+    }
+}
+
+impl Serialize for Endpoint {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Endpoint {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Why a string isn't an [`Endpoint`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointError {
+    MissingPort,
+    /// zero or not a `u16`
+    InvalidPort,
+    /// neither an IP nor a valid hostname
+    InvalidHost,
+    /// e.g. `0.0.0.0`, which nobody can connect to
+    UnspecifiedIp,
+    /// a valid hostname where a socket address was asked for
+    Hostname,
+}
+
+impl fmt::Display for EndpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPort => write!(f, "missing port"),
+            Self::InvalidPort => write!(f, "invalid port"),
+            Self::InvalidHost => write!(f, "invalid host"),
+            Self::UnspecifiedIp => write!(f, "unspecified IP"),
+            Self::Hostname => write!(f, "hostname, not an IP"),
+        }
+    }
+}
+
+impl std::error::Error for EndpointError {}
+
+/// A member's network or batch poster address that isn't a valid [`Endpoint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidEndpoint {
+    pub field: &'static str,
+    pub value: String,
+    pub reason: EndpointError,
+}
+
+impl fmt::Display for InvalidEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} {:?}: {}",
+            self.field, self.value, self.reason
+        )
+    }
+}
+
+impl std::error::Error for InvalidEndpoint {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.reason)
+    }
+}
+
+fn parse_endpoint(field: &'static str, value: &str) -> Result<Endpoint, InvalidEndpoint> {
+    value.parse().map_err(|reason| InvalidEndpoint {
+        field,
+        value: value.to_string(),
+        reason,
+    })
+}
+
+fn parse_socket_addr(field: &'static str, value: &str) -> Result<SocketAddr, InvalidEndpoint> {
+    parse_endpoint(field, value)?
+        .socket_addr()
+        .ok_or_else(|| InvalidEndpoint {
+            field,
+            value: value.to_string(),
+            reason: EndpointError::Hostname,
+        })
+}
+
+/// A [`CommitteeSol`] (de)serialized with checksummed addresses, for persisting snapshots.
+/// Keys are 0x-prefixed hex and `registeredBlockNumber` a hex quantity, as in the derived
+/// serde of the bindings, which lowercases addresses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SerdeCommittee(#[serde(with = "CommitteeDef")] pub CommitteeSol);
+
+/// A [`CommitteeMemberSol`] (de)serialized like in [`SerdeCommittee`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SerdeCommitteeMember(#[serde(with = "CommitteeMemberDef")] pub CommitteeMemberSol);
+
+impl From<CommitteeSol> for SerdeCommittee {
+    fn from(committee: CommitteeSol) -> Self {
+        Self(committee)
+    }
+}
+
+impl From<SerdeCommittee> for CommitteeSol {
+    fn from(committee: SerdeCommittee) -> Self {
+        committee.0
+    }
+}
+
+impl From<CommitteeMemberSol> for SerdeCommitteeMember {
+    fn from(member: CommitteeMemberSol) -> Self {
+        Self(member)
+    }
+}
+
+impl From<SerdeCommitteeMember> for CommitteeMemberSol {
+    fn from(member: SerdeCommitteeMember) -> Self {
+        member.0
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "CommitteeSol")]
+#[allow(non_snake_case)]
+struct CommitteeDef {
+    id: u64,
+    effectiveTimestamp: u64,
+    registeredBlockNumber: U256,
+    #[serde(with = "member_list")]
+    members: Vec<CommitteeMemberSol>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "CommitteeMemberSol")]
+#[allow(non_snake_case)]
+struct CommitteeMemberDef {
+    sigKey: Bytes,
+    dhKey: Bytes,
+    dkgKey: Bytes,
+    #[serde(with = "crate::address_hex")]
+    sigKeyAddress: Address,
+    networkAddress: String,
+    batchPosterAddress: String,
+}
+
+mod member_list {
+    use super::*;
+
+    struct MemberRef<'a>(&'a CommitteeMemberSol);
+
+    impl Serialize for MemberRef<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            CommitteeMemberDef::serialize(self.0, serializer)
+        }
+    }
+
+    pub fn serialize<S: Serializer>(
+        members: &[CommitteeMemberSol],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(members.iter().map(MemberRef))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<CommitteeMemberSol>, D::Error> {
+        let members = Vec::<SerdeCommitteeMember>::deserialize(deserializer)?;
+        Ok(members.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Byte lengths of the member keys, as encoded by the nodes
+pub const SIG_KEY_LEN: usize = 32;
+pub const DH_KEY_LEN: usize = 32;
+pub const DKG_KEY_LEN: usize = 32;
+
+/// A member key that doesn't have the expected encoded length
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyError {
+    pub field: &'static str,
+    pub expected: usize,
+    pub actual: usize,
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} must be {} bytes, got {}",
+            self.field, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+fn fixed_key<const N: usize>(field: &'static str, key: &[u8]) -> Result<[u8; N], KeyError> {
+    key.try_into().map_err(|_| KeyError {
+        field,
+        expected: N,
+        actual: key.len(),
+    })
+}
+
+/// Why a [`CommitteeMemberBuilder`] couldn't build a member, or [`CommitteeMemberSol::validate`]
+/// rejected one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitteeMemberError {
+    /// a required field was never set
+    Missing(&'static str),
+    Key(KeyError),
+    Address(InvalidEndpoint),
+}
+
+impl fmt::Display for CommitteeMemberError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(field) => write!(f, "missing {field}"),
+            Self::Key(err) => err.fmt(f),
+            Self::Address(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CommitteeMemberError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Key(err) => Some(err),
+            Self::Address(err) => Some(err),
+            Self::Missing(_) => None,
+        }
+    }
+}
+
+impl From<KeyError> for CommitteeMemberError {
+    fn from(err: KeyError) -> Self {
+        Self::Key(err)
+    }
+}
+
+impl From<InvalidEndpoint> for CommitteeMemberError {
+    fn from(err: InvalidEndpoint) -> Self {
+        Self::Address(err)
+    }
+}
+
+/// A node key that goes into one of the key fields of a [`CommitteeMemberSol`], see
+/// [`CommitteeMemberSol::from_keys`]. Implement it for the node's own key types.
+pub trait MemberKey {
+    /// the key as encoded on chain, e.g. a compressed public key
+    fn to_member_key(&self) -> Bytes;
+}
+
+impl<const N: usize> MemberKey for [u8; N] {
+    fn to_member_key(&self) -> Bytes {
+        Bytes::copy_from_slice(self)
+    }
+}
+
+impl MemberKey for [u8] {
+    fn to_member_key(&self) -> Bytes {
+        Bytes::copy_from_slice(self)
+    }
+}
+
+impl MemberKey for Vec<u8> {
+    fn to_member_key(&self) -> Bytes {
+        Bytes::copy_from_slice(self)
+    }
+}
+
+impl MemberKey for Bytes {
+    fn to_member_key(&self) -> Bytes {
+        self.clone()
+    }
+}
+
+impl MemberKey for B256 {
+    fn to_member_key(&self) -> Bytes {
+        Bytes::copy_from_slice(self.as_slice())
+    }
+}
+
+impl<K: MemberKey + ?Sized> MemberKey for &K {
+    fn to_member_key(&self) -> Bytes {
+        (**self).to_member_key()
+    }
+}
+
+/// Validating builder of a [`CommitteeMemberSol`], all fields are required
+#[derive(Debug, Clone, Default)]
+pub struct CommitteeMemberBuilder {
+    sig_key: Option<Bytes>,
+    dh_key: Option<Bytes>,
+    dkg_key: Option<Bytes>,
+    sig_key_address: Option<Address>,
+    network_address: Option<String>,
+    batch_poster_address: Option<String>,
+}
+
+impl CommitteeMemberBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sig_key(mut self, key: impl Into<Bytes>) -> Self {
+        self.sig_key = Some(key.into());
+        self
+    }
+
+    pub fn dh_key(mut self, key: impl Into<Bytes>) -> Self {
+        self.dh_key = Some(key.into());
+        self
+    }
+
+    pub fn dkg_key(mut self, key: impl Into<Bytes>) -> Self {
+        self.dkg_key = Some(key.into());
+        self
+    }
+
+    pub fn sig_key_address(mut self, address: Address) -> Self {
+        self.sig_key_address = Some(address);
+        self
+    }
+
+    /// `ip:port`, `[ipv6]:port` or `hostname:port`, see [`Endpoint`]
+    pub fn network_address(mut self, address: impl Into<String>) -> Self {
+        self.network_address = Some(address.into());
+        self
+    }
+
+    /// `ip:port`, `[ipv6]:port` or `hostname:port`, see [`Endpoint`]
+    pub fn batch_poster_address(mut self, address: impl Into<String>) -> Self {
+        self.batch_poster_address = Some(address.into());
+        self
+    }
+
+    /// Check key lengths and addresses, reporting the first problem found
+    pub fn build(self) -> Result<CommitteeMemberSol, CommitteeMemberError> {
+        let member = CommitteeMemberSol {
+            sigKey: required(self.sig_key, "sigKey")?,
+            dhKey: required(self.dh_key, "dhKey")?,
+            dkgKey: required(self.dkg_key, "dkgKey")?,
+            sigKeyAddress: required(self.sig_key_address, "sigKeyAddress")?,
+            networkAddress: required(self.network_address, "networkAddress")?,
+            batchPosterAddress: required(self.batch_poster_address, "batchPosterAddress")?,
+        };
+        match member.validate() {
+            Ok(()) => Ok(member),
+            Err(mut errors) => Err(errors.remove(0)),
+        }
+    }
+}
+
+/// `value`, or [`CommitteeMemberError::Missing`] naming the Solidity `field` it was meant for
+fn required<T>(value: Option<T>, field: &'static str) -> Result<T, CommitteeMemberError> {
+    value.ok_or(CommitteeMemberError::Missing(field))
+}
+
 impl CommitteeMemberSol {
-    #[cfg(test)]
+    pub fn builder() -> CommitteeMemberBuilder {
+        CommitteeMemberBuilder::new()
+    }
+
+    /// A member with the given node keys, validated like [`CommitteeMemberBuilder::build`], so
+    /// a key that doesn't encode to its fixed length is rejected
+    pub fn from_keys(
+        sig_key: &(impl MemberKey + ?Sized),
+        dh_key: &(impl MemberKey + ?Sized),
+        dkg_key: &(impl MemberKey + ?Sized),
+        sig_key_address: Address,
+        network_address: impl Into<String>,
+        batch_poster_address: impl Into<String>,
+    ) -> Result<Self, CommitteeMemberError> {
+        Self::builder()
+            .sig_key(sig_key.to_member_key())
+            .dh_key(dh_key.to_member_key())
+            .dkg_key(dkg_key.to_member_key())
+            .sig_key_address(sig_key_address)
+            .network_address(network_address)
+            .batch_poster_address(batch_poster_address)
+            .build()
+    }
+
+    /// `networkAddress` as an endpoint
+    pub fn network_endpoint(&self) -> Result<Endpoint, InvalidEndpoint> {
+        parse_endpoint("networkAddress", &self.networkAddress)
+    }
+
+    /// `batchPosterAddress` as an endpoint
+    pub fn batch_poster_endpoint(&self) -> Result<Endpoint, InvalidEndpoint> {
+        parse_endpoint("batchPosterAddress", &self.batchPosterAddress)
+    }
+
+    /// `networkAddress` as a socket address, rejecting a hostname like
+    /// [`EndpointError::Hostname`]
+    pub fn network_socket_addr(&self) -> Result<SocketAddr, InvalidEndpoint> {
+        parse_socket_addr("networkAddress", &self.networkAddress)
+    }
+
+    /// `batchPosterAddress` as a socket address, rejecting a hostname like
+    /// [`EndpointError::Hostname`]
+    pub fn batch_poster_socket_addr(&self) -> Result<SocketAddr, InvalidEndpoint> {
+        parse_socket_addr("batchPosterAddress", &self.batchPosterAddress)
+    }
+
+    /// `sigKey` as a fixed-size array
+    pub fn sig_key(&self) -> Result<[u8; SIG_KEY_LEN], KeyError> {
+        fixed_key("sigKey", &self.sigKey)
+    }
+
+    /// `dhKey` as a fixed-size array
+    pub fn dh_key(&self) -> Result<[u8; DH_KEY_LEN], KeyError> {
+        fixed_key("dhKey", &self.dhKey)
+    }
+
+    /// `dkgKey` as a fixed-size array
+    pub fn dkg_key(&self) -> Result<[u8; DKG_KEY_LEN], KeyError> {
+        fixed_key("dkgKey", &self.dkgKey)
+    }
+
+    /// Check the key lengths and both endpoints, e.g. of a member read from chain before
+    /// using it, returning every problem found (in field order)
+    pub fn validate(&self) -> Result<(), Vec<CommitteeMemberError>> {
+        let errors: Vec<CommitteeMemberError> = [
+            self.sig_key().err().map(Into::into),
+            self.dh_key().err().map(Into::into),
+            self.dkg_key().err().map(Into::into),
+            self.network_endpoint().err().map(Into::into),
+            self.batch_poster_endpoint().err().map(Into::into),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// A member with random keys and `127.0.0.1` addresses, see
+    /// [`MemberGenerator`](crate::test_util::MemberGenerator) for other bounds or a seed
+    #[cfg(any(test, feature = "test-util"))]
     pub fn random() -> Self {
-        use alloy::primitives::Bytes;
-        use rand::prelude::*;
-
-        let mut rng = rand::rng();
-        CommitteeMemberSol {
-            sigKey: Bytes::from(rng.random::<[u8; 32]>()),
-            dhKey: Bytes::from(rng.random::<[u8; 32]>()),
-            dkgKey: Bytes::from(rng.random::<[u8; 32]>()),
-            networkAddress: format!("127.0.0.1:{}", rng.random::<u16>()),
-            batchPosterAddress: format!("127.0.0.1:{}", rng.random::<u16>()),
-            sigKeyAddress: alloy::primitives::Address::default(),
+        crate::test_util::MemberGenerator::new(rand::rng()).member()
+    }
+}
+
+/// A committee member with decoded keys and parsed socket addresses, see [`CommitteeMemberSol`]
+/// for the wire format, so a member with a hostname endpoint doesn't convert. Serialized with
+/// 0x-prefixed hex keys and a checksummed address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CommitteeMember {
+    #[serde(with = "alloy::hex")]
+    pub sig_key: [u8; SIG_KEY_LEN],
+    #[serde(with = "alloy::hex")]
+    pub dh_key: [u8; DH_KEY_LEN],
+    #[serde(with = "alloy::hex")]
+    pub dkg_key: [u8; DKG_KEY_LEN],
+    #[serde(with = "crate::address_hex")]
+    pub sig_key_address: Address,
+    pub network_address: SocketAddr,
+    pub batch_poster_address: SocketAddr,
+}
+
+/// A committee with native field types, see [`CommitteeSol`] for the wire format
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Committee {
+    pub id: u64,
+    pub effective_timestamp: u64,
+    pub registered_block_number: u64,
+    pub members: Vec<CommitteeMember>,
+}
+
+/// Why a [`CommitteeSol`] couldn't be converted into a [`Committee`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommitteeError {
+    /// `registeredBlockNumber` doesn't fit a `u64`
+    BlockNumber(U256),
+    /// the first invalid member, with its index
+    Member {
+        index: usize,
+        error: CommitteeMemberError,
+    },
+}
+
+impl fmt::Display for CommitteeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BlockNumber(n) => write!(f, "registered block number {n} is out of range"),
+            Self::Member { index, error } => write!(f, "member {index}: {error}"),
         }
     }
 }
+
+impl std::error::Error for CommitteeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Member { error, .. } => Some(error),
+            Self::BlockNumber(_) => None,
+        }
+    }
+}
+
+impl TryFrom<CommitteeMemberSol> for CommitteeMember {
+    type Error = CommitteeMemberError;
+
+    /// Fails with the first problem [`CommitteeMemberSol::validate`] would report
+    fn try_from(member: CommitteeMemberSol) -> Result<Self, Self::Error> {
+        Ok(Self {
+            sig_key: member.sig_key()?,
+            dh_key: member.dh_key()?,
+            dkg_key: member.dkg_key()?,
+            sig_key_address: member.sigKeyAddress,
+            network_address: member.network_socket_addr()?,
+            batch_poster_address: member.batch_poster_socket_addr()?,
+        })
+    }
+}
+
+impl TryFrom<CommitteeSol> for Committee {
+    type Error = CommitteeError;
+
+    fn try_from(committee: CommitteeSol) -> Result<Self, Self::Error> {
+        let registered_block_number = committee
+            .registeredBlockNumber
+            .try_into()
+            .map_err(|_| CommitteeError::BlockNumber(committee.registeredBlockNumber))?;
+        let members = committee
+            .members
+            .into_iter()
+            .enumerate()
+            .map(|(index, member)| {
+                member
+                    .try_into()
+                    .map_err(|error| CommitteeError::Member { index, error })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            id: committee.id,
+            effective_timestamp: committee.effectiveTimestamp,
+            registered_block_number,
+            members,
+        })
+    }
+}
+
+impl From<CommitteeMember> for CommitteeMemberSol {
+    fn from(member: CommitteeMember) -> Self {
+        Self {
+            sigKey: Bytes::copy_from_slice(&member.sig_key),
+            dhKey: Bytes::copy_from_slice(&member.dh_key),
+            dkgKey: Bytes::copy_from_slice(&member.dkg_key),
+            sigKeyAddress: member.sig_key_address,
+            networkAddress: member.network_address.to_string(),
+            batchPosterAddress: member.batch_poster_address.to_string(),
+        }
+    }
+}
+
+impl From<Committee> for CommitteeSol {
+    fn from(committee: Committee) -> Self {
+        Self {
+            id: committee.id,
+            effectiveTimestamp: committee.effective_timestamp,
+            registeredBlockNumber: U256::from(committee.registered_block_number),
+            members: committee.members.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Committee, CommitteeError, CommitteeMember, CommitteeMemberError, CommitteeMemberSol,
+        CommitteeSol, Endpoint, EndpointError, KeyError, MemberKey, SerdeCommittee,
+        SerdeCommitteeMember, committee_id_for_timestamp, diff_committees, member_index,
+    };
+    use alloy::{
+        primitives::{Address, B256, Bytes, U256},
+        sol_types::SolValue,
+    };
+
+    #[test]
+    fn test_from_abi_bytes() {
+        let committee = CommitteeSol {
+            id: 2,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members: vec![CommitteeMemberSol::random(), CommitteeMemberSol::random()],
+        };
+        let bytes = committee.abi_encode();
+        assert_eq!(CommitteeSol::from_abi_bytes(&bytes).unwrap(), committee);
+
+        assert!(CommitteeSol::from_abi_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(CommitteeSol::from_abi_bytes(&[bytes.clone(), vec![0; 32]].concat()).is_err());
+        assert!(CommitteeSol::from_abi_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn test_committee_id_for_timestamp() {
+        let committee = |id, ts| CommitteeSol {
+            id,
+            effectiveTimestamp: ts,
+            ..Default::default()
+        };
+        let committees = [committee(2, 300), committee(0, 100), committee(1, 200)];
+        assert_eq!(committee_id_for_timestamp(&committees, 99), None);
+        assert_eq!(committee_id_for_timestamp(&committees, 100), Some(0));
+        assert_eq!(committee_id_for_timestamp(&committees, 199), Some(0));
+        assert_eq!(committee_id_for_timestamp(&committees, 200), Some(1));
+        assert_eq!(committee_id_for_timestamp(&committees, u64::MAX), Some(2));
+        assert_eq!(committee_id_for_timestamp(&[], 100), None);
+
+        // the later registration wins a tie
+        let committees = [committee(4, 100), committee(3, 100)];
+        assert_eq!(committee_id_for_timestamp(&committees, 100), Some(4));
+    }
+
+    #[test]
+    fn test_native_committee() {
+        let committee = CommitteeSol {
+            id: 4,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(12),
+            members: vec![CommitteeMemberSol::random(), CommitteeMemberSol::random()],
+        };
+        let native = Committee::try_from(committee.clone()).unwrap();
+        assert_eq!(native.registered_block_number, 12);
+        assert_eq!(
+            native.members[1].sig_key[..],
+            committee.members[1].sigKey[..]
+        );
+        assert_eq!(
+            native.members[0].network_address.to_string(),
+            committee.members[0].networkAddress
+        );
+        let json = serde_json::to_value(&native).unwrap();
+        assert_eq!(
+            json["members"][0]["sig_key_address"],
+            committee.members[0].sigKeyAddress.to_checksum(None)
+        );
+        assert_eq!(
+            json["members"][0]["sig_key"],
+            committee.members[0].sigKey.to_string()
+        );
+        assert_eq!(serde_json::from_value::<Committee>(json).unwrap(), native);
+        assert_eq!(CommitteeSol::from(native), committee);
+
+        let mut invalid = committee.clone();
+        invalid.members[1].dhKey = Bytes::from_static(&[1; 31]);
+        assert_eq!(
+            Committee::try_from(invalid).unwrap_err(),
+            CommitteeError::Member {
+                index: 1,
+                error: CommitteeMemberError::Key(KeyError {
+                    field: "dhKey",
+                    expected: 32,
+                    actual: 31
+                })
+            }
+        );
+
+        let mut invalid = committee.clone();
+        invalid.registeredBlockNumber = U256::MAX;
+        assert_eq!(
+            Committee::try_from(invalid).unwrap_err(),
+            CommitteeError::BlockNumber(U256::MAX)
+        );
+
+        let mut invalid = committee.clone();
+        invalid.members[0].networkAddress = "node-1.example.com:8000".to_string();
+        let err = Committee::try_from(invalid).unwrap_err();
+        let CommitteeError::Member {
+            index: 0,
+            error: CommitteeMemberError::Address(err),
+        } = err
+        else {
+            panic!("unexpected error {err}");
+        };
+        assert_eq!(err.reason, EndpointError::Hostname);
+
+        let mut member = committee.members[0].clone();
+        member.batchPosterAddress = "127.0.0.1".into();
+        let err = CommitteeMember::try_from(member).unwrap_err();
+        assert!(matches!(err, CommitteeMemberError::Address(e) if e.field == "batchPosterAddress"));
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let mut member = CommitteeMemberSol::random();
+        member.sigKeyAddress = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+            .parse()
+            .unwrap();
+        let committee = CommitteeSol {
+            id: 3,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(42),
+            members: vec![member.clone(), CommitteeMemberSol::random()],
+        };
+        let json = serde_json::to_value(SerdeCommittee(committee.clone())).unwrap();
+        assert_eq!(json["id"], 3);
+        assert_eq!(json["registeredBlockNumber"], "0x2a");
+        assert_eq!(json["members"][0]["sigKey"], member.sigKey.to_string());
+        assert_eq!(
+            json["members"][0]["sigKeyAddress"],
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+        );
+        let back: SerdeCommittee = serde_json::from_value(json).unwrap();
+        assert_eq!(CommitteeSol::from(back), committee);
+
+        let text = serde_json::to_string(&SerdeCommitteeMember(member.clone())).unwrap();
+        let back: SerdeCommitteeMember = serde_json::from_str(&text).unwrap();
+        assert_eq!(back.0, member);
+        assert!(serde_json::from_str::<SerdeCommitteeMember>("{}").is_err());
+
+        // the lowercase addresses of the bindings' own serde are accepted too
+        let plain = serde_json::to_string(&committee).unwrap();
+        let back: SerdeCommittee = serde_json::from_str(&plain).unwrap();
+        assert_eq!(back.0, committee);
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let mut member = CommitteeMemberSol::random();
+        member.networkAddress = "10.0.0.1:8000".to_string();
+        member.batchPosterAddress = "[2001:db8::1]:9000".to_string();
+        assert_eq!(
+            member.network_endpoint().unwrap(),
+            Endpoint::Socket("10.0.0.1:8000".parse().unwrap())
+        );
+        let poster = member.batch_poster_endpoint().unwrap();
+        assert!(poster.socket_addr().unwrap().is_ipv6());
+        assert_eq!(poster.port(), 9000);
+
+        member.networkAddress = "node-1.example.com:8000".to_string();
+        let endpoint = member.network_endpoint().unwrap();
+        assert_eq!(
+            endpoint,
+            Endpoint::Host {
+                host: "node-1.example.com".into(),
+                port: 8000
+            }
+        );
+        assert_eq!(endpoint.to_string(), member.networkAddress);
+        assert_eq!(endpoint.socket_addr(), None);
+        let json = serde_json::to_value(&endpoint).unwrap();
+        assert_eq!(json, "node-1.example.com:8000");
+        assert_eq!(serde_json::from_value::<Endpoint>(json).unwrap(), endpoint);
+        assert!(serde_json::from_value::<Endpoint>("localhost".into()).is_err());
+
+        for (addr, reason) in [
+            ("10.0.0.1", EndpointError::MissingPort),
+            ("2001:db8::1", EndpointError::MissingPort),
+            ("localhost", EndpointError::MissingPort),
+            ("", EndpointError::MissingPort),
+            ("10.0.0.1:70000", EndpointError::InvalidPort),
+            ("10.0.0.1:0", EndpointError::InvalidPort),
+            ("localhost:0", EndpointError::InvalidPort),
+            ("10.0.0.256:8000", EndpointError::InvalidHost),
+            ("[2001:db8::zz]:9000", EndpointError::InvalidHost),
+            ("-node.example.com:8000", EndpointError::InvalidHost),
+            ("node..example.com:8000", EndpointError::InvalidHost),
+            ("0.0.0.0:8000", EndpointError::UnspecifiedIp),
+            ("[::]:8000", EndpointError::UnspecifiedIp),
+        ] {
+            member.networkAddress = addr.to_string();
+            let err = member.network_endpoint().unwrap_err();
+            assert_eq!(err.field, "networkAddress");
+            assert_eq!(err.value, addr);
+            assert_eq!(err.reason, reason, "{addr}");
+            assert_eq!(
+                err.to_string(),
+                format!("invalid networkAddress {addr:?}: {reason}")
+            );
+        }
+    }
+
+    #[test]
+    fn test_socket_addrs() {
+        let mut member = CommitteeMemberSol::random();
+        member.networkAddress = "10.0.0.1:8000".to_string();
+        member.batchPosterAddress = "[2001:db8::1]:9000".to_string();
+        let addr = member.network_socket_addr().unwrap();
+        assert_eq!(
+            addr,
+            "10.0.0.1:8000".parse::<std::net::SocketAddr>().unwrap()
+        );
+        let poster = member.batch_poster_socket_addr().unwrap();
+        assert!(poster.is_ipv6());
+        assert_eq!(poster.port(), 9000);
+
+        member.batchPosterAddress = "poster.example.com:9000".to_string();
+        assert!(member.batch_poster_endpoint().is_ok());
+        let err = member.batch_poster_socket_addr().unwrap_err();
+        assert_eq!(err.field, "batchPosterAddress");
+        assert_eq!(err.reason, EndpointError::Hostname);
+
+        for (addr, reason) in [
+            ("10.0.0.1", EndpointError::MissingPort),
+            ("[2001:db8::zz]:9000", EndpointError::InvalidHost),
+            ("10.0.0.1:0", EndpointError::InvalidPort),
+            ("10.0.0.256:8000", EndpointError::InvalidHost),
+            ("0.0.0.0:8000", EndpointError::UnspecifiedIp),
+        ] {
+            member.networkAddress = addr.to_string();
+            let err = member.network_socket_addr().unwrap_err();
+            assert_eq!(err.reason, reason, "{addr}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_endpoint() {
+        let addr = "127.0.0.1:8000".parse().unwrap();
+        assert_eq!(Endpoint::Socket(addr).resolve().await.unwrap(), [addr]);
+        let host: Endpoint = "localhost:8000".parse().unwrap();
+        let resolved = host.resolve().await.unwrap();
+        assert!(!resolved.is_empty());
+        assert!(
+            resolved
+                .iter()
+                .all(|a| a.ip().is_loopback() && a.port() == 8000)
+        );
+    }
+
+    #[test]
+    fn test_validate_member() {
+        let m = CommitteeMemberSol::random();
+        assert_eq!(m.sig_key().unwrap().as_slice(), m.sigKey.as_ref());
+        assert_eq!(m.dkg_key().unwrap().as_slice(), m.dkgKey.as_ref());
+        assert_eq!(m.validate(), Ok(()));
+
+        let bad = CommitteeMemberSol {
+            dhKey: Bytes::from_static(&[1; 31]),
+            dkgKey: Bytes::new(),
+            batchPosterAddress: "localhost".into(),
+            ..m
+        };
+        assert_eq!(
+            bad.dh_key().unwrap_err(),
+            KeyError {
+                field: "dhKey",
+                expected: 32,
+                actual: 31
+            }
+        );
+        let errors = bad.validate().unwrap_err();
+        let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0], "dhKey must be 32 bytes, got 31");
+        assert_eq!(messages[1], "dkgKey must be 32 bytes, got 0");
+        assert!(matches!(
+            &errors[2],
+            CommitteeMemberError::Address(e) if e.field == "batchPosterAddress"
+        ));
+    }
+
+    /// stands in for a node's own key type
+    struct NodeKey([u8; 32]);
+
+    impl MemberKey for NodeKey {
+        fn to_member_key(&self) -> Bytes {
+            self.0.to_member_key()
+        }
+    }
+
+    #[test]
+    fn test_from_keys() {
+        let addr = Address::with_last_byte(1);
+        let member = CommitteeMemberSol::from_keys(
+            &NodeKey([1; 32]),
+            &B256::repeat_byte(2),
+            &vec![3; 32],
+            addr,
+            "127.0.0.1:8000",
+            "127.0.0.1:8001",
+        )
+        .unwrap();
+        assert_eq!(member.sig_key().unwrap(), [1; 32]);
+        assert_eq!(member.dh_key().unwrap(), [2; 32]);
+        assert_eq!(member.dkg_key().unwrap(), [3; 32]);
+        assert_eq!(member.sigKeyAddress, addr);
+        assert_eq!(member.batchPosterAddress, "127.0.0.1:8001");
+
+        let err = CommitteeMemberSol::from_keys(
+            &[1; 32],
+            &[2; 32],
+            &[3u8; 48][..],
+            addr,
+            "127.0.0.1:8000",
+            "127.0.0.1:8001",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            CommitteeMemberError::Key(KeyError {
+                field: "dkgKey",
+                expected: 32,
+                actual: 48
+            })
+        );
+    }
+
+    #[test]
+    fn test_member_builder() {
+        let m = CommitteeMemberSol::random();
+        let builder = CommitteeMemberSol::builder()
+            .sig_key(m.sigKey.clone())
+            .dh_key(m.dhKey.clone())
+            .dkg_key(m.dkgKey.clone())
+            .sig_key_address(m.sigKeyAddress)
+            .network_address(&m.networkAddress)
+            .batch_poster_address(&m.batchPosterAddress);
+        assert_eq!(builder.clone().build().unwrap(), m);
+
+        for field in [
+            "sigKey",
+            "dhKey",
+            "dkgKey",
+            "sigKeyAddress",
+            "networkAddress",
+            "batchPosterAddress",
+        ] {
+            let mut missing = builder.clone();
+            match field {
+                "sigKey" => missing.sig_key = None,
+                "dhKey" => missing.dh_key = None,
+                "dkgKey" => missing.dkg_key = None,
+                "sigKeyAddress" => missing.sig_key_address = None,
+                "networkAddress" => missing.network_address = None,
+                _ => missing.batch_poster_address = None,
+            }
+            let err = missing.build().unwrap_err();
+            assert_eq!(err, CommitteeMemberError::Missing(field));
+            assert_eq!(err.to_string(), format!("missing {field}"));
+        }
+        let err = CommitteeMemberSol::builder().build().unwrap_err();
+        assert_eq!(err.to_string(), "missing sigKey");
+
+        let err = builder.clone().dh_key([0; 33]).build().unwrap_err();
+        assert_eq!(err.to_string(), "dhKey must be 32 bytes, got 33");
+        let err = builder.clone().sig_key(Bytes::new()).build().unwrap_err();
+        assert!(matches!(
+            err,
+            CommitteeMemberError::Key(KeyError { actual: 0, .. })
+        ));
+
+        let err = builder
+            .clone()
+            .batch_poster_address("10.0.0.1")
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            r#"invalid batchPosterAddress "10.0.0.1": missing port"#
+        );
+        let built = builder
+            .clone()
+            .sig_key_address(Address::with_last_byte(1))
+            .network_address("[::1]:8000")
+            .build()
+            .unwrap();
+        assert_eq!(built.network_endpoint().unwrap().port(), 8000);
+        let built = builder
+            .batch_poster_address("poster.example.com:9000")
+            .build()
+            .unwrap();
+        assert_eq!(built.batch_poster_endpoint().unwrap().port(), 9000);
+    }
+
+    #[test]
+    fn test_diff() {
+        let members = (0..3)
+            .map(|_| CommitteeMemberSol::random())
+            .collect::<Vec<_>>();
+        let previous = CommitteeSol {
+            id: 1,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members: members.clone(),
+        };
+        assert!(previous.diff(&previous).is_empty());
+
+        let mut current = previous.clone();
+        current.members.remove(0);
+        current.members[0].networkAddress = "10.0.0.1:8000".to_string();
+        current.members.push(CommitteeMemberSol::random());
+        let diff = current.diff(&previous);
+        assert_eq!(diff.added, [current.members[2].clone()]);
+        assert_eq!(diff.removed, [members[0].clone()]);
+        assert_eq!(
+            diff.changed,
+            [(members[1].clone(), current.members[0].clone())]
+        );
+    }
+
+    #[test]
+    fn test_diff_committees() {
+        let members = (1..=3)
+            .map(|i| CommitteeMemberSol {
+                sigKeyAddress: Address::with_last_byte(i),
+                ..CommitteeMemberSol::random()
+            })
+            .collect::<Vec<_>>();
+        let old = CommitteeSol {
+            id: 1,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members: members.clone(),
+        };
+
+        // order doesn't matter
+        let mut reordered = old.clone();
+        reordered.members.reverse();
+        assert!(diff_committees(&old, &reordered).is_empty());
+
+        // key rotation keeps the member, unlike matching by `sigKey`
+        let mut rotated = old.clone();
+        rotated.members[1].sigKey = CommitteeMemberSol::random().sigKey;
+        rotated.members[1].dhKey = CommitteeMemberSol::random().dhKey;
+        let diff = diff_committees(&old, &rotated);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(
+            diff.changed,
+            [(members[1].clone(), rotated.members[1].clone())]
+        );
+        assert_eq!(rotated.diff(&old).added, [rotated.members[1].clone()]);
+
+        // a full replacement shares no member
+        let replacement = CommitteeSol {
+            members: (4..6)
+                .map(|i| CommitteeMemberSol {
+                    sigKeyAddress: Address::with_last_byte(i),
+                    ..CommitteeMemberSol::random()
+                })
+                .collect(),
+            ..old.clone()
+        };
+        let diff = diff_committees(&old, &replacement);
+        assert_eq!(diff.added, replacement.members);
+        assert_eq!(diff.removed, members);
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn test_diff_committees_without_addresses() {
+        let members = (0..2)
+            .map(|_| CommitteeMemberSol {
+                sigKeyAddress: Address::ZERO,
+                ..CommitteeMemberSol::random()
+            })
+            .collect::<Vec<_>>();
+        let old = CommitteeSol {
+            id: 1,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members: members.clone(),
+        };
+        let mut reordered = old.clone();
+        reordered.members.reverse();
+        assert!(diff_committees(&old, &reordered).is_empty());
+
+        // the zero address doesn't make the members one
+        let mut new = old.clone();
+        new.members.remove(0);
+        new.members[0].networkAddress = "10.0.0.1:8000".to_string();
+        let diff = diff_committees(&old, &new);
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.removed, [members[0].clone()]);
+        assert_eq!(diff.changed, [(members[1].clone(), new.members[0].clone())]);
+
+        // without an address, a key rotation is a new member
+        let mut rotated = old.clone();
+        rotated.members[1].sigKey = CommitteeMemberSol::random().sigKey;
+        let diff = diff_committees(&old, &rotated);
+        assert_eq!(diff.added, [rotated.members[1].clone()]);
+        assert_eq!(diff.removed, [members[1].clone()]);
+        assert!(diff.changed.is_empty());
+    }
+
+    #[test]
+    fn test_member_index() {
+        let mut committee = CommitteeSol {
+            id: 0,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(1),
+            members: (1..=3)
+                .map(|i| CommitteeMemberSol {
+                    sigKeyAddress: Address::with_last_byte(i),
+                    ..CommitteeMemberSol::random()
+                })
+                .collect(),
+        };
+        assert_eq!(
+            member_index(&committee, Address::with_last_byte(2)),
+            Some(1)
+        );
+        assert_eq!(member_index(&committee, Address::with_last_byte(4)), None);
+
+        committee.members[0].sigKeyAddress = Address::ZERO;
+        assert_eq!(member_index(&committee, Address::ZERO), None);
+    }
+
+    #[test]
+    fn test_display() {
+        let member = CommitteeMemberSol {
+            sigKey: Bytes::from((0..32).collect::<Vec<u8>>()),
+            dhKey: Bytes::from(vec![0xab; 32]),
+            dkgKey: Bytes::from_static(&[1, 2, 3]),
+            sigKeyAddress: "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"
+                .parse()
+                .unwrap(),
+            networkAddress: "10.0.0.1:8000".to_string(),
+            batchPosterAddress: "[::1]:9000".to_string(),
+        };
+        assert_eq!(
+            member.to_string(),
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed sig=0x00010203…1e1f dh=0xabababab…abab \
+             dkg=0x010203 net=10.0.0.1:8000 batch=[::1]:9000"
+        );
+
+        let committee = CommitteeSol {
+            id: 3,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members: vec![member.clone(), member.clone()],
+        };
+        assert_eq!(
+            committee.to_string(),
+            format!("committee 3 effective at 100 (block 10), 2 members: [{member}; {member}]")
+        );
+        let empty = CommitteeSol {
+            members: vec![],
+            ..committee
+        };
+        assert_eq!(
+            empty.to_string(),
+            "committee 3 effective at 100 (block 10), 0 members: []"
+        );
+    }
+
+    #[test]
+    fn test_identity_hash() {
+        let members = (0..4)
+            .map(|_| CommitteeMemberSol::random())
+            .collect::<Vec<_>>();
+        let committee = CommitteeSol {
+            id: 1,
+            effectiveTimestamp: 100,
+            registeredBlockNumber: U256::from(10),
+            members: members.clone(),
+        };
+
+        // id, block number and member order don't contribute
+        let mut reregistered = committee.clone();
+        reregistered.id = 7;
+        reregistered.registeredBlockNumber = U256::from(70);
+        reregistered.members.reverse();
+        assert_eq!(committee.identity_hash(), reregistered.identity_hash());
+
+        let mut later = committee.clone();
+        later.effectiveTimestamp = 101;
+        assert_ne!(committee.identity_hash(), later.identity_hash());
+
+        let mut changed = committee.clone();
+        changed.members[0] = CommitteeMemberSol::random();
+        assert_ne!(committee.identity_hash(), changed.identity_hash());
+    }
+}
@@ -0,0 +1,295 @@
+//! Interchangeable backends for reading committees
+//!
+//! [`CommitteeSource`] is implemented by the [`KeyManagerInstance`] itself, reading from the
+//! chain, and by [`SubgraphCommitteeSource`], querying a GraphQL indexer of the KeyManager's
+//! events, which is typically much faster for history queries.
+//!
+//! The indexer is expected to serve this schema (as generated by The Graph, where `BigInt`s are
+//! decimal strings and `Bytes` are 0x-prefixed hex strings):
+//!
+//! ```graphql
+//! type Committee {
+//!   id: ID!
+//!   committeeId: BigInt!
+//!   effectiveTimestamp: BigInt!
+//!   registeredBlockNumber: BigInt!
+//!   members(orderBy: index): [CommitteeMember!]!
+//! }
+//!
+//! type CommitteeMember {
+//!   index: Int!
+//!   sigKey: Bytes!
+//!   dhKey: Bytes!
+//!   dkgKey: Bytes!
+//!   sigKeyAddress: Bytes!
+//!   networkAddress: String!
+//!   batchPosterAddress: String!
+//! }
+//!
+//! type Query {
+//!   committees(first: Int, where: Committee_filter, orderBy: Committee_orderBy,
+//!              orderDirection: OrderDirection): [Committee!]!
+//! }
+//! ```
+//!
+//! with `committeeId_gte` and `committeeId_lt` filters. Committees pruned on chain are expected to
+//! be removed from the index as well, so both backends agree on what `committee` returns.
+
+use std::ops::Range;
+
+use alloy::{
+    primitives::{Address, Bytes, U256},
+    providers::Provider,
+    transports::http::reqwest::{self, Url, header::CONTENT_TYPE},
+};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::json;
+
+use crate::{CommitteeMemberSol, CommitteeSol, KeyManager::KeyManagerInstance, reader};
+
+/// Read access to registered committees
+pub trait CommitteeSource: Sync {
+    /// committee `id`, `None` if it was never registered or has been pruned
+    fn committee(&self, id: u64) -> impl Future<Output = Result<Option<CommitteeSol>>> + Send;
+
+    /// id the next registered committee will get, i.e. the number of committees ever registered
+    fn next_committee_id(&self) -> impl Future<Output = Result<u64>> + Send;
+
+    /// the stored committees with ids in `ids`, in id order
+    fn committees(
+        &self,
+        ids: Range<u64>,
+    ) -> impl Future<Output = Result<Vec<CommitteeSol>>> + Send {
+        async move {
+            let mut committees = Vec::new();
+            for id in ids {
+                committees.extend(self.committee(id).await?);
+            }
+            Ok(committees)
+        }
+    }
+}
+
+impl<P: Provider> CommitteeSource for KeyManagerInstance<P> {
+    async fn committee(&self, id: u64) -> Result<Option<CommitteeSol>> {
+        Ok(reader::try_committee(self, id).await?)
+    }
+
+    async fn next_committee_id(&self) -> Result<u64> {
+        Ok(self.nextCommitteeId().call().await?)
+    }
+}
+
+const COMMITTEE_FIELDS: &str = "committeeId effectiveTimestamp registeredBlockNumber \
+    members(orderBy: index) { sigKey dhKey dkgKey sigKeyAddress networkAddress batchPosterAddress }";
+
+/// Page size of range queries, The Graph's maximum for `first`
+const PAGE_SIZE: u64 = 1000;
+
+/// Committees read from a GraphQL indexer serving the schema in the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct SubgraphCommitteeSource {
+    client: reqwest::Client,
+    url: Url,
+}
+
+impl SubgraphCommitteeSource {
+    pub fn new(url: Url) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+        }
+    }
+
+    async fn query<T: DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<T> {
+        let body = json!({ "query": query, "variables": variables });
+        let resp = self
+            .client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body)?)
+            .send()
+            .await?
+            .error_for_status()?;
+        let resp: GraphQlResponse<T> =
+            serde_json::from_slice(&resp.bytes().await?).context("malformed GraphQL response")?;
+        if let Some(errors) = resp.errors.filter(|e| !e.is_empty()) {
+            let messages = errors.into_iter().map(|e| e.message).collect::<Vec<_>>();
+            bail!("GraphQL query failed: {}", messages.join("; "));
+        }
+        resp.data.context("GraphQL response without data")
+    }
+
+    async fn committees_where(
+        &self,
+        filter: serde_json::Value,
+        first: u64,
+        desc: bool,
+    ) -> Result<Vec<CommitteeSol>> {
+        let query = format!(
+            "query($where: Committee_filter, $first: Int) {{ committees(where: $where, \
+             first: $first, orderBy: committeeId, orderDirection: {}) {{ {COMMITTEE_FIELDS} }} }}",
+            if desc { "desc" } else { "asc" }
+        );
+        let data: CommitteesData = self
+            .query(&query, json!({ "where": filter, "first": first }))
+            .await?;
+        data.committees.into_iter().map(TryInto::try_into).collect()
+    }
+}
+
+impl CommitteeSource for SubgraphCommitteeSource {
+    async fn committee(&self, id: u64) -> Result<Option<CommitteeSol>> {
+        let filter = json!({ "committeeId": id.to_string() });
+        Ok(self.committees_where(filter, 1, false).await?.pop())
+    }
+
+    async fn next_committee_id(&self) -> Result<u64> {
+        let latest = self.committees_where(json!({}), 1, true).await?;
+        Ok(latest.first().map_or(0, |c| c.id + 1))
+    }
+
+    async fn committees(&self, ids: Range<u64>) -> Result<Vec<CommitteeSol>> {
+        let mut committees = Vec::new();
+        let mut from = ids.start;
+        while from < ids.end {
+            let filter = json!({
+                "committeeId_gte": from.to_string(),
+                "committeeId_lt": ids.end.to_string(),
+            });
+            let page = self.committees_where(filter, PAGE_SIZE, false).await?;
+            let Some(last) = page.last() else { break };
+            from = last.id + 1;
+            let full = page.len() as u64 == PAGE_SIZE;
+            committees.extend(page);
+            if !full {
+                break;
+            }
+        }
+        Ok(committees)
+    }
+}
+
+#[derive(Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    errors: Option<Vec<GraphQlError>>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct CommitteesData {
+    committees: Vec<SubgraphCommittee>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubgraphCommittee {
+    committee_id: String,
+    effective_timestamp: String,
+    registered_block_number: String,
+    members: Vec<SubgraphMember>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubgraphMember {
+    sig_key: Bytes,
+    dh_key: Bytes,
+    dkg_key: Bytes,
+    sig_key_address: Address,
+    network_address: String,
+    batch_poster_address: String,
+}
+
+impl TryFrom<SubgraphCommittee> for CommitteeSol {
+    type Error = anyhow::Error;
+
+    fn try_from(c: SubgraphCommittee) -> Result<Self> {
+        Ok(Self {
+            id: c.committee_id.parse().context("invalid committeeId")?,
+            effectiveTimestamp: c
+                .effective_timestamp
+                .parse()
+                .context("invalid effectiveTimestamp")?,
+            registeredBlockNumber: c
+                .registered_block_number
+                .parse::<U256>()
+                .context("invalid registeredBlockNumber")?,
+            members: c
+                .members
+                .into_iter()
+                .map(|m| CommitteeMemberSol {
+                    sigKey: m.sig_key,
+                    dhKey: m.dh_key,
+                    dkgKey: m.dkg_key,
+                    sigKeyAddress: m.sig_key_address,
+                    networkAddress: m.network_address,
+                    batchPosterAddress: m.batch_poster_address,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommitteeSource, SubgraphCommitteeSource};
+    use crate::{CommitteeMemberSol, test_util::http};
+    use alloy::primitives::U256;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_subgraph_committee_source() {
+        let member = CommitteeMemberSol::random();
+        let committee = json!({
+            "committeeId": "3",
+            "effectiveTimestamp": "100",
+            "registeredBlockNumber": "42",
+            "members": [{
+                "sigKey": member.sigKey,
+                "dhKey": member.dhKey,
+                "dkgKey": member.dkgKey,
+                "sigKeyAddress": member.sigKeyAddress,
+                "networkAddress": member.networkAddress,
+                "batchPosterAddress": member.batchPosterAddress,
+            }],
+        });
+        let responses = vec![
+            json!({ "data": { "committees": [committee] } }),
+            json!({ "data": { "committees": [committee] } }),
+            json!({ "data": { "committees": [] } }),
+            json!({ "errors": [{ "message": "indexer is syncing" }] }),
+        ];
+
+        let (listener, url) = http::listen("/graphql").await;
+        let requests = responses.len();
+        let mut responses = responses.into_iter();
+        let server = tokio::spawn(http::serve(listener, requests, move |_| {
+            (200, responses.next().unwrap().to_string())
+        }));
+        let source = SubgraphCommitteeSource::new(url);
+
+        let c = source.committee(3).await.unwrap().unwrap();
+        assert_eq!((c.id, c.effectiveTimestamp), (3, 100));
+        assert_eq!(c.registeredBlockNumber, U256::from(42));
+        assert_eq!(c.members, [member]);
+        assert_eq!(source.next_committee_id().await.unwrap(), 4);
+        assert!(source.committee(5).await.unwrap().is_none());
+        let err = source.committee(3).await.unwrap_err();
+        assert!(err.to_string().contains("indexer is syncing"));
+
+        let requests = server.await.unwrap();
+        assert!(requests[0].body.contains(r#""committeeId":"3""#));
+        assert!(requests[1].body.contains("orderDirection: desc"));
+    }
+}
@@ -0,0 +1,550 @@
+//! Adapters over `CommitteeCreated` event streams
+
+use std::{
+    collections::{HashSet, VecDeque},
+    time::Duration,
+};
+
+use crate::time::Instant;
+use alloy::{eips::BlockId, providers::Provider, rpc::types::Log};
+use futures::{Stream, StreamExt};
+use tracing::{debug, error, warn};
+
+use crate::{
+    CommitteeDiff, CommitteeMemberSol, CommitteeSol,
+    KeyManager::{CommitteeCreated, KeyManagerInstance},
+    reader::{try_committee, try_committee_at},
+};
+
+/// A `CommitteeCreated` event with the committee it announced.
+///
+/// The event carries only the committee id. The effective timestamp and members have to be read
+/// from the contract, with [`CommitteeCreatedInfo::fetch`] or for a whole stream with
+/// [`with_committees`]; until then [`CommitteeCreatedInfo::needs_fetch`] is true and the
+/// committee accessors return `None`.
+#[derive(Debug, Clone)]
+pub struct CommitteeCreatedInfo {
+    log: Log<CommitteeCreated>,
+    committee: Option<CommitteeSol>,
+}
+
+impl CommitteeCreatedInfo {
+    /// the created committee's id
+    pub fn id(&self) -> u64 {
+        self.log.data().id
+    }
+
+    /// the event's log, with its block and transaction
+    pub fn log(&self) -> &Log<CommitteeCreated> {
+        &self.log
+    }
+
+    /// whether the committee still has to be read from the contract
+    pub fn needs_fetch(&self) -> bool {
+        self.committee.is_none()
+    }
+
+    /// the committee, if fetched
+    pub fn committee(&self) -> Option<&CommitteeSol> {
+        self.committee.as_ref()
+    }
+
+    /// when the committee takes over, if fetched
+    pub fn effective_timestamp(&self) -> Option<u64> {
+        self.committee.as_ref().map(|c| c.effectiveTimestamp)
+    }
+
+    /// the committee's members, if fetched
+    pub fn members(&self) -> Option<&[CommitteeMemberSol]> {
+        self.committee.as_ref().map(|c| c.members.as_slice())
+    }
+
+    /// Read the committee from `contract`, unless already fetched.
+    ///
+    /// `None` if the contract doesn't store it (anymore), e.g. it has been pruned since the event.
+    pub async fn fetch<P: Provider>(
+        &mut self,
+        contract: &KeyManagerInstance<P>,
+    ) -> Result<Option<&CommitteeSol>, alloy::contract::Error> {
+        if self.committee.is_none() {
+            self.committee = try_committee(contract, self.id()).await?;
+        }
+        Ok(self.committee.as_ref())
+    }
+}
+
+impl From<Log<CommitteeCreated>> for CommitteeCreatedInfo {
+    fn from(log: Log<CommitteeCreated>) -> Self {
+        Self {
+            log,
+            committee: None,
+        }
+    }
+}
+
+/// Read the committee of each event in `events` from `contract`, one at a time in order.
+///
+/// Committees that can't be read (e.g. pruned, or on an RPC error) are logged and their events
+/// yielded with [`CommitteeCreatedInfo::needs_fetch`] still true.
+pub fn with_committees<'a, S, P>(
+    events: S,
+    contract: &'a KeyManagerInstance<P>,
+) -> impl Stream<Item = CommitteeCreatedInfo> + 'a
+where
+    S: Stream<Item = Log<CommitteeCreated>> + 'a,
+    P: Provider,
+{
+    events.then(move |log| async move {
+        let mut info = CommitteeCreatedInfo::from(log);
+        let id = info.id();
+        match info.fetch(contract).await {
+            Ok(Some(_)) => {}
+            Ok(None) => warn!(%id, "created committee is not stored in the contract"),
+            Err(err) => error!(%id, %err, "failed to read created committee"),
+        }
+        info
+    })
+}
+
+/// Item of a gap-checked committee event stream
+#[derive(Debug, Clone)]
+pub enum StreamItem {
+    /// a `CommitteeCreated` event with the next expected id
+    Created(Log<CommitteeCreated>),
+    /// an event skipped ahead of the `expected` id, the missing committees follow as
+    /// [`StreamItem::Backfilled`] before the event with id `got`
+    Gap { expected: u64, got: u64 },
+    /// a missing committee, read from the contract
+    Backfilled(CommitteeSol),
+}
+
+/// Check that committee ids in `events` are contiguous, starting after `last_seen`
+/// (or at whatever id arrives first if `None`).
+///
+/// On a jump ahead, yields a [`StreamItem::Gap`] and reads the skipped committees from `contract`.
+/// Committees that can't be read (e.g. pruned) are logged. Events at or below the last seen id,
+/// e.g. replayed or reordered ones, were already delivered and are dropped.
+pub fn detect_gaps<'a, S, P>(
+    events: S,
+    contract: &'a KeyManagerInstance<P>,
+    last_seen: Option<u64>,
+) -> impl Stream<Item = StreamItem> + 'a
+where
+    S: Stream<Item = Log<CommitteeCreated>> + 'a,
+    P: Provider,
+{
+    let state = GapState {
+        events: Box::pin(events),
+        contract,
+        next: last_seen.map(|id| id + 1),
+        pending: VecDeque::new(),
+    };
+    futures::stream::unfold(state, |mut state| async move {
+        let item = state.next_item().await?;
+        Some((item, state))
+    })
+}
+
+struct GapState<'a, S, P> {
+    events: std::pin::Pin<Box<S>>,
+    contract: &'a KeyManagerInstance<P>,
+    /// next expected id
+    next: Option<u64>,
+    pending: VecDeque<StreamItem>,
+}
+
+impl<S, P> GapState<'_, S, P>
+where
+    S: Stream<Item = Log<CommitteeCreated>>,
+    P: Provider,
+{
+    async fn next_item(&mut self) -> Option<StreamItem> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(item);
+            }
+            let log = self.events.next().await?;
+            let got = log.data().id;
+            let expected = self.next.unwrap_or(got);
+            if got < expected {
+                debug!(%got, %expected, "dropping already delivered committee event");
+                continue;
+            }
+            self.next = Some(got + 1);
+            if got == expected {
+                return Some(StreamItem::Created(log));
+            }
+
+            warn!(%expected, %got, "gap in committee ids, backfilling");
+            self.pending.push_back(StreamItem::Gap { expected, got });
+            for id in expected..got {
+                match try_committee(self.contract, id).await {
+                    Ok(Some(committee)) => {
+                        self.pending.push_back(StreamItem::Backfilled(committee))
+                    }
+                    Ok(None) => error!(%id, "missing committee is not stored in the contract"),
+                    Err(err) => error!(%id, %err, "failed to backfill committee"),
+                }
+            }
+            self.pending.push_back(StreamItem::Created(log));
+        }
+    }
+}
+
+/// Item of a duplicate-checked committee event stream
+#[derive(Debug, Clone)]
+pub enum IdCheckedItem {
+    /// the first event with this committee id
+    New(Log<CommitteeCreated>),
+    /// another event with an id that was already seen, not to be processed again
+    Duplicate(Log<CommitteeCreated>),
+}
+
+/// Flag `CommitteeCreated` events whose committee id was already seen.
+///
+/// The contract assigns every id once, so a second event with the same id points at an RPC (or
+/// contract) bug. Unlike [`crate::provider::dedup_logs`], which drops replays of the same log,
+/// this is keyed on the id only; run `dedup_logs` first so that plain replays are dropped
+/// silently and only conflicting events are flagged.
+pub fn flag_duplicate_ids<S>(events: S) -> impl Stream<Item = IdCheckedItem>
+where
+    S: Stream<Item = Log<CommitteeCreated>>,
+{
+    let mut seen = HashSet::new();
+    events.map(move |log| {
+        let id = log.data().id;
+        if seen.insert(id) {
+            IdCheckedItem::New(log)
+        } else {
+            warn!(
+                %id,
+                block = ?log.block_number,
+                log_index = ?log.log_index,
+                "duplicate committee created event"
+            );
+            IdCheckedItem::Duplicate(log)
+        }
+    })
+}
+
+/// Only the events of committees with an id of at least `min_id`, e.g. to resume after the last
+/// processed committee. Events of earlier committees, also replayed or late ones arriving in
+/// between, are dropped.
+pub fn from_committee_id<S>(events: S, min_id: u64) -> impl Stream<Item = Log<CommitteeCreated>>
+where
+    S: Stream<Item = Log<CommitteeCreated>>,
+{
+    events.filter(move |log| {
+        let id = log.data().id;
+        if id < min_id {
+            debug!(%id, %min_id, "dropping event of an earlier committee");
+        }
+        futures::future::ready(id >= min_id)
+    })
+}
+
+/// Rate limit `events` to at most one item per `interval`, keeping only the latest.
+///
+/// An item arriving after a quiet `interval` is yielded right away; items arriving sooner are
+/// held until the interval since the last yielded item passes, and only the most recent of them
+/// is yielded. For consumers that only care about the current committee, not every intermediate
+/// one, e.g. during a bulk seed. The last item is always yielded before the stream ends.
+pub fn coalesce_latest<S: Stream>(events: S, interval: Duration) -> impl Stream<Item = S::Item> {
+    let state = (Box::pin(events), None::<Instant>, false);
+    futures::stream::unfold(state, move |(mut events, last, done)| async move {
+        if done {
+            return None;
+        }
+        let mut latest = events.next().await?;
+        if let Some(deadline) = last.map(|t| t + interval) {
+            loop {
+                match crate::time::timeout_at(deadline, events.next()).await {
+                    Ok(Some(item)) => latest = item,
+                    Ok(None) => return Some((latest, (events, last, true))),
+                    Err(_) => break,
+                }
+            }
+        }
+        Some((latest, (events, Some(Instant::now()), false)))
+    })
+}
+
+/// Call `callback` with the items of `events`, coalesced with [`coalesce_latest`] so that it
+/// fires at most once per `interval`, until the stream ends
+pub async fn on_latest<S, F, Fut>(events: S, interval: Duration, mut callback: F)
+where
+    S: Stream,
+    F: FnMut(S::Item) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let mut items = std::pin::pin!(coalesce_latest(events, interval));
+    while let Some(item) = items.next().await {
+        callback(item).await;
+    }
+}
+
+/// A committee of a stream with how its membership changed, see [`committee_diffs`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitteeChange {
+    pub committee: CommitteeSol,
+    /// [`CommitteeSol::diff`] from the committee before it in the stream, all members are
+    /// `added` for the first one
+    pub diff: CommitteeDiff,
+}
+
+/// Pair each of `committees` with its membership change from the one before it, e.g. to
+/// reconfigure along committee rotations without diffing by hand
+pub fn committee_diffs<S>(committees: S) -> impl Stream<Item = CommitteeChange>
+where
+    S: Stream<Item = CommitteeSol>,
+{
+    let mut previous = None::<CommitteeSol>;
+    committees.map(move |committee| {
+        let diff = match &previous {
+            Some(previous) => committee.diff(previous),
+            None => CommitteeDiff {
+                added: committee.members.clone(),
+                ..CommitteeDiff::default()
+            },
+        };
+        previous = Some(committee.clone());
+        CommitteeChange { committee, diff }
+    })
+}
+
+/// Committees with id `from_id` onwards, each yielded only once the block it was registered in is
+/// finalized, by polling the `finalized` block every `poll_interval`.
+///
+/// On chains without the `finalized` block tag every poll fails; failures are logged and retried,
+/// so such a stream never yields.
+pub fn finalized_committee_stream<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    from_id: u64,
+    poll_interval: Duration,
+) -> impl Stream<Item = CommitteeSol> + '_ {
+    let state = (from_id, VecDeque::new());
+    futures::stream::unfold(state, move |(mut next, mut ready)| async move {
+        loop {
+            if let Some(committee) = ready.pop_front() {
+                return Some((committee, (next, ready)));
+            }
+            match finalized_since(contract, next).await {
+                Ok((count, committees)) => {
+                    next = count;
+                    ready.extend(committees);
+                    if !ready.is_empty() {
+                        continue;
+                    }
+                }
+                Err(err) => warn!(%err, "failed to read finalized committees"),
+            }
+            crate::time::sleep(poll_interval).await;
+        }
+    })
+}
+
+/// The finalized committee count, and the committees from `next` up to it, pruned ones skipped
+async fn finalized_since<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    next: u64,
+) -> Result<(u64, Vec<CommitteeSol>), alloy::contract::Error> {
+    // pin the number, the finalized block may advance while reading
+    let Some(block) = contract.provider().get_block(BlockId::finalized()).await? else {
+        return Ok((next, Vec::new()));
+    };
+    let at = BlockId::number(block.header.number);
+    let count = contract.nextCommitteeId().block(at).call().await?;
+    let mut committees = Vec::new();
+    for id in next..count {
+        match try_committee_at(contract, id, at).await? {
+            Some(committee) => committees.push(committee),
+            None => debug!(%id, "skipping pruned committee"),
+        }
+    }
+    Ok((count.max(next), committees))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CommitteeCreatedInfo, IdCheckedItem, StreamItem, coalesce_latest, committee_diffs,
+        detect_gaps, flag_duplicate_ids, from_committee_id, on_latest, with_committees,
+    };
+    use crate::{CommitteeMemberSol, CommitteeSol, KeyManager, KeyManager::CommitteeCreated};
+    use alloy::{
+        primitives::{Address, U256},
+        rpc::types::Log,
+    };
+    use futures::StreamExt;
+    use std::time::Duration;
+
+    fn created(contract: Address, id: u64) -> Log<CommitteeCreated> {
+        Log {
+            inner: alloy::primitives::Log {
+                address: contract,
+                data: CommitteeCreated { id },
+            },
+            block_hash: None,
+            block_number: None,
+            block_timestamp: None,
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            removed: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_latest() {
+        let interval = Duration::from_millis(50);
+        // a burst: the first is yielded right away, the rest collapse into the last one
+        let burst = coalesce_latest(futures::stream::iter(0..5), interval)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(burst, [0, 4]);
+
+        // items further apart than the interval all come through
+        let spaced = futures::stream::iter([0, 1, 2]).then(|i| async move {
+            if i == 2 {
+                tokio::time::sleep(3 * interval).await;
+            }
+            i
+        });
+        let mut seen = Vec::new();
+        on_latest(spaced, interval, |i| {
+            seen.push(i);
+            async {}
+        })
+        .await;
+        assert_eq!(seen, [0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_committee_diffs() {
+        let members = (0..3)
+            .map(|_| CommitteeMemberSol::random())
+            .collect::<Vec<_>>();
+        let committee = |id, members: &[CommitteeMemberSol]| CommitteeSol {
+            id,
+            effectiveTimestamp: id,
+            registeredBlockNumber: U256::from(id),
+            members: members.to_vec(),
+        };
+        let mut moved = members[1].clone();
+        moved.networkAddress = "127.0.0.1:1".to_string();
+        let committees = vec![
+            committee(0, &members[..2]),
+            committee(1, &[moved.clone(), members[2].clone()]),
+        ];
+
+        let changes = committee_diffs(futures::stream::iter(committees.clone()))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(changes[0].committee, committees[0]);
+        assert_eq!(changes[0].diff.added, &members[..2]);
+        assert!(changes[0].diff.removed.is_empty());
+        assert_eq!(changes[1].diff.added, [members[2].clone()]);
+        assert_eq!(changes[1].diff.removed, [members[0].clone()]);
+        assert_eq!(changes[1].diff.changed, [(members[1].clone(), moved)]);
+    }
+
+    #[tokio::test]
+    async fn test_from_committee_id() {
+        let addr = Address::with_last_byte(1);
+        // older committees replayed in between newer ones
+        let events = futures::stream::iter([1, 3, 2, 4, 0, 3, 5].map(|id| created(addr, id)));
+        let ids = from_committee_id(events, 3)
+            .map(|log| log.data().id)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(ids, [3, 4, 3, 5]);
+
+        let events = futures::stream::iter([0, 1].map(|id| created(addr, id)));
+        assert_eq!(from_committee_id(events, 0).count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_flag_duplicate_ids() {
+        let addr = Address::with_last_byte(1);
+        let events = futures::stream::iter([0, 1, 0, 2, 1].map(|id| created(addr, id)));
+        let items = flag_duplicate_ids(events)
+            .map(|item| match item {
+                IdCheckedItem::New(log) => (log.data().id, false),
+                IdCheckedItem::Duplicate(log) => (log.data().id, true),
+            })
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            items,
+            [(0, false), (1, false), (0, true), (2, false), (1, true)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_detect_gaps() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        for i in 0..4 {
+            contract
+                .setNextCommittee(100 + i, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        }
+
+        // the event for 1 arrives late, after 2
+        let events = futures::stream::iter([0, 2, 1, 3].map(|id| created(addr, id)));
+        let items = detect_gaps(events, &contract, None)
+            .map(|item| match item {
+                StreamItem::Created(log) => format!("created {}", log.data().id),
+                StreamItem::Gap { expected, got } => format!("gap {expected} {got}"),
+                StreamItem::Backfilled(c) => format!("backfilled {}", c.id),
+            })
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            items,
+            [
+                "created 0",
+                "gap 1 2",
+                "backfilled 1",
+                "created 2",
+                "created 3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_committees() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let members = vec![CommitteeMemberSol::random(), CommitteeMemberSol::random()];
+        contract
+            .setNextCommittee(100, members.clone())
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+
+        let mut info = CommitteeCreatedInfo::from(created(addr, 0));
+        assert_eq!(info.id(), 0);
+        assert!(info.needs_fetch());
+        assert_eq!(info.effective_timestamp(), None);
+        assert_eq!(info.fetch(&contract).await.unwrap().unwrap().id, 0);
+        assert!(!info.needs_fetch());
+        assert_eq!(info.effective_timestamp(), Some(100));
+        assert_eq!(info.members().unwrap(), members.as_slice());
+
+        // 1 was never registered
+        let events = futures::stream::iter([0, 1].map(|id| created(addr, id)));
+        let infos = with_committees(events, &contract).collect::<Vec<_>>().await;
+        assert_eq!(infos[0].committee(), info.committee());
+        assert_eq!(infos[1].id(), 1);
+        assert!(infos[1].needs_fetch());
+    }
+}
@@ -0,0 +1,322 @@
+//! Deployment and event stream metrics
+//!
+//! With the `metrics` feature these are recorded through the [`metrics`](https://docs.rs/metrics)
+//! facade, so they end up in whatever recorder (e.g. a Prometheus exporter) the application
+//! installs. Without it the hooks below are empty and compile away.
+//!
+//! | name | kind | labels |
+//! |------|------|--------|
+//! | [`DEPLOYMENT_GAS_USED`] | histogram | `contract` |
+//! | [`DEPLOYMENT_DURATION`] | histogram (seconds) | `contract` |
+//! | [`EVENTS_RECEIVED`] | counter | `event` |
+//! | [`EVENT_STREAM_RECONNECTS`] | counter | |
+//! | [`EVENT_DECODE_FAILURES`] | counter | `event` |
+//!
+//! With the `trace-calldata` feature, every contract call the crate's helpers send (committee
+//! registrations, initialization, upgrades, timelock operations) is also logged at `debug`
+//! level: target, selector, hex calldata and, for KeyManager calls, the decoded arguments, ready
+//! to be replayed with e.g. `cast call <to> <calldata>`. Only calldata is logged, never keys or
+//! signed transactions. Off by default, as calldata may be sensitive to the deployment.
+
+#[cfg(feature = "metrics")]
+use crate::time::Instant;
+
+use alloy::{
+    contract::{CallBuilder, CallDecoder},
+    network::Network,
+    primitives::Address,
+    providers::Provider,
+};
+
+pub const DEPLOYMENT_GAS_USED: &str = "timeboost_contract_deployment_gas_used";
+pub const DEPLOYMENT_DURATION: &str = "timeboost_contract_deployment_duration_seconds";
+pub const EVENTS_RECEIVED: &str = "timeboost_contract_events_received_total";
+pub const EVENT_STREAM_RECONNECTS: &str = "timeboost_contract_event_stream_reconnects_total";
+pub const EVENT_DECODE_FAILURES: &str = "timeboost_contract_event_decode_failures_total";
+
+/// Register descriptions of the above with the installed recorder
+#[cfg(feature = "metrics")]
+pub fn describe() {
+    use metrics::{Unit, describe_counter, describe_histogram};
+    describe_histogram!(DEPLOYMENT_GAS_USED, "gas used by a contract deployment");
+    describe_histogram!(
+        DEPLOYMENT_DURATION,
+        Unit::Seconds,
+        "time from sending a deployment until it is confirmed"
+    );
+    describe_counter!(EVENTS_RECEIVED, "events delivered by event streams");
+    describe_counter!(
+        EVENT_STREAM_RECONNECTS,
+        "times an event stream lost its subscription or failed to renew it"
+    );
+    describe_counter!(EVENT_DECODE_FAILURES, "logs event streams failed to decode");
+}
+
+/// Measures a deployment, zero-sized without the `metrics` feature
+pub(crate) struct Stopwatch {
+    #[cfg(feature = "metrics")]
+    started: Instant,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Self {
+            #[cfg(feature = "metrics")]
+            started: Instant::now(),
+        }
+    }
+}
+
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn deployed(contract: &str, gas_used: u64, stopwatch: Stopwatch) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [("contract", contract.to_owned())];
+        metrics::histogram!(DEPLOYMENT_GAS_USED, &labels).record(gas_used as f64);
+        metrics::histogram!(DEPLOYMENT_DURATION, &labels).record(stopwatch.started.elapsed());
+    }
+}
+
+// event streams are native only
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn event_received(event: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(EVENTS_RECEIVED, "event" => event).increment(1);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn event_stream_reconnect() {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(EVENT_STREAM_RECONNECTS).increment(1);
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn event_decode_failure(event: &'static str) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(EVENT_DECODE_FAILURES, "event" => event).increment(1);
+}
+
+/// `call` to `to`, having logged its calldata with the `trace-calldata` feature
+pub(crate) fn traced<P: Provider<N>, D: CallDecoder, N: Network>(
+    to: Address,
+    call: CallBuilder<P, D, N>,
+) -> CallBuilder<P, D, N> {
+    calldata(to, call.calldata());
+    call
+}
+
+#[cfg_attr(not(feature = "trace-calldata"), allow(unused_variables))]
+fn calldata(to: Address, input: &[u8]) {
+    #[cfg(feature = "trace-calldata")]
+    {
+        use alloy::{hex, sol_types::SolInterface};
+        let selector = input.get(..4).map(hex::encode_prefixed).unwrap_or_default();
+        let calldata = hex::encode_prefixed(input);
+        match crate::KeyManager::KeyManagerCalls::abi_decode(input) {
+            Ok(call) => {
+                let args = serde_json::to_string(&call).unwrap_or_default();
+                tracing::debug!(%to, %selector, %args, %calldata, "sending KeyManager call");
+            }
+            Err(_) => tracing::debug!(%to, %selector, %calldata, "sending call"),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "trace-calldata"))]
+mod calldata_tests {
+    use std::sync::{Arc, Mutex};
+
+    use alloy::{
+        primitives::Address,
+        providers::ProviderBuilder,
+        sol_types::{SolCall, SolInterface},
+    };
+    use tracing::{
+        Event, Metadata, Subscriber,
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+    };
+
+    use crate::{CommitteeMemberSol, KeyManager};
+
+    /// name and value of an event's fields
+    #[derive(Default)]
+    struct Fields(Vec<(String, String)>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{value:?}")));
+        }
+    }
+
+    /// the fields of every event
+    #[derive(Default, Clone)]
+    struct EventLog(Arc<Mutex<Vec<Fields>>>);
+
+    impl Subscriber for EventLog {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(1)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, event: &Event<'_>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields);
+        }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn test_traced_calldata() {
+        let provider = ProviderBuilder::new().connect_http("http://localhost:1".parse().unwrap());
+        let to = Address::with_last_byte(1);
+        let contract = KeyManager::new(to, &provider);
+        let members = vec![CommitteeMemberSol::random()];
+        let log = EventLog::default();
+        tracing::subscriber::with_default(log.clone(), || {
+            let _ = super::traced(to, contract.setNextCommittee(100, members.clone()));
+            super::calldata(to, &[0xde, 0xad, 0xbe, 0xef]);
+        });
+
+        let events = log.0.lock().unwrap();
+        let field = |event: usize, name: &str| {
+            events[event]
+                .0
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, v)| v.clone())
+        };
+        let input = KeyManager::setNextCommitteeCall {
+            effectiveTimestamp: 100,
+            members,
+        }
+        .abi_encode();
+        assert_eq!(field(0, "message").unwrap(), "sending KeyManager call");
+        assert_eq!(field(0, "to").unwrap(), to.to_string());
+        assert_eq!(
+            field(0, "selector").unwrap(),
+            alloy::hex::encode_prefixed(KeyManager::setNextCommitteeCall::SELECTOR)
+        );
+        assert_eq!(
+            field(0, "calldata").unwrap(),
+            alloy::hex::encode_prefixed(&input)
+        );
+        let args = field(0, "args").unwrap();
+        assert!(args.contains("setNextCommittee"), "{args}");
+        assert!(KeyManager::KeyManagerCalls::abi_decode(&input).is_ok());
+
+        // not a KeyManager call: raw calldata only
+        assert_eq!(field(1, "message").unwrap(), "sending call");
+        assert_eq!(field(1, "calldata").unwrap(), "0xdeadbeef");
+        assert!(field(1, "args").is_none());
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU64, Ordering},
+        },
+    };
+
+    use metrics::{
+        Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString,
+        Unit,
+    };
+
+    #[derive(Default)]
+    struct Samples(Mutex<Vec<f64>>);
+
+    impl HistogramFn for Samples {
+        fn record(&self, value: f64) {
+            self.0.lock().unwrap().push(value);
+        }
+    }
+
+    /// keeps every metric under `name{k=v,..}`
+    #[derive(Default)]
+    struct TestRecorder {
+        counters: Mutex<BTreeMap<String, Arc<AtomicU64>>>,
+        histograms: Mutex<BTreeMap<String, Arc<Samples>>>,
+    }
+
+    fn key_string(key: &Key) -> String {
+        let labels: Vec<_> = key
+            .labels()
+            .map(|l| format!("{}={}", l.key(), l.value()))
+            .collect();
+        format!("{}{{{}}}", key.name(), labels.join(","))
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            let mut counters = self.counters.lock().unwrap();
+            Counter::from_arc(counters.entry(key_string(key)).or_default().clone())
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            let mut histograms = self.histograms.lock().unwrap();
+            Histogram::from_arc(histograms.entry(key_string(key)).or_default().clone())
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        let recorder = TestRecorder::default();
+        metrics::with_local_recorder(&recorder, || {
+            super::describe();
+            super::deployed("KeyManager", 1000, super::Stopwatch::start());
+            super::event_received("CommitteeCreated(uint64)");
+            super::event_received("CommitteeCreated(uint64)");
+            super::event_stream_reconnect();
+            super::event_decode_failure("KeyManager");
+        });
+
+        let counters = recorder.counters.lock().unwrap();
+        let count = |key: &str| counters[key].load(Ordering::Relaxed);
+        assert_eq!(
+            count("timeboost_contract_events_received_total{event=CommitteeCreated(uint64)}"),
+            2
+        );
+        assert_eq!(
+            count("timeboost_contract_event_stream_reconnects_total{}"),
+            1
+        );
+        assert_eq!(
+            count("timeboost_contract_event_decode_failures_total{event=KeyManager}"),
+            1
+        );
+
+        let histograms = recorder.histograms.lock().unwrap();
+        let gas = histograms["timeboost_contract_deployment_gas_used{contract=KeyManager}"]
+            .0
+            .lock()
+            .unwrap()
+            .clone();
+        assert_eq!(gas, [1000.0]);
+        assert!(
+            histograms.contains_key(
+                "timeboost_contract_deployment_duration_seconds{contract=KeyManager}"
+            )
+        );
+    }
+}
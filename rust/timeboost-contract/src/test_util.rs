@@ -0,0 +1,248 @@
+//! Random committee members for tests, also outside this crate with the `test-util` feature
+//!
+//! [`MemberGenerator`] draws keys of configurable lengths and socket addresses from configurable
+//! ranges. Seeded with [`MemberGenerator::seeded`], it yields the same members on every run, e.g.
+//! to reproduce a failing property test.
+
+use std::{net::Ipv4Addr, ops::RangeInclusive};
+
+use alloy::primitives::{Address, Bytes};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::{CommitteeMemberSol, DH_KEY_LEN, DKG_KEY_LEN, SIG_KEY_LEN};
+
+/// Generates [`CommitteeMemberSol`]s, see the [module docs](self).
+///
+/// By default keys have their expected lengths and both addresses are `127.0.0.1` with any
+/// port, like [`CommitteeMemberSol::random`]. `sigKeyAddress` is always zero.
+#[derive(Debug, Clone)]
+pub struct MemberGenerator<R = StdRng> {
+    rng: R,
+    sig_key_len: usize,
+    dh_key_len: usize,
+    dkg_key_len: usize,
+    ips: RangeInclusive<Ipv4Addr>,
+    ports: RangeInclusive<u16>,
+}
+
+impl MemberGenerator<StdRng> {
+    /// a generator yielding the same members for the same `seed`
+    pub fn seeded(seed: u64) -> Self {
+        Self::new(StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<R: Rng> MemberGenerator<R> {
+    pub fn new(rng: R) -> Self {
+        Self {
+            rng,
+            sig_key_len: SIG_KEY_LEN,
+            dh_key_len: DH_KEY_LEN,
+            dkg_key_len: DKG_KEY_LEN,
+            ips: Ipv4Addr::LOCALHOST..=Ipv4Addr::LOCALHOST,
+            ports: 0..=u16::MAX,
+        }
+    }
+
+    /// length of all three keys, e.g. a wrong one to test validation
+    pub fn with_key_len(self, len: usize) -> Self {
+        self.with_sig_key_len(len)
+            .with_dh_key_len(len)
+            .with_dkg_key_len(len)
+    }
+
+    pub fn with_sig_key_len(mut self, len: usize) -> Self {
+        self.sig_key_len = len;
+        self
+    }
+
+    pub fn with_dh_key_len(mut self, len: usize) -> Self {
+        self.dh_key_len = len;
+        self
+    }
+
+    pub fn with_dkg_key_len(mut self, len: usize) -> Self {
+        self.dkg_key_len = len;
+        self
+    }
+
+    /// IPs of both addresses, must not be empty
+    pub fn with_ips(mut self, ips: RangeInclusive<Ipv4Addr>) -> Self {
+        self.ips = ips;
+        self
+    }
+
+    /// ports of both addresses, must not be empty; exclude 0 for members that pass
+    /// [`CommitteeMemberSol::validate`]
+    pub fn with_ports(mut self, ports: RangeInclusive<u16>) -> Self {
+        self.ports = ports;
+        self
+    }
+
+    /// The next member. Panics if the IP or port range is empty.
+    pub fn member(&mut self) -> CommitteeMemberSol {
+        CommitteeMemberSol {
+            sigKey: self.key(self.sig_key_len),
+            dhKey: self.key(self.dh_key_len),
+            dkgKey: self.key(self.dkg_key_len),
+            networkAddress: self.addr(),
+            batchPosterAddress: self.addr(),
+            sigKeyAddress: Address::default(),
+        }
+    }
+
+    /// the next `n` members
+    pub fn members(&mut self, n: usize) -> Vec<CommitteeMemberSol> {
+        (0..n).map(|_| self.member()).collect()
+    }
+
+    fn key(&mut self, len: usize) -> Bytes {
+        let mut key = vec![0; len];
+        self.rng.fill(&mut key[..]);
+        key.into()
+    }
+
+    fn addr(&mut self) -> String {
+        let (lo, hi) = (u32::from(*self.ips.start()), u32::from(*self.ips.end()));
+        let ip = Ipv4Addr::from(self.rng.random_range(lo..=hi));
+        let port = self.rng.random_range(self.ports.clone());
+        format!("{ip}:{port}")
+    }
+}
+
+/// A minimal HTTP/1.1 server for the tests of this crate's HTTP clients, e.g. of the Safe
+/// transaction service or webhooks, answering one request per connection
+#[cfg(test)]
+pub(crate) mod http {
+    use alloy::transports::http::reqwest::{StatusCode, Url};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    /// A request received by [`serve`]
+    #[derive(Debug, Clone)]
+    pub(crate) struct Request {
+        /// request line and headers
+        pub head: String,
+        pub body: String,
+    }
+
+    impl Request {
+        /// the body as JSON, `Null` if it isn't
+        pub(crate) fn json(&self) -> serde_json::Value {
+            serde_json::from_str(&self.body).unwrap_or_default()
+        }
+    }
+
+    /// a listener on a free local port, with the url of `path` on it
+    pub(crate) async fn listen(path: &str) -> (TcpListener, Url) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{path}", listener.local_addr().unwrap())
+            .parse()
+            .unwrap();
+        (listener, url)
+    }
+
+    /// Answer `requests` requests in turn, each with the status and JSON body (empty for none)
+    /// `respond` gives for it, returning the requests received
+    pub(crate) async fn serve<F>(
+        listener: TcpListener,
+        requests: usize,
+        mut respond: F,
+    ) -> Vec<Request>
+    where
+        F: FnMut(&Request) -> (u16, String),
+    {
+        let mut received = Vec::with_capacity(requests);
+        for _ in 0..requests {
+            let (mut conn, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut conn).await;
+            let (status, body) = respond(&request);
+            let reason = StatusCode::from_u16(status)
+                .ok()
+                .and_then(|status| status.canonical_reason())
+                .unwrap_or_default();
+            let content_type = if body.is_empty() {
+                ""
+            } else {
+                "content-type: application/json\r\n"
+            };
+            let resp = format!(
+                "HTTP/1.1 {status} {reason}\r\n{content_type}content-length: {}\r\n\
+                 connection: close\r\n\r\n{body}",
+                body.len()
+            );
+            conn.write_all(resp.as_bytes()).await.unwrap();
+            received.push(request);
+        }
+        received
+    }
+
+    /// read a full request, with headers and a `content-length` body
+    async fn read_request(conn: &mut TcpStream) -> Request {
+        let mut buf = Vec::new();
+        loop {
+            let mut chunk = [0; 4096];
+            let n = conn.read(&mut chunk).await.unwrap();
+            assert!(n > 0, "connection closed mid-request");
+            buf.extend_from_slice(&chunk[..n]);
+            let req = String::from_utf8_lossy(&buf);
+            if let Some((head, body)) = req.split_once("\r\n\r\n") {
+                let len = head
+                    .lines()
+                    .find_map(|l| {
+                        l.to_lowercase()
+                            .strip_prefix("content-length: ")?
+                            .parse()
+                            .ok()
+                    })
+                    .unwrap_or(0);
+                if body.len() >= len {
+                    return Request {
+                        head: head.to_string(),
+                        body: body.to_string(),
+                    };
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MemberGenerator;
+    use std::net::{Ipv4Addr, SocketAddr};
+
+    #[test]
+    fn test_member_generator() {
+        let members = MemberGenerator::seeded(7).members(4);
+        assert_eq!(members, MemberGenerator::seeded(7).members(4));
+        assert_ne!(members, MemberGenerator::seeded(8).members(4));
+        assert_ne!(members[0], members[1]);
+        assert!(members.iter().all(|m| m.sigKey.len() == 32));
+
+        let (lo, hi) = (Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 9));
+        let mut generator = MemberGenerator::seeded(1)
+            .with_ips(lo..=hi)
+            .with_ports(9000..=9009);
+        for member in generator.members(20) {
+            assert!(member.validate().is_ok());
+            for addr in [&member.networkAddress, &member.batchPosterAddress] {
+                let SocketAddr::V4(addr) = addr.parse().unwrap() else {
+                    panic!("not an IPv4 address: {addr}");
+                };
+                assert!((lo..=hi).contains(addr.ip()));
+                assert!((9000..=9009).contains(&addr.port()));
+            }
+        }
+
+        let member = MemberGenerator::seeded(1)
+            .with_key_len(48)
+            .with_dkg_key_len(0)
+            .member();
+        assert_eq!((member.sigKey.len(), member.dhKey.len()), (48, 48));
+        assert!(member.dkgKey.is_empty());
+        assert!(member.validate().is_err());
+    }
+}
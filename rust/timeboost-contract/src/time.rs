@@ -0,0 +1,10 @@
+//! Timers that also work on wasm32, where tokio's have no driver and `std::time::Instant`
+//! panics, so the browser's clock and `setTimeout` stand in
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use tokio::time::{Instant, sleep, timeout, timeout_at};
+#[cfg(target_arch = "wasm32")]
+pub(crate) use wasmtimer::{
+    std::Instant,
+    tokio::{sleep, timeout_at},
+};
@@ -0,0 +1,116 @@
+//! Human readable committee history
+
+use std::fmt::Write;
+
+use crate::{CommitteeMemberSol, CommitteeSol};
+
+/// A chronological timeline of `committees`, one entry per committee with its id, effective
+/// time, size, time since the previous committee and the membership changes relative to it.
+///
+/// Committees are ordered by effective timestamp (then id), whatever the input order.
+pub fn format_committee_timeline(committees: &[CommitteeSol]) -> String {
+    let mut sorted = committees.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|c| (c.effectiveTimestamp, c.id));
+
+    let mut out = String::new();
+    let mut previous: Option<&CommitteeSol> = None;
+    for committee in sorted {
+        let size = committee.members.len();
+        let _ = write!(
+            out,
+            "committee {}: effective at {}",
+            committee.id, committee.effectiveTimestamp
+        );
+        if let Some(prev) = previous {
+            let elapsed = committee.effectiveTimestamp - prev.effectiveTimestamp;
+            let _ = write!(out, " ({} after previous)", format_elapsed(elapsed));
+        }
+        let _ = writeln!(out, ", {size} member{}", if size == 1 { "" } else { "s" });
+
+        if let Some(prev) = previous {
+            let diff = committee.diff(prev);
+            if diff.is_empty() {
+                let _ = writeln!(out, "  (membership unchanged)");
+            }
+            for member in &diff.added {
+                let _ = writeln!(out, "  + {}", describe(member));
+            }
+            for member in &diff.removed {
+                let _ = writeln!(out, "  - {}", describe(member));
+            }
+            for (_, member) in &diff.changed {
+                let _ = writeln!(out, "  ~ {}", describe(member));
+            }
+        }
+        previous = Some(committee);
+    }
+    out
+}
+
+fn describe(member: &CommitteeMemberSol) -> String {
+    format!("{} at {}", member.sigKeyAddress, member.networkAddress)
+}
+
+/// `secs` in its largest whole unit, e.g. "3 days" or "1 minute"
+fn format_elapsed(secs: u64) -> String {
+    let (n, unit) = match secs {
+        86400.. => (secs / 86400, "day"),
+        3600.. => (secs / 3600, "hour"),
+        60.. => (secs / 60, "minute"),
+        _ => (secs, "second"),
+    };
+    format!("{n} {unit}{}", if n == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_committee_timeline, format_elapsed};
+    use crate::{CommitteeMemberSol, CommitteeSol};
+    use alloy::primitives::{Address, U256};
+
+    fn member(i: u8) -> CommitteeMemberSol {
+        CommitteeMemberSol {
+            sigKeyAddress: Address::with_last_byte(i),
+            networkAddress: format!("10.0.0.{i}:8000"),
+            ..CommitteeMemberSol::random()
+        }
+    }
+
+    #[test]
+    fn test_format_committee_timeline() {
+        let (a, b, c) = (member(1), member(2), member(3));
+        let committee = |id, ts, members: Vec<CommitteeMemberSol>| CommitteeSol {
+            id,
+            effectiveTimestamp: ts,
+            registeredBlockNumber: U256::ZERO,
+            members,
+        };
+        let committees = [
+            committee(1, 1000 + 3 * 86400, vec![b.clone(), c.clone()]),
+            committee(0, 1000, vec![a.clone(), b.clone()]),
+            committee(2, 1060 + 3 * 86400, vec![b, c]),
+        ];
+        let expected = format!(
+            "committee 0: effective at 1000, 2 members\n\
+             committee 1: effective at {} (3 days after previous), 2 members\n  \
+             + {} at 10.0.0.3:8000\n  \
+             - {} at 10.0.0.1:8000\n\
+             committee 2: effective at {} (1 minute after previous), 2 members\n  \
+             (membership unchanged)\n",
+            1000 + 3 * 86400,
+            Address::with_last_byte(3),
+            Address::with_last_byte(1),
+            1060 + 3 * 86400,
+        );
+        assert_eq!(format_committee_timeline(&committees), expected);
+        assert_eq!(format_committee_timeline(&[]), "");
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(0), "0 seconds");
+        assert_eq!(format_elapsed(59), "59 seconds");
+        assert_eq!(format_elapsed(7200), "2 hours");
+        assert_eq!(format_elapsed(86400 + 3600), "1 day");
+    }
+}
@@ -0,0 +1,192 @@
+//! Committee updates routed through an OpenZeppelin `TimelockController`
+//!
+//! The timelock must be the KeyManager's manager. An update is first scheduled, and can be executed
+//! once the timelock's delay has passed, with the exact same `(timestamp, members, salt)`.
+
+use alloy::{
+    primitives::{Address, B256, Bytes, U256, keccak256},
+    providers::Provider,
+    rpc::types::TransactionReceipt,
+    sol,
+    sol_types::{SolCall, SolValue},
+};
+use anyhow::{Result, bail};
+use tracing::info;
+
+use crate::{CommitteeMemberSol, KeyManager, telemetry};
+
+sol! {
+    /// The subset of OpenZeppelin's `TimelockController` used for committee updates
+    #[sol(rpc)]
+    interface TimelockController {
+        function schedule(address target, uint256 value, bytes calldata data, bytes32 predecessor, bytes32 salt, uint256 delay) external;
+        function execute(address target, uint256 value, bytes calldata payload, bytes32 predecessor, bytes32 salt) external payable;
+        function getMinDelay() external view returns (uint256);
+        function getTimestamp(bytes32 id) external view returns (uint256);
+        function isOperationReady(bytes32 id) external view returns (bool);
+        function isOperationDone(bytes32 id) external view returns (bool);
+    }
+}
+
+/// A committee update as a timelock operation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitteeUpdateOperation {
+    pub key_manager: Address,
+    /// ABI-encoded `setNextCommittee` call
+    pub data: Bytes,
+    /// distinguishes otherwise identical operations
+    pub salt: B256,
+}
+
+impl CommitteeUpdateOperation {
+    pub fn new(
+        key_manager: Address,
+        timestamp: u64,
+        members: Vec<CommitteeMemberSol>,
+        salt: B256,
+    ) -> Self {
+        let call = KeyManager::setNextCommitteeCall {
+            effectiveTimestamp: timestamp,
+            members,
+        };
+        Self {
+            key_manager,
+            data: call.abi_encode().into(),
+            salt,
+        }
+    }
+
+    /// operation id as computed by `TimelockController.hashOperation`, without predecessor
+    pub fn id(&self) -> B256 {
+        keccak256(
+            (
+                self.key_manager,
+                U256::ZERO,
+                self.data.clone(),
+                B256::ZERO,
+                self.salt,
+            )
+                .abi_encode_params(),
+        )
+    }
+}
+
+/// State of a scheduled operation in the timelock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperationStatus {
+    pub id: B256,
+    /// unix timestamp from which the operation can be executed, 0 if it was never scheduled
+    /// and 1 once it was executed
+    pub eta: u64,
+    pub ready: bool,
+    pub done: bool,
+}
+
+/// Current status of `op` in the timelock at `timelock`
+pub async fn committee_update_status<P: Provider>(
+    provider: &P,
+    timelock: Address,
+    op: &CommitteeUpdateOperation,
+) -> Result<OperationStatus> {
+    let timelock = TimelockController::new(timelock, provider);
+    let id = op.id();
+    let eta = timelock.getTimestamp(id).call().await?;
+    Ok(OperationStatus {
+        id,
+        eta: eta.try_into()?,
+        ready: timelock.isOperationReady(id).call().await?,
+        done: timelock.isOperationDone(id).call().await?,
+    })
+}
+
+/// Queue `op` in the timelock, executable after the timelock's minimum delay.
+/// The sender needs the timelock's proposer role.
+pub async fn schedule_committee_update<P: Provider>(
+    provider: &P,
+    timelock: Address,
+    op: &CommitteeUpdateOperation,
+) -> Result<OperationStatus> {
+    let contract = TimelockController::new(timelock, provider);
+    let delay = contract.getMinDelay().call().await?;
+    let call = contract.schedule(
+        op.key_manager,
+        U256::ZERO,
+        op.data.clone(),
+        B256::ZERO,
+        op.salt,
+        delay,
+    );
+    let receipt = telemetry::traced(timelock, call)
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    let status = committee_update_status(provider, timelock, op).await?;
+    info!(
+        id = %status.id,
+        eta = %status.eta,
+        tx = %receipt.transaction_hash,
+        "committee update scheduled"
+    );
+    Ok(status)
+}
+
+/// Execute a scheduled `op` whose delay has passed.
+/// The sender needs the timelock's executor role, unless that role is open.
+pub async fn execute_committee_update<P: Provider>(
+    provider: &P,
+    timelock: Address,
+    op: &CommitteeUpdateOperation,
+) -> Result<TransactionReceipt> {
+    let status = committee_update_status(provider, timelock, op).await?;
+    if !status.ready {
+        bail!(
+            "committee update {} is not ready (eta {}, done {})",
+            status.id,
+            status.eta,
+            status.done
+        );
+    }
+    let contract = TimelockController::new(timelock, provider);
+    let call = contract.execute(
+        op.key_manager,
+        U256::ZERO,
+        op.data.clone(),
+        B256::ZERO,
+        op.salt,
+    );
+    let receipt = telemetry::traced(timelock, call)
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    info!(id = %status.id, tx = %receipt.transaction_hash, "committee update executed");
+    Ok(receipt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommitteeUpdateOperation;
+    use crate::{CommitteeMemberSol, KeyManager};
+    use alloy::{
+        primitives::{Address, B256},
+        sol_types::SolCall,
+    };
+
+    #[test]
+    fn test_operation_id() {
+        let members = vec![CommitteeMemberSol::random()];
+        let km = Address::with_last_byte(1);
+        let op = CommitteeUpdateOperation::new(km, 100, members.clone(), B256::ZERO);
+
+        let call = KeyManager::setNextCommitteeCall::abi_decode(&op.data).unwrap();
+        assert_eq!(call.effectiveTimestamp, 100);
+        assert_eq!(call.members, members);
+
+        // the salt and the call both identify the operation
+        let salted = CommitteeUpdateOperation::new(km, 100, members.clone(), B256::repeat_byte(1));
+        assert_ne!(op.id(), salted.id());
+        let later = CommitteeUpdateOperation::new(km, 101, members, B256::ZERO);
+        assert_ne!(op.id(), later.id());
+    }
+}
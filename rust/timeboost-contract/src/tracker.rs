@@ -0,0 +1,208 @@
+//! Live committee state, kept up to date from KeyManager events
+//!
+//! A [`CommitteeTracker`] reads the committees stored in a KeyManager, then follows its
+//! `CommitteeCreated` and `CommitteesPruned` events in a background task, so that the current
+//! committee can be looked up at any time without an RPC round trip.
+
+use std::collections::BTreeMap;
+
+use alloy::{eips::BlockNumberOrTag, primitives::Address, providers::Provider, rpc::types::Log};
+use futures::{Stream, StreamExt};
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    CommitteeSol, KeyManager,
+    KeyManager::KeyManagerInstance,
+    KeyManagerEvent,
+    provider::{HttpProvider, PubSubProvider},
+    reader::{stored_committees, try_committee},
+};
+
+/// Committees by id
+pub type CommitteeMap = BTreeMap<u64, CommitteeSol>;
+
+/// The stored committees of a KeyManager, following its events, see the [module docs](self).
+///
+/// Each event is applied to the map as a whole, readers never see it half updated. Following
+/// stops when the event stream ends, i.e. after the provider's `max_retries` failed
+/// reconnections, or when the tracker is dropped.
+#[derive(Debug)]
+pub struct CommitteeTracker {
+    committees: watch::Sender<CommitteeMap>,
+    task: JoinHandle<()>,
+}
+
+impl CommitteeTracker {
+    /// Read the committees stored in the KeyManager at `contract`, then follow its events.
+    ///
+    /// The subscription is made before reading, at the latest block, so that nothing registered
+    /// in between is missed; events for committees already read are applied again, which
+    /// changes nothing.
+    pub async fn start(provider: PubSubProvider, contract: Address) -> anyhow::Result<Self> {
+        let from = provider.get_block_number().await?;
+        let events = provider
+            .multi_event_stream(contract, BlockNumberOrTag::Number(from))
+            .await?;
+        let km = KeyManager::new(contract, HttpProvider::clone(&provider));
+        let stored = stored_committees(&km).await?;
+        info!(%contract, count = stored.len(), "read stored committees");
+
+        let (committees, _) = watch::channel(stored.into_iter().map(|c| (c.id, c)).collect());
+        let task = tokio::spawn(follow(provider, km, events, committees.clone()));
+        Ok(Self { committees, task })
+    }
+
+    /// Committee `id`, `None` if it was never registered or has been pruned
+    pub fn get(&self, id: u64) -> Option<CommitteeSol> {
+        self.committees.borrow().get(&id).cloned()
+    }
+
+    /// The committee in effect at `now`, like
+    /// [`reader::current_committee`](crate::reader::current_committee)
+    pub fn current(&self, now: u64) -> Option<CommitteeSol> {
+        self.committees
+            .borrow()
+            .values()
+            .rev()
+            .find(|c| c.effectiveTimestamp <= now)
+            .cloned()
+    }
+
+    /// All stored committees
+    pub fn committees(&self) -> CommitteeMap {
+        self.committees.borrow().clone()
+    }
+
+    /// A receiver seeing the committees change, e.g. to wait for one with `wait_for`
+    pub fn subscribe(&self) -> watch::Receiver<CommitteeMap> {
+        self.committees.subscribe()
+    }
+
+    /// whether events are still being followed
+    pub fn is_following(&self) -> bool {
+        !self.task.is_finished()
+    }
+}
+
+impl Drop for CommitteeTracker {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn follow<S>(
+    // kept connected for as long as events are followed
+    _provider: PubSubProvider,
+    km: KeyManagerInstance<HttpProvider>,
+    events: S,
+    committees: watch::Sender<CommitteeMap>,
+) where
+    S: Stream<Item = Log<KeyManagerEvent>>,
+{
+    let mut events = std::pin::pin!(events);
+    while let Some(log) = events.next().await {
+        match log.data() {
+            KeyManagerEvent::CommitteeCreated(created) => {
+                let id = created.id;
+                match try_committee(&km, id).await {
+                    Ok(Some(committee)) => committees.send_modify(|map| {
+                        map.insert(id, committee);
+                    }),
+                    Ok(None) => debug!(%id, "created committee is already pruned"),
+                    Err(err) => error!(%id, %err, "failed to read created committee"),
+                }
+            }
+            KeyManagerEvent::CommitteesPruned(pruned) => {
+                let to = pruned.toId;
+                debug!(from = %pruned.fromId, %to, "committees pruned");
+                committees.send_modify(|map| map.retain(|id, _| *id > to));
+            }
+            _ => {}
+        }
+    }
+    warn!(contract = %km.address(), "committee event stream ended, no longer tracking");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommitteeTracker;
+    use crate::{
+        CommitteeMemberSol, KeyManager,
+        provider::{PubSubProvider, PubSubProviderConfig},
+    };
+    use alloy::{
+        node_bindings::Anvil,
+        providers::{ProviderBuilder, WalletProvider},
+    };
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_committee_tracker() {
+        let anvil = Anvil::new().spawn();
+        let provider = ProviderBuilder::new()
+            .wallet(anvil.wallet().unwrap())
+            .connect_http(anvil.endpoint_url());
+        let manager = provider.default_signer_address();
+        let km_addr = crate::deployer::deploy_key_manager_contract(&provider, manager)
+            .await
+            .unwrap();
+        let contract = KeyManager::new(km_addr, &provider);
+        let register = async |ts| {
+            contract
+                .setNextCommittee(ts, vec![CommitteeMemberSol::random()])
+                .send()
+                .await
+                .unwrap()
+                .get_receipt()
+                .await
+                .unwrap();
+        };
+        register(100).await;
+        register(200).await;
+
+        let pubsub = PubSubProvider::new(PubSubProviderConfig::new(anvil.ws_endpoint_url()))
+            .await
+            .unwrap();
+        let tracker = CommitteeTracker::start(pubsub, km_addr).await.unwrap();
+        assert_eq!(tracker.committees().len(), 2);
+        assert_eq!(tracker.get(1).unwrap().effectiveTimestamp, 200);
+        assert_eq!(tracker.current(150).unwrap().id, 0);
+        assert!(tracker.current(99).is_none());
+
+        let mut changes = tracker.subscribe();
+        register(300).await;
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            changes.wait_for(|map| map.contains_key(&2)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(tracker.current(u64::MAX).unwrap().id, 2);
+        assert_eq!(
+            tracker.get(2).unwrap(),
+            contract.getCommitteeById(2).call().await.unwrap()
+        );
+
+        contract
+            .pruneUntil(0)
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            changes.wait_for(|map| !map.contains_key(&0)),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(tracker.get(0).is_none());
+        assert!(tracker.current(150).is_none());
+        assert_eq!(tracker.committees().len(), 2);
+        assert!(tracker.is_following());
+    }
+}
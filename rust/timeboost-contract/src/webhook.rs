@@ -0,0 +1,302 @@
+//! Forward committee changes to an external HTTP endpoint.
+//!
+//! For every `CommitteeCreated` event, the [`WebhookDispatcher`] POSTs a JSON
+//! [`CommitteeChangePayload`] to the configured URL:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "event": "CommitteeCreated",
+//!   "contract": "0x5FbDB2315678afecb367f032d93F642f64180aa3",
+//!   "committeeId": 3,
+//!   "blockNumber": 42,
+//!   "transactionHash": "0x…"
+//! }
+//! ```
+//!
+//! The schema is stable for a given `version`, fields are only ever added along with a version
+//! bump. When a secret is configured, the request carries a `X-Timeboost-Signature: sha256=<hex>`
+//! header, the HMAC-SHA256 of the raw request body keyed with the secret.
+
+use std::{pin::pin, time::Duration};
+
+use alloy::{
+    eips::BlockNumberOrTag,
+    hex,
+    primitives::{Address, TxHash},
+    rpc::types::Log,
+    transports::http::reqwest::{self, StatusCode, Url, header::CONTENT_TYPE},
+};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::{error, info, warn};
+
+use crate::{KeyManager::CommitteeCreated, provider::PubSubProvider};
+
+/// Current version of the [`CommitteeChangePayload`] schema
+pub const PAYLOAD_VERSION: u8 = 1;
+
+/// Header carrying the HMAC-SHA256 signature of the request body
+pub const SIGNATURE_HEADER: &str = "X-Timeboost-Signature";
+
+/// JSON body POSTed for each committee change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CommitteeChangePayload {
+    pub version: u8,
+    pub event: String,
+    #[serde(with = "crate::address_hex")]
+    pub contract: Address,
+    pub committee_id: u64,
+    pub block_number: Option<u64>,
+    pub transaction_hash: Option<TxHash>,
+}
+
+impl CommitteeChangePayload {
+    pub fn from_log(log: &Log<CommitteeCreated>) -> Self {
+        Self {
+            version: PAYLOAD_VERSION,
+            event: "CommitteeCreated".to_string(),
+            contract: log.address(),
+            committee_id: log.data().id,
+            block_number: log.block_number,
+            transaction_hash: log.transaction_hash,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct WebhookConfig {
+    pub url: Url,
+    /// HMAC key for signing payloads, unsigned if `None`
+    pub secret: Option<Vec<u8>>,
+    pub max_retries: u32,
+    /// wait before the first retry, doubled after each failed attempt
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// of each attempt, from connecting until the response is read
+    pub request_timeout: Duration,
+}
+
+impl WebhookConfig {
+    pub fn new(url: Url) -> Self {
+        Self {
+            url,
+            secret: None,
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+
+    pub fn with_secret(mut self, secret: impl Into<Vec<u8>>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+}
+
+/// POSTs committee changes to a webhook, retrying with exponential backoff on connection
+/// failures, timeouts and `429` or `5xx` responses.
+pub struct WebhookDispatcher {
+    client: reqwest::Client,
+    cfg: WebhookConfig,
+}
+
+impl WebhookDispatcher {
+    pub fn new(cfg: WebhookConfig) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            cfg,
+        }
+    }
+
+    /// deliver a single payload, giving up after `max_retries` failed retries or right away on
+    /// a response the endpoint won't answer differently, e.g. a `400` or `401`
+    pub async fn dispatch(&self, payload: &CommitteeChangePayload) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(payload)?;
+        let mut backoff = self.cfg.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            let mut req = self
+                .client
+                .post(self.cfg.url.clone())
+                .header(CONTENT_TYPE, "application/json")
+                .timeout(self.cfg.request_timeout)
+                .body(body.clone());
+            if let Some(secret) = &self.cfg.secret {
+                req = req.header(SIGNATURE_HEADER, sign_payload(secret, &body));
+            }
+
+            match req.send().await.and_then(|r| r.error_for_status()) {
+                Ok(_) => {
+                    info!(committee = %payload.committee_id, "webhook delivered");
+                    return Ok(());
+                }
+                Err(err) if attempt < self.cfg.max_retries && is_retryable(&err) => {
+                    warn!(%err, %attempt, ?backoff, "webhook delivery failed, retrying");
+                    crate::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.cfg.max_backoff);
+                    attempt += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// subscribe to `CommitteeCreated` on `contract` and dispatch each event, until the stream
+    /// ends. A payload that can't be delivered is logged and skipped.
+    pub async fn run(
+        &self,
+        pubsub: &PubSubProvider,
+        contract: Address,
+        from_block: BlockNumberOrTag,
+    ) -> anyhow::Result<()> {
+        let mut events = pin!(
+            pubsub
+                .event_stream::<CommitteeCreated>(contract, from_block)
+                .await?
+        );
+        while let Some(log) = events.next().await {
+            let payload = CommitteeChangePayload::from_log(&log);
+            if let Err(err) = self.dispatch(&payload).await {
+                error!(%err, committee = %payload.committee_id, "dropping webhook payload");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// whether another attempt may succeed where this one failed
+fn is_retryable(err: &reqwest::Error) -> bool {
+    match err.status() {
+        Some(status) => status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error(),
+        // `is_request` covers e.g. the connection being reset mid-request
+        None => err.is_connect() || err.is_timeout() || err.is_request(),
+    }
+}
+
+/// Value of the [`SIGNATURE_HEADER`] for `body`: `sha256=` followed by the hex HMAC-SHA256
+pub fn sign_payload(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CommitteeChangePayload, PAYLOAD_VERSION, WebhookConfig, WebhookDispatcher, sign_payload,
+    };
+    use crate::test_util::http;
+    use alloy::{
+        primitives::Address,
+        transports::http::reqwest::{self, Url},
+    };
+    use std::time::Duration;
+    use tokio::{net::TcpListener, task::JoinHandle};
+
+    #[test]
+    fn test_sign_payload() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign_payload(b"Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    /// answer the requests with `statuses` in turn, handing back those received
+    fn serve(listener: TcpListener, statuses: Vec<u16>) -> JoinHandle<Vec<http::Request>> {
+        let requests = statuses.len();
+        let mut statuses = statuses.into_iter();
+        tokio::spawn(http::serve(listener, requests, move |_| {
+            (statuses.next().unwrap(), String::new())
+        }))
+    }
+
+    fn payload() -> CommitteeChangePayload {
+        CommitteeChangePayload {
+            version: PAYLOAD_VERSION,
+            event: "CommitteeCreated".to_string(),
+            contract: Address::with_last_byte(1),
+            committee_id: 3,
+            block_number: Some(42),
+            transaction_hash: None,
+        }
+    }
+
+    async fn listen() -> (TcpListener, Url) {
+        http::listen("/hook").await
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_retries_until_delivered() {
+        let (listener, url) = listen().await;
+        // throttle, fail, then accept
+        let statuses = vec![429, 503, 200];
+        let server = serve(listener, statuses);
+
+        let mut cfg = WebhookConfig::new(url).with_secret("s3cret");
+        cfg.initial_backoff = Duration::from_millis(10);
+        let dispatcher = WebhookDispatcher::new(cfg);
+        let payload = payload();
+        dispatcher.dispatch(&payload).await.unwrap();
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.len(), 3);
+        let request = &requests[2];
+        let body = serde_json::to_vec(&payload).unwrap();
+        let signature = sign_payload(b"s3cret", &body);
+        assert!(
+            request
+                .head
+                .to_lowercase()
+                .contains(&format!("x-timeboost-signature: {signature}"))
+        );
+        assert!(request.body.contains("\"committeeId\":3"));
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_gives_up_on_client_errors() {
+        let (listener, url) = listen().await;
+        let server = serve(listener, vec![400]);
+        let mut cfg = WebhookConfig::new(url);
+        cfg.initial_backoff = Duration::from_millis(10);
+        let err = WebhookDispatcher::new(cfg)
+            .dispatch(&payload())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("400"), "{err}");
+        assert_eq!(server.await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_request_timeout() {
+        let (listener, url) = listen().await;
+        // accept connections but never answer
+        let server = tokio::spawn(async move {
+            let mut conns = Vec::new();
+            loop {
+                conns.push(listener.accept().await.unwrap().0);
+            }
+        });
+        let mut cfg = WebhookConfig::new(url).with_request_timeout(Duration::from_millis(100));
+        cfg.initial_backoff = Duration::from_millis(10);
+        cfg.max_retries = 1;
+        let err = WebhookDispatcher::new(cfg)
+            .dispatch(&payload())
+            .await
+            .unwrap_err();
+        let err = err.downcast::<reqwest::Error>().unwrap();
+        assert!(err.is_timeout(), "{err}");
+        server.abort();
+    }
+}
@@ -0,0 +1,1005 @@
+//! Helpers for sending write transactions to a deployed KeyManager
+
+use std::{fmt, net::Ipv4Addr, time::Duration};
+
+use alloy::{
+    consensus::Transaction as _,
+    contract::{Error as ContractError, RawCallBuilder},
+    primitives::{Address, Bytes, TxHash, U256},
+    providers::{PendingTransactionError, Provider, WalletProvider},
+    rpc::types::TransactionReceipt,
+    transports::{RpcError, TransportErrorKind},
+};
+use rand::Rng;
+use tracing::{info, warn};
+
+use crate::{
+    CommitteeMemberSol, DH_KEY_LEN, DKG_KEY_LEN,
+    KeyManager::{CommitteeCreated, KeyManagerErrors, KeyManagerInstance},
+    SIG_KEY_LEN,
+    client::{ClientError, ClientResult, receipt_within},
+    telemetry,
+};
+
+/// Result of a transaction bounded by a block deadline
+#[derive(Debug, Clone)]
+pub enum DeadlineOutcome {
+    /// the transaction was mined, receipt of the original transaction
+    Mined(TransactionReceipt),
+    /// the deadline passed and the nonce was consumed by a cancellation, receipt of the
+    /// cancellation
+    Cancelled(TransactionReceipt),
+    /// neither the transaction nor its cancellation was mined by the cancellation deadline, either
+    /// may still land
+    Unresolved {
+        tx_hash: TxHash,
+        cancel_hash: TxHash,
+    },
+}
+
+/// Replace a pending transaction with a zero-value self-transfer at the same nonce, with fees
+/// bumped by 20% so that nodes accept the replacement.
+/// Returns the hash of the cancellation transaction.
+///
+/// Only a transaction of the provider's signer can be replaced: one sent by another account
+/// fails with [`ClientError::ForeignSender`], and one that is already mined with
+/// [`ClientError::AlreadyMined`].
+pub async fn cancel_transaction<P>(provider: &P, tx_hash: TxHash) -> ClientResult<TxHash>
+where
+    P: Provider + WalletProvider,
+{
+    let tx = provider
+        .get_transaction_by_hash(tx_hash)
+        .await
+        .map_err(ContractError::from)?
+        .ok_or_else(|| {
+            PendingTransactionError::TransportError(TransportErrorKind::custom_str(
+                "transaction to cancel not found",
+            ))
+        })?;
+    if tx.block_number.is_some() {
+        return Err(ClientError::AlreadyMined(tx_hash));
+    }
+    let from = provider.default_signer_address();
+    let sender = tx.inner.signer();
+    if sender != from {
+        return Err(ClientError::ForeignSender {
+            tx_hash,
+            from: sender,
+        });
+    }
+    let bump = |fee| bump_fee(fee, 20);
+
+    let cancel = RawCallBuilder::new_raw(provider, Bytes::new())
+        .from(from)
+        .to(from)
+        .value(U256::ZERO)
+        .nonce(tx.nonce());
+    let cancel = match tx.max_priority_fee_per_gas() {
+        Some(priority_fee) => cancel
+            .max_fee_per_gas(bump(tx.max_fee_per_gas()))
+            .max_priority_fee_per_gas(bump(priority_fee)),
+        None => cancel.gas_price(bump(tx.max_fee_per_gas())),
+    };
+
+    let pending = telemetry::traced(from, cancel).send().await?;
+    info!(%tx_hash, cancel_tx = %pending.tx_hash(), nonce = %tx.nonce(), "cancelling transaction");
+    Ok(*pending.tx_hash())
+}
+
+/// Register the next committee, but only if the transaction is mined no later than
+/// `deadline_block`.
+///
+/// If the deadline passes without the transaction being mined, it is cancelled via
+/// [`cancel_transaction`] so that the committee update cannot land at a later time. Either of the
+/// two is waited for until `cancel_deadline_block`, after that the outcome is
+/// [`DeadlineOutcome::Unresolved`].
+pub async fn set_next_committee_with_deadline<P>(
+    contract: &KeyManagerInstance<P>,
+    timestamp: u64,
+    members: Vec<CommitteeMemberSol>,
+    deadline_block: u64,
+    cancel_deadline_block: u64,
+) -> ClientResult<DeadlineOutcome>
+where
+    P: Provider + WalletProvider,
+{
+    let provider = contract.provider();
+    let call = contract.setNextCommittee(timestamp, members);
+    let pending = telemetry::traced(*contract.address(), call).send().await?;
+    let tx_hash = *pending.tx_hash();
+    info!(%tx_hash, %deadline_block, "committee update sent");
+
+    if let Some(receipt) = wait_for_receipt_until(provider, tx_hash, deadline_block).await? {
+        return Ok(DeadlineOutcome::Mined(receipt));
+    }
+
+    warn!(%tx_hash, %deadline_block, "committee update missed its deadline");
+    let cancel_hash = match cancel_transaction(provider, tx_hash).await {
+        Ok(hash) => hash,
+        Err(err) => {
+            // the original may have been mined in the meantime, making the nonce stale
+            if let Some(receipt) = receipt(provider, tx_hash).await? {
+                return Ok(DeadlineOutcome::Mined(receipt));
+            }
+            warn!(%tx_hash, %err, "failed to cancel committee update");
+            return Err(err);
+        }
+    };
+
+    loop {
+        // checked before the receipts, they may have landed in the deadline block itself
+        let past_deadline = block_number(provider).await? >= cancel_deadline_block;
+        if let Some(receipt) = receipt(provider, tx_hash).await? {
+            return Ok(DeadlineOutcome::Mined(receipt));
+        }
+        if let Some(receipt) = receipt(provider, cancel_hash).await? {
+            return Ok(DeadlineOutcome::Cancelled(receipt));
+        }
+        if past_deadline {
+            warn!(%tx_hash, %cancel_hash, %cancel_deadline_block, "cancellation missed its deadline");
+            return Ok(DeadlineOutcome::Unresolved {
+                tx_hash,
+                cancel_hash,
+            });
+        }
+        crate::time::sleep(provider.client().poll_interval()).await;
+    }
+}
+
+/// Gas that registering the next committee would use if sent now by the provider's signer,
+/// without sending it. If it would revert, e.g. because the signer isn't the manager or the
+/// timestamp doesn't increase, this fails with the reason: a [`ClientError::Reverted`] for the
+/// KeyManager's own errors, see also [`ClientError::revert_reason`].
+pub async fn estimate_set_next_committee<P>(
+    contract: &KeyManagerInstance<P>,
+    timestamp: u64,
+    members: Vec<CommitteeMemberSol>,
+) -> ClientResult<u64>
+where
+    P: Provider + WalletProvider,
+{
+    let from = contract.provider().default_signer_address();
+    estimate_set_next_committee_from(contract, from, timestamp, members).await
+}
+
+async fn estimate_set_next_committee_from<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    from: Address,
+    timestamp: u64,
+    members: Vec<CommitteeMemberSol>,
+) -> ClientResult<u64> {
+    contract
+        .setNextCommittee(timestamp, members)
+        .from(from)
+        .estimate_gas()
+        .await
+        .map_err(ClientError::from)
+}
+
+/// Gas that registering a committee of `member_count` members would use on the KeyManager at
+/// `proxy`, estimated like [`estimate_set_next_committee`] with random members and the latest
+/// possible effective timestamp, without sending anything. Fails like it, e.g. for a zero
+/// `member_count` or a signer that isn't the manager.
+///
+/// The members have keys of the expected lengths and IPv4 addresses; actual gas varies with the
+/// size of the members' fields, e.g. longer hostnames cost more, so leave some headroom.
+pub async fn estimate_committee_gas<P>(
+    provider: &P,
+    proxy: Address,
+    member_count: usize,
+) -> ClientResult<u64>
+where
+    P: Provider + WalletProvider,
+{
+    let from = provider.default_signer_address();
+    let contract = KeyManagerInstance::new(proxy, provider);
+    let members = (0..member_count).map(|_| sample_member()).collect();
+    estimate_set_next_committee_from(&contract, from, u64::MAX, members).await
+}
+
+/// a member with random keys and addresses of realistic sizes
+fn sample_member() -> CommitteeMemberSol {
+    let mut rng = rand::rng();
+    let mut key = |len| Bytes::from_iter((0..len).map(|_| rng.random::<u8>()));
+    let (sig_key, dh_key, dkg_key) = (key(SIG_KEY_LEN), key(DH_KEY_LEN), key(DKG_KEY_LEN));
+    let sig_key_address = Address::from(rng.random::<[u8; 20]>());
+    let mut endpoint = || {
+        let ip = Ipv4Addr::from(rng.random::<u32>());
+        format!("{ip}:{}", rng.random_range(1..=u16::MAX))
+    };
+    CommitteeMemberSol {
+        sigKey: sig_key,
+        dhKey: dh_key,
+        dkgKey: dkg_key,
+        sigKeyAddress: sig_key_address,
+        networkAddress: endpoint(),
+        batchPosterAddress: endpoint(),
+    }
+}
+
+/// When and how far [`set_next_committee_with_fee_bumps`] bumps the fees of a stuck transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeBumpConfig {
+    /// how long an attempt may stay pending before it is replaced
+    pub stuck_after: Duration,
+    /// replacements sent at most, the last one is waited for another `stuck_after`
+    pub max_bumps: u32,
+    /// fee increase of each replacement in percent, nodes commonly require at least 10
+    pub bump_percent: u32,
+}
+
+impl Default for FeeBumpConfig {
+    fn default() -> Self {
+        Self {
+            stuck_after: Duration::from_secs(60),
+            max_bumps: 3,
+            bump_percent: 20,
+        }
+    }
+}
+
+impl FeeBumpConfig {
+    pub fn with_stuck_after(mut self, d: Duration) -> Self {
+        self.stuck_after = d;
+        self
+    }
+
+    pub fn with_max_bumps(mut self, n: u32) -> Self {
+        self.max_bumps = n;
+        self
+    }
+
+    pub fn with_bump_percent(mut self, p: u32) -> Self {
+        self.bump_percent = p;
+        self
+    }
+}
+
+/// Register the next committee, replacing the transaction with one paying higher fees whenever
+/// it stays pending for `cfg.stuck_after`.
+///
+/// Each replacement has the nonce, gas limit and calldata of the original and fees bumped by
+/// `cfg.bump_percent` over the previous attempt. A replacement rejected as underpriced is retried
+/// with the next bump right away, without waiting another `cfg.stuck_after`. Returns the receipt
+/// of whichever attempt is mined, or fails with [`ClientError::Failed`] if it reverted and with
+/// [`ClientError::Timeout`] (for the last attempt) once `cfg.max_bumps` replacements went
+/// unmined.
+pub async fn set_next_committee_with_fee_bumps<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    timestamp: u64,
+    members: Vec<CommitteeMemberSol>,
+    cfg: FeeBumpConfig,
+) -> ClientResult<TransactionReceipt> {
+    let provider = contract.provider();
+    let call = || contract.setNextCommittee(timestamp, members.clone());
+    let pending = telemetry::traced(*contract.address(), call())
+        .send()
+        .await?;
+    let mut sent = vec![*pending.tx_hash()];
+    // the nonce, gas and fees the provider filled in, each replacement builds on them
+    let tx = provider
+        .get_transaction_by_hash(sent[0])
+        .await
+        .map_err(ContractError::from)?
+        .ok_or_else(|| {
+            PendingTransactionError::TransportError(TransportErrorKind::custom_str(
+                "sent committee update not found",
+            ))
+        })?;
+    let (nonce, gas) = (tx.nonce(), tx.gas_limit());
+    let mut fees = (tx.max_fee_per_gas(), tx.max_priority_fee_per_gas());
+
+    let mut bumps = 0;
+    // after an underpriced replacement only check once, the attempts have been pending long enough
+    let mut wait = cfg.stuck_after;
+    loop {
+        if let Some(receipt) = receipt_of_any(provider, &sent, wait).await? {
+            if !receipt.status() {
+                return Err(ClientError::Failed(receipt.transaction_hash));
+            }
+            info!(tx_hash = %receipt.transaction_hash, %bumps, "committee update mined");
+            return Ok(receipt);
+        }
+        if bumps == cfg.max_bumps {
+            return Err(ClientError::Timeout(sent[sent.len() - 1]));
+        }
+        bumps += 1;
+        fees = (
+            bump_fee(fees.0, cfg.bump_percent),
+            fees.1.map(|fee| bump_fee(fee, cfg.bump_percent)),
+        );
+        let replacement = match fees {
+            (max_fee, Some(priority_fee)) => call()
+                .max_fee_per_gas(max_fee)
+                .max_priority_fee_per_gas(priority_fee),
+            (gas_price, None) => call().gas_price(gas_price),
+        };
+        let replacement = replacement.nonce(nonce).gas(gas);
+        match telemetry::traced(*contract.address(), replacement)
+            .send()
+            .await
+        {
+            Ok(pending) => {
+                warn!(%nonce, %bumps, tx_hash = %pending.tx_hash(), "replaced stuck committee update");
+                sent.push(*pending.tx_hash());
+                wait = cfg.stuck_after;
+            }
+            // not enough of a bump for this node, the next one is larger
+            Err(err) if rejected_with(&err, "underpriced") => {
+                warn!(%nonce, %bumps, "committee update replacement underpriced");
+                if bumps == cfg.max_bumps {
+                    return Err(ClientError::Timeout(sent[sent.len() - 1]));
+                }
+                wait = Duration::ZERO;
+            }
+            // an earlier attempt was mined in the meantime, the next poll finds it
+            Err(err) if rejected_with(&err, "nonce too low") => wait = cfg.stuck_after,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+/// Why [`replace_pending_committee_member`] refused or failed
+#[derive(Debug)]
+pub enum PendingCommitteeError {
+    /// committees were registered after this one, a replacement would only start after them
+    NotLatest {
+        id: u64,
+        next_id: u64,
+    },
+    /// the committee is (or was) in effect at the latest block, it's too late to correct it
+    AlreadyEffective {
+        id: u64,
+        effective_timestamp: u64,
+    },
+    /// there's no member at `index` to replace
+    NoSuchMember {
+        index: usize,
+        len: usize,
+    },
+    Client(ClientError),
+}
+
+impl fmt::Display for PendingCommitteeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotLatest { id, next_id } => {
+                write!(f, "committee {id} is not the latest, next id is {next_id}")
+            }
+            Self::AlreadyEffective {
+                id,
+                effective_timestamp,
+            } => write!(
+                f,
+                "committee {id} is already effective since {effective_timestamp}"
+            ),
+            Self::NoSuchMember { index, len } => {
+                write!(f, "no member {index} in a committee of {len}")
+            }
+            Self::Client(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PendingCommitteeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Client(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<ClientError> for PendingCommitteeError {
+    fn from(err: ClientError) -> Self {
+        Self::Client(err)
+    }
+}
+
+impl From<alloy::contract::Error> for PendingCommitteeError {
+    fn from(err: alloy::contract::Error) -> Self {
+        Self::Client(err.into())
+    }
+}
+
+impl From<PendingTransactionError> for PendingCommitteeError {
+    fn from(err: PendingTransactionError) -> Self {
+        Self::Client(err.into())
+    }
+}
+
+/// Correct member `index` of the latest committee `id` before it becomes effective.
+///
+/// The KeyManager never changes a registered committee, and `setNextCommittee` requires a later
+/// effective timestamp than the latest committee's. So rather than overwriting committee `id`, this
+/// registers a copy with `member` in place of member `index` as committee `id + 1`, effective
+/// from `effective_timestamp`. Committee `id` stays registered and, unless `effective_timestamp`
+/// is the earliest possible (one second after its own), is still in effect in between.
+///
+/// Refuses if committee `id` is in effect at the latest block according to the contract, which
+/// is final only once mined: a committee that becomes effective before the replacement lands is
+/// replaced all the same. With a `receipt_timeout`, a replacement not mined within it fails with
+/// [`ClientError::Timeout`].
+pub async fn replace_pending_committee_member<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    id: u64,
+    index: usize,
+    member: CommitteeMemberSol,
+    effective_timestamp: u64,
+    receipt_timeout: Option<Duration>,
+) -> Result<TransactionReceipt, PendingCommitteeError> {
+    let next_id = contract.nextCommitteeId().call().await?;
+    // no committee follows `u64::MAX`, so it can't be the latest one registered
+    if id.checked_add(1) != Some(next_id) {
+        return Err(PendingCommitteeError::NotLatest { id, next_id });
+    }
+    let committee = contract.getCommitteeById(id).call().await?;
+    let effective = match contract.currentCommitteeId().call().await {
+        Ok(current) => current >= id,
+        Err(err) => match ClientError::from(err) {
+            ClientError::Reverted(KeyManagerErrors::NoCommitteeScheduled(_)) => false,
+            err => return Err(err.into()),
+        },
+    };
+    if effective {
+        return Err(PendingCommitteeError::AlreadyEffective {
+            id,
+            effective_timestamp: committee.effectiveTimestamp,
+        });
+    }
+
+    let mut members = committee.members;
+    let len = members.len();
+    *members
+        .get_mut(index)
+        .ok_or(PendingCommitteeError::NoSuchMember { index, len })? = member;
+    let call = contract.setNextCommittee(effective_timestamp, members);
+    let pending = telemetry::traced(*contract.address(), call).send().await?;
+    let receipt = receipt_within(pending, receipt_timeout).await?;
+    info!(%id, %index, %effective_timestamp, "registered corrected committee");
+    Ok(receipt)
+}
+
+/// A committee registered by [`set_next_committees`]
+#[derive(Debug, Clone)]
+pub struct RegisteredCommittee {
+    pub id: u64,
+    pub receipt: TransactionReceipt,
+}
+
+/// Entry of [`set_next_committees`] that failed, stopping the sequence
+#[derive(Debug)]
+pub struct FailedCommittee {
+    /// position of the entry in the given committees
+    pub index: usize,
+    /// with [`ClientError::Pending`] or [`ClientError::Timeout`] the transaction was sent and may
+    /// still be mined, check before sending the entry again
+    pub error: ClientError,
+}
+
+/// How far [`set_next_committees`] got: the entries before [`Self::next_index`] are registered,
+/// none after it were sent
+#[derive(Debug)]
+#[must_use]
+pub struct BatchRegistration {
+    /// the registered committees, one per entry from the first on, in order
+    pub registered: Vec<RegisteredCommittee>,
+    /// the entry that failed, `None` if all were registered
+    pub failed: Option<FailedCommittee>,
+}
+
+impl BatchRegistration {
+    /// whether every entry was registered
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_none()
+    }
+
+    /// index of the first entry not registered, from which on to resume
+    pub fn next_index(&self) -> usize {
+        self.registered.len()
+    }
+}
+
+/// Register `committees`, as `(effective timestamp, members)`, as the next committees in order.
+///
+/// The KeyManager has no batch setter (and a multicall contract can't send as the manager), so
+/// these are one `setNextCommittee` transaction each, from the provider's signer, each sent once
+/// the previous one is mined. The first one failing, e.g. reverting because its timestamp doesn't
+/// increase, stops the sequence; the outcome tells which entries are registered, so the rest can
+/// be resumed with `&committees[outcome.next_index()..]`.
+pub async fn set_next_committees<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    committees: &[(u64, Vec<CommitteeMemberSol>)],
+) -> BatchRegistration {
+    let mut registered = Vec::with_capacity(committees.len());
+    for (index, (timestamp, members)) in committees.iter().enumerate() {
+        match register_committee(contract, *timestamp, members.clone()).await {
+            Ok(committee) => {
+                info!(index, id = committee.id, "registered committee");
+                registered.push(committee);
+            }
+            Err(error) => {
+                warn!(index, %error, "committee registration failed, stopping");
+                return BatchRegistration {
+                    registered,
+                    failed: Some(FailedCommittee { index, error }),
+                };
+            }
+        }
+    }
+    BatchRegistration {
+        registered,
+        failed: None,
+    }
+}
+
+async fn register_committee<P: Provider>(
+    contract: &KeyManagerInstance<P>,
+    timestamp: u64,
+    members: Vec<CommitteeMemberSol>,
+) -> ClientResult<RegisteredCommittee> {
+    let call = contract.setNextCommittee(timestamp, members);
+    let pending = telemetry::traced(*contract.address(), call).send().await?;
+    let receipt = receipt_within(pending, None).await?;
+    let hash = receipt.transaction_hash;
+    let id = receipt
+        .decoded_log::<CommitteeCreated>()
+        .map(|log| log.data.id)
+        // mined and succeeded, but not by the KeyManager
+        .ok_or(ClientError::Failed(hash))?;
+    Ok(RegisteredCommittee { id, receipt })
+}
+
+/// `fee` raised by `percent`, and by at least one wei
+fn bump_fee(fee: u128, percent: u32) -> u128 {
+    fee.saturating_add(fee.saturating_mul(percent.into()) / 100 + 1)
+}
+
+/// whether a node refused to accept a transaction with a message containing `reason`
+fn rejected_with(err: &ContractError, reason: &str) -> bool {
+    matches!(
+        err,
+        ContractError::TransportError(RpcError::ErrorResp(resp))
+            if resp.message.to_lowercase().contains(reason)
+    )
+}
+
+/// Poll for a receipt for any of `hashes` (attempts at the same nonce) for up to `timeout`
+async fn receipt_of_any<P: Provider>(
+    provider: &P,
+    hashes: &[TxHash],
+    timeout: Duration,
+) -> ClientResult<Option<TransactionReceipt>> {
+    let deadline = crate::time::Instant::now() + timeout;
+    loop {
+        for hash in hashes {
+            let receipt = provider
+                .get_transaction_receipt(*hash)
+                .await
+                .map_err(ContractError::from)?;
+            if receipt.is_some() {
+                return Ok(receipt);
+            }
+        }
+        if crate::time::Instant::now() >= deadline {
+            return Ok(None);
+        }
+        crate::time::sleep(provider.client().poll_interval()).await;
+    }
+}
+
+/// Poll for the receipt of `tx_hash` until the chain moves past `last_block`
+async fn wait_for_receipt_until<P: Provider>(
+    provider: &P,
+    tx_hash: TxHash,
+    last_block: u64,
+) -> ClientResult<Option<TransactionReceipt>> {
+    loop {
+        if let Some(receipt) = receipt(provider, tx_hash).await? {
+            return Ok(Some(receipt));
+        }
+        if block_number(provider).await? >= last_block {
+            // a final check, the tx may have landed in the deadline block itself
+            return receipt(provider, tx_hash).await;
+        }
+        crate::time::sleep(provider.client().poll_interval()).await;
+    }
+}
+
+async fn receipt<P: Provider>(
+    provider: &P,
+    tx_hash: TxHash,
+) -> ClientResult<Option<TransactionReceipt>> {
+    Ok(provider
+        .get_transaction_receipt(tx_hash)
+        .await
+        .map_err(ContractError::from)?)
+}
+
+async fn block_number<P: Provider>(provider: &P) -> ClientResult<u64> {
+    Ok(provider
+        .get_block_number()
+        .await
+        .map_err(ContractError::from)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        DeadlineOutcome, FeeBumpConfig, PendingCommitteeError, bump_fee, cancel_transaction,
+        estimate_committee_gas, estimate_set_next_committee, replace_pending_committee_member,
+        sample_member, set_next_committee_with_deadline, set_next_committee_with_fee_bumps,
+        set_next_committees,
+    };
+    use crate::{
+        CommitteeMemberSol, KeyManager, KeyManager::KeyManagerErrors, client::ClientError,
+        deployer::deploy_key_manager_contract, error::KeyManagerError,
+    };
+    use alloy::{
+        consensus::Transaction as _,
+        eips::BlockNumberOrTag,
+        network::TransactionBuilder,
+        primitives::{Address, U256},
+        providers::{Provider, WalletProvider},
+        rpc::types::TransactionRequest,
+    };
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_set_next_committees() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let committees: Vec<_> = [100, 200, 150, 300]
+            .into_iter()
+            .map(|ts| (ts, vec![CommitteeMemberSol::random()]))
+            .collect();
+
+        // the third doesn't increase the timestamp and reverts, the fourth isn't sent
+        let outcome = set_next_committees(&contract, &committees).await;
+        assert!(!outcome.is_complete());
+        assert_eq!(outcome.next_index(), 2);
+        let ids: Vec<_> = outcome.registered.iter().map(|c| c.id).collect();
+        assert_eq!(ids, [0, 1]);
+        for committee in &outcome.registered {
+            assert!(committee.receipt.status());
+        }
+        let failed = outcome.failed.as_ref().unwrap();
+        assert_eq!(failed.index, 2);
+        assert_eq!(
+            failed.error.key_manager_error(),
+            Some(KeyManagerError::InvalidTimestamp {
+                timestamp: 150,
+                last: 200
+            })
+        );
+        assert_eq!(contract.nextCommitteeId().call().await.unwrap(), 2);
+
+        // resume with the failed entry fixed
+        let mut rest = committees[outcome.next_index()..].to_vec();
+        rest[0].0 = 250;
+        let outcome = set_next_committees(&contract, &rest).await;
+        assert!(outcome.is_complete());
+        let ids: Vec<_> = outcome.registered.iter().map(|c| c.id).collect();
+        assert_eq!(ids, [2, 3]);
+        let latest = contract.getCommitteeById(3).call().await.unwrap();
+        assert_eq!(latest.effectiveTimestamp, 300);
+        assert_eq!(latest.members, committees[3].1);
+    }
+
+    #[test]
+    fn test_sample_member() {
+        let (a, b) = (sample_member(), sample_member());
+        a.validate().unwrap();
+        assert_ne!(a.sigKey, b.sigKey);
+        assert_ne!(a.sigKeyAddress, b.sigKeyAddress);
+    }
+
+    #[tokio::test]
+    async fn test_estimate_committee_gas() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let mut gas = Vec::new();
+        for n in [1, 4, 16] {
+            gas.push(estimate_committee_gas(&provider, addr, n).await.unwrap());
+        }
+        assert!(gas.is_sorted() && gas[0] < gas[2], "{gas:?}");
+        // nothing was sent
+        let contract = KeyManager::new(addr, &provider);
+        assert_eq!(contract.nextCommitteeId().call().await.unwrap(), 0);
+
+        let err = estimate_committee_gas(&provider, addr, 0)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Reverted(KeyManagerErrors::EmptyCommitteeMembers(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_estimate_set_next_committee() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, provider.clone());
+        let members = vec![CommitteeMemberSol::random()];
+
+        let gas = estimate_set_next_committee(&contract, 100, members.clone())
+            .await
+            .unwrap();
+        assert!(gas > 21_000);
+        // nothing was sent
+        assert_eq!(contract.nextCommitteeId().call().await.unwrap(), 0);
+
+        contract
+            .setNextCommittee(100, members.clone())
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let err = estimate_set_next_committee(&contract, 100, members.clone())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Reverted(KeyManagerErrors::InvalidEffectiveTimestamp(_))
+        ));
+        assert!(
+            err.revert_reason()
+                .unwrap()
+                .contains("InvalidEffectiveTimestamp")
+        );
+
+        // a KeyManager managed by someone else
+        let other = deploy_key_manager_contract(&provider, Address::with_last_byte(1))
+            .await
+            .unwrap();
+        let err =
+            estimate_set_next_committee(&KeyManager::new(other, provider.clone()), 100, members)
+                .await
+                .unwrap_err();
+        assert!(matches!(
+            err,
+            ClientError::Reverted(KeyManagerErrors::NotManager(e))
+                if e.caller == provider.default_signer_address()
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replace_pending_committee_member() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, &provider);
+        let now = provider
+            .get_block_by_number(BlockNumberOrTag::Latest)
+            .await
+            .unwrap()
+            .unwrap()
+            .header
+            .timestamp;
+        let mine_at = async |timestamp: u64| {
+            provider
+                .raw_request::<_, ()>("evm_setNextBlockTimestamp".into(), (timestamp,))
+                .await
+                .unwrap();
+            provider
+                .raw_request::<_, String>("evm_mine".into(), ())
+                .await
+                .unwrap();
+        };
+
+        let members = vec![CommitteeMemberSol::random(), CommitteeMemberSol::random()];
+        contract
+            .setNextCommittee(now + 100, members.clone())
+            .send()
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let corrected = CommitteeMemberSol::random();
+        replace_pending_committee_member(&contract, 0, 1, corrected.clone(), now + 101, None)
+            .await
+            .unwrap();
+        let replacement = contract.getCommitteeById(1).call().await.unwrap();
+        assert_eq!(replacement.effectiveTimestamp, now + 101);
+        assert_eq!(replacement.members, [members[0].clone(), corrected.clone()]);
+        // the original is still registered
+        assert_eq!(
+            contract.getCommitteeById(0).call().await.unwrap().members,
+            members
+        );
+
+        let err =
+            replace_pending_committee_member(&contract, 0, 1, corrected.clone(), now + 102, None)
+                .await
+                .unwrap_err();
+        assert!(matches!(
+            err,
+            PendingCommitteeError::NotLatest { id: 0, next_id: 2 }
+        ));
+
+        // a second before committee 1 takes effect it's still pending ...
+        mine_at(now + 100).await;
+        let err =
+            replace_pending_committee_member(&contract, 1, 2, corrected.clone(), now + 102, None)
+                .await
+                .unwrap_err();
+        assert!(matches!(
+            err,
+            PendingCommitteeError::NoSuchMember { index: 2, len: 2 }
+        ));
+
+        // ... and from its timestamp on it's too late
+        mine_at(now + 101).await;
+        let err = replace_pending_committee_member(&contract, 1, 0, corrected, now + 102, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            PendingCommitteeError::AlreadyEffective { id: 1, effective_timestamp } if effective_timestamp == now + 101
+        ));
+        assert_eq!(contract.nextCommitteeId().call().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_committee_update_cancelled_after_deadline() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, provider);
+        let provider = contract.provider();
+
+        // stop mining so the committee update stays pending past its deadline
+        provider
+            .raw_request::<_, bool>("evm_setAutomine".into(), (false,))
+            .await
+            .unwrap();
+        let deadline = provider.get_block_number().await.unwrap();
+        let members = vec![CommitteeMemberSol::random()];
+
+        let (outcome, _) = futures::join!(
+            set_next_committee_with_deadline(&contract, 1, members, deadline, deadline + 5),
+            async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                provider
+                    .raw_request::<_, String>("evm_mine".into(), ())
+                    .await
+                    .unwrap();
+            }
+        );
+
+        match outcome.unwrap() {
+            DeadlineOutcome::Cancelled(receipt) => {
+                assert_eq!(receipt.to, Some(provider.default_signer_address()))
+            }
+            outcome => panic!("committee update should have been cancelled: {outcome:?}"),
+        }
+        assert_eq!(contract.nextCommitteeId().call().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_transaction_of_others_or_mined() {
+        use crate::provider::AnvilImpersonate;
+
+        let (provider, _) = crate::init_test_chain().await.unwrap();
+        let signer = provider.default_signer_address();
+        let transfer = TransactionRequest::default()
+            .with_to(signer)
+            .with_value(U256::from(1));
+        let mined = provider
+            .send_transaction(transfer.clone())
+            .await
+            .unwrap()
+            .get_receipt()
+            .await
+            .unwrap();
+        let err = cancel_transaction(&provider, mined.transaction_hash)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::AlreadyMined(hash) if hash == mined.transaction_hash));
+
+        // a pending transaction of another account can't be replaced by ours
+        provider
+            .raw_request::<_, bool>("evm_setAutomine".into(), (false,))
+            .await
+            .unwrap();
+        let stranger = Address::with_last_byte(0x42);
+        provider
+            .raw_request::<_, ()>(
+                "anvil_setBalance".into(),
+                (stranger, U256::from(10).pow(U256::from(18))),
+            )
+            .await
+            .unwrap();
+        let as_stranger = provider.impersonated(stranger).await.unwrap();
+        let pending = as_stranger.send_transaction(transfer).await.unwrap();
+        let err = cancel_transaction(&provider, *pending.tx_hash())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::ForeignSender { from, .. } if from == stranger));
+    }
+
+    #[tokio::test]
+    async fn test_committee_update_unresolved_after_cancel_deadline() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, provider);
+        let provider = contract.provider();
+
+        // nothing gets mined, neither the update nor its cancellation
+        provider
+            .raw_request::<_, bool>("evm_setAutomine".into(), (false,))
+            .await
+            .unwrap();
+        let deadline = provider.get_block_number().await.unwrap();
+        let members = vec![CommitteeMemberSol::random()];
+        let outcome =
+            set_next_committee_with_deadline(&contract, 1, members, deadline, deadline).await;
+        let DeadlineOutcome::Unresolved {
+            tx_hash,
+            cancel_hash,
+        } = outcome.unwrap()
+        else {
+            panic!("neither transaction should have been mined");
+        };
+        assert_ne!(tx_hash, cancel_hash);
+        let cancel = provider
+            .get_transaction_by_hash(cancel_hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cancel.to(), Some(provider.default_signer_address()));
+    }
+
+    #[test]
+    fn test_bump_fee() {
+        assert_eq!(bump_fee(100, 20), 121);
+        assert_eq!(bump_fee(0, 20), 1);
+        // saturates rather than overflowing on huge fees
+        assert!(bump_fee(u128::MAX / 2, 300) > u128::MAX / 2);
+        assert_eq!(bump_fee(u128::MAX, 20), u128::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_set_next_committee_with_fee_bumps() {
+        let (provider, addr) = crate::init_test_chain().await.unwrap();
+        let contract = KeyManager::new(addr, provider);
+        let provider = contract.provider();
+        let initial = provider.estimate_eip1559_fees().await.unwrap();
+
+        // stop mining so that the first attempts get stuck
+        provider
+            .raw_request::<_, bool>("evm_setAutomine".into(), (false,))
+            .await
+            .unwrap();
+        let members = vec![CommitteeMemberSol::random()];
+        let cfg = FeeBumpConfig::default()
+            .with_stuck_after(Duration::from_millis(300))
+            .with_max_bumps(5);
+        let (receipt, _) = futures::join!(
+            set_next_committee_with_fee_bumps(&contract, 100, members.clone(), cfg),
+            async {
+                tokio::time::sleep(Duration::from_millis(800)).await;
+                provider
+                    .raw_request::<_, String>("evm_mine".into(), ())
+                    .await
+                    .unwrap();
+            }
+        );
+
+        let receipt = receipt.unwrap();
+        assert!(receipt.status());
+        let tx = provider
+            .get_transaction_by_hash(receipt.transaction_hash)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(tx.max_priority_fee_per_gas().unwrap() > initial.max_priority_fee_per_gas);
+        let committee = contract.getCommitteeById(0).call().await.unwrap();
+        assert_eq!(committee.members, members);
+
+        // without replacements the timeout is that of the only attempt
+        let cfg = cfg.with_max_bumps(0);
+        let err = set_next_committee_with_fee_bumps(&contract, 200, members, cfg)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ClientError::Timeout(_)));
+    }
+}